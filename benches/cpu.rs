@@ -0,0 +1,97 @@
+//!
+//! Emulation throughput, both of the cpu on its own and of whole frames, run
+//! with `cargo bench --features bench`. Compare against another revision by
+//! running `cargo bench --features bench -- --save-baseline before` there
+//! first, then `cargo bench --features bench -- --baseline before`.
+//!
+
+use atari_2600_rust_web_assembly::{Atari2600, BenchCpu};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Cpu cycles run by each iteration of the cpu benchmarks
+const CPU_CYCLES: u32 = 100_000;
+
+/// Colour clocks in a frame which never strobes VSYNC, `run_frame` stops after this many
+const COLOR_CLOCKS_PER_FRAME: u64 = 312 * 228;
+
+/// Build a 4K rom with the given program at $F000 and the reset vector pointing to it
+fn rom_with_program(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0xEA; 0x1000];
+    rom[..program.len()].copy_from_slice(program);
+    rom[0xFFC] = 0x00;
+    rom[0xFFD] = 0xF0;
+    rom
+}
+
+/// Mixes most addressing modes, a subroutine call and stack accesses in a tight loop
+fn cpu_heavy_program() -> Vec<u8> {
+    let mut program = vec![
+        0xA2, 0x00, // start: LDX #$00
+        0xB5, 0x80, // loop: LDA $80,X
+        0x7D, 0x00, 0xF1, // ADC $F100,X
+        0x95, 0x80, // STA $80,X
+        0xE6, 0xC0, // INC $C0
+        0xB1, 0x90, // LDA ($90),Y
+        0x2A, // ROL A
+        0xE8, // INX
+        0xD0, 0xF1, // BNE loop
+        0x20, 0x20, 0xF0, // JSR subroutine
+        0x4C, 0x00, 0xF0, // JMP start
+    ];
+    program.resize(0x20, 0xEA);
+    program.extend_from_slice(&[
+        0x48, // subroutine: PHA
+        0x68, // PLA
+        0x60, // RTS
+    ]);
+
+    program
+}
+
+/// A typical kernel, waiting for the start of each scanline before updating the playfield
+fn kernel_rom() -> Vec<u8> {
+    rom_with_program(&[
+        0x85, 0x02, // loop: STA WSYNC
+        0x86, 0x0D, // STX PF0
+        0xE8, // INX
+        0x8A, // TXA
+        0x85, 0x09, // STA COLUBK
+        0x4C, 0x00, 0xF0, // JMP loop
+    ])
+}
+
+fn bench_cpu(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(CPU_CYCLES as u64));
+
+    let mut cpu = BenchCpu::new(&cpu_heavy_program());
+    group.bench_function("cpu_heavy", |b| b.iter(|| black_box(cpu.run(CPU_CYCLES))));
+
+    group.finish();
+}
+
+fn bench_frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(COLOR_CLOCKS_PER_FRAME));
+
+    for (name, rom) in [
+        ("cpu_heavy", rom_with_program(&cpu_heavy_program())),
+        ("kernel", kernel_rom()),
+    ] {
+        for fast_mode in [false, true] {
+            let mut system = Atari2600::new(&rom).unwrap();
+            system.set_fast_mode(fast_mode);
+            let name = if fast_mode {
+                format!("{}_fast", name)
+            } else {
+                name.to_string()
+            };
+            group.bench_function(name, |b| b.iter(|| black_box(system.run_frame())));
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_cpu, bench_frames);
+criterion_main!(benches);
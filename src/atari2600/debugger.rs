@@ -0,0 +1,508 @@
+use wasm_bindgen::prelude::*;
+
+use super::{Atari2600, ADDRESS_MASK, COLOR_CLOCKS_PER_CPU_CYCLE, MAX_COLOR_CLOCKS_PER_FRAME};
+use crate::cpu::{is_illegal_opcode, Flag, Register, RegisterSnapshot};
+
+/// Give up on a step over, step out or run to scanline after this many frames
+const MAX_STEP_FRAMES: u32 = 10;
+
+const JSR: u8 = 0x20;
+const RTI: u8 = 0x40;
+const RTS: u8 = 0x60;
+
+/// Why the system last stopped running
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// The requested frame, step or scanline was run to completion
+    Completed,
+    Breakpoint,
+    ReadWatchpoint,
+    WriteWatchpoint,
+    Opcode,
+    IllegalOpcode,
+    /// A step gave up before completing, e.g. stepping out of a subroutine which never returns
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Watchpoint {
+    start: u16,
+    end: u16,
+    on_read: bool,
+    on_write: bool,
+}
+
+///
+/// Breakpoints and watchpoints set on the system. Addresses are stored with
+/// the 6507 address mask applied, so a breakpoint on $F000 also stops at
+/// $1000 and every other mirror.
+///
+/// Everything is checked at instruction boundaries, a watchpoint hit part
+/// way through an instruction stops the system once that instruction has
+/// finished.
+///
+pub(super) struct Debugger {
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<Watchpoint>,
+    opcode_breaks: Vec<u8>,
+    break_on_illegal_opcodes: bool,
+    pending_watchpoint: Option<(StopReason, u16, u8)>,
+    last_watchpoint: Option<(u16, u8)>,
+}
+
+impl Debugger {
+    pub(super) fn new() -> Self {
+        Debugger {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            opcode_breaks: Vec::new(),
+            break_on_illegal_opcodes: false,
+            pending_watchpoint: None,
+            last_watchpoint: None,
+        }
+    }
+
+    /// Whether anything is set which could stop the system
+    pub(super) fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || !self.opcode_breaks.is_empty()
+            || self.break_on_illegal_opcodes
+    }
+
+    pub(super) fn is_watching(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    /// Called with every bus access the cpu makes while watchpoints are set
+    pub(super) fn check_access(&mut self, address: u16, value: u8, is_write: bool) {
+        if self.pending_watchpoint.is_some() {
+            return;
+        }
+
+        let hit = self.watchpoints.iter().any(|watchpoint| {
+            (watchpoint.start..=watchpoint.end).contains(&address)
+                && if is_write {
+                    watchpoint.on_write
+                } else {
+                    watchpoint.on_read
+                }
+        });
+        if hit {
+            let reason = match is_write {
+                true => StopReason::WriteWatchpoint,
+                false => StopReason::ReadWatchpoint,
+            };
+            self.pending_watchpoint = Some((reason, address, value));
+        }
+    }
+
+    /// Called at each instruction boundary with the next instruction to execute
+    pub(super) fn check_instruction(&mut self, pc: u16, opcode: u8) -> Option<StopReason> {
+        if let Some((reason, address, value)) = self.pending_watchpoint.take() {
+            self.last_watchpoint = Some((address, value));
+            return Some(reason);
+        }
+
+        if self.breakpoints.contains(&(pc & ADDRESS_MASK)) {
+            Some(StopReason::Breakpoint)
+        } else if self.opcode_breaks.contains(&opcode) {
+            Some(StopReason::Opcode)
+        } else if self.break_on_illegal_opcodes && is_illegal_opcode(opcode) {
+            Some(StopReason::IllegalOpcode)
+        } else {
+            None
+        }
+    }
+}
+
+impl Atari2600 {
+    fn pc(&self) -> u16 {
+        self.cpu.register(Register::ProgramCounter)
+    }
+
+    fn run_for_step<F: FnMut(&Atari2600, bool) -> bool>(&mut self, is_done: F) -> StopReason {
+        let (reason, _) = self.run_until(MAX_STEP_FRAMES * MAX_COLOR_CLOCKS_PER_FRAME, is_done);
+        self.stop_reason = reason;
+
+        reason
+    }
+}
+
+#[wasm_bindgen]
+impl Atari2600 {
+    /// Stop before executing the instruction at this address
+    pub fn add_breakpoint(&mut self, address: u16) {
+        let address = address & ADDRESS_MASK;
+        if !self.debugger.breakpoints.contains(&address) {
+            self.debugger.breakpoints.push(address);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.debugger
+            .breakpoints
+            .retain(|&breakpoint| breakpoint != address & ADDRESS_MASK);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.breakpoints.clear();
+    }
+
+    /// Stop after any instruction which reads and/or writes an address in `start..=end`
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, on_read: bool, on_write: bool) {
+        self.debugger.watchpoints.push(Watchpoint {
+            start: start & ADDRESS_MASK,
+            end: end & ADDRESS_MASK,
+            on_read,
+            on_write,
+        });
+    }
+
+    pub fn remove_watchpoint(&mut self, start: u16, end: u16) {
+        self.debugger.watchpoints.retain(|watchpoint| {
+            watchpoint.start != start & ADDRESS_MASK || watchpoint.end != end & ADDRESS_MASK
+        });
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.debugger.watchpoints.clear();
+        self.debugger.pending_watchpoint = None;
+    }
+
+    /// Stop before executing any instance of the given opcode
+    pub fn add_opcode_break(&mut self, opcode: u8) {
+        if !self.debugger.opcode_breaks.contains(&opcode) {
+            self.debugger.opcode_breaks.push(opcode);
+        }
+    }
+
+    pub fn remove_opcode_break(&mut self, opcode: u8) {
+        self.debugger.opcode_breaks.retain(|&op| op != opcode);
+    }
+
+    /// Stop before executing any of the undocumented opcodes
+    pub fn set_break_on_illegal_opcodes(&mut self, enabled: bool) {
+        self.debugger.break_on_illegal_opcodes = enabled;
+    }
+
+    /// Why `run_frame` or the last step stopped
+    pub fn stop_reason(&self) -> StopReason {
+        self.stop_reason
+    }
+
+    /// The address accessed when a watchpoint last stopped the system
+    pub fn watchpoint_address(&self) -> Option<u16> {
+        self.debugger.last_watchpoint.map(|(address, _)| address)
+    }
+
+    /// The value read or written when a watchpoint last stopped the system
+    pub fn watchpoint_value(&self) -> Option<u8> {
+        self.debugger.last_watchpoint.map(|(_, value)| value)
+    }
+
+    /// Run until the current instruction (or interrupt) finishes and the next is about to start
+    pub fn step_instruction(&mut self) -> StopReason {
+        self.run_for_step(|_, instruction_boundary| instruction_boundary)
+    }
+
+    /// As `step_instruction` except that a JSR is run until the subroutine returns
+    pub fn step_over(&mut self) -> StopReason {
+        let pc = self.pc();
+        if !self.cpu.at_instruction_boundary() || self.peek_byte(pc) != JSR {
+            return self.step_instruction();
+        }
+
+        let return_address = pc.wrapping_add(3) & ADDRESS_MASK;
+        let stack_pointer = self.cpu.register(Register::StackPointer);
+        self.run_for_step(|system, instruction_boundary| {
+            instruction_boundary
+                && system.pc() & ADDRESS_MASK == return_address
+                && system.cpu.register(Register::StackPointer) == stack_pointer
+        })
+    }
+
+    /// Run until an RTS or RTI returns from the current subroutine or interrupt handler
+    pub fn step_out(&mut self) -> StopReason {
+        let start_stack_pointer = self.cpu.register(Register::StackPointer) as u8;
+        let mut last_opcode = match self.cpu.at_instruction_boundary() {
+            true => Some(self.peek_byte(self.pc())),
+            false => None,
+        };
+
+        self.run_for_step(|system, instruction_boundary| {
+            if !instruction_boundary {
+                return false;
+            }
+
+            // The stack wraps, a return from a subroutine entered with S near $00 leaves it there
+            let stack_pointer = system.cpu.register(Register::StackPointer) as u8;
+            let pulled = stack_pointer.wrapping_sub(start_stack_pointer) as i8 > 0;
+            let returned = matches!(last_opcode, Some(RTS) | Some(RTI)) && pulled;
+            last_opcode = Some(system.peek_byte(system.pc()));
+
+            returned
+        })
+    }
+
+    /// Run until the first instruction boundary on the given scanline, counted from VSYNC
+    pub fn run_to_scanline(&mut self, scanline: usize) -> StopReason {
+        let mut left_scanline = self.tia.scanline() != scanline;
+
+        self.run_for_step(|system, instruction_boundary| {
+            let on_scanline = system.tia.scanline() == scanline;
+            left_scanline |= !on_scanline;
+
+            left_scanline && on_scanline && instruction_boundary
+        })
+    }
+
+    /// Every cpu register at once
+    pub fn registers(&self) -> RegisterSnapshot {
+        self.cpu.registers()
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        self.cpu.register(register)
+    }
+
+    /// Change a register, only the low byte is used for 8 bit registers
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        self.cpu.set_register(register, value);
+    }
+
+    /// Jump to `address`, abandoning any instruction part way through so that it's fetched next
+    pub fn set_pc(&mut self, address: u16) {
+        self.cpu.set_pc(address);
+    }
+
+    pub fn flag(&self, flag: Flag) -> bool {
+        self.cpu.flag(flag)
+    }
+
+    pub fn set_flag(&mut self, flag: Flag, value: bool) {
+        self.cpu.set_flag(flag, value);
+    }
+
+    /// Read a byte as the cpu would see it without any side effects such as bank switching
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        let address = address & ADDRESS_MASK;
+        let a12 = address & 0b0001_0000_0000_0000 != 0;
+        let a7 = address & 0b0000_0000_1000_0000 != 0;
+
+        match (a12, a7) {
+            (true, _) => self.cartridge.peek_byte(address & 0xFFF),
+            (false, false) => self.tia.read_byte(address & 0xF),
+            (false, true) => self.riot.peek_byte(address & 0x2FF),
+        }
+    }
+
+    /// As `peek_byte` for a block of memory
+    pub fn peek_memory(&self, start: u16, length: u16) -> Vec<u8> {
+        (0..length)
+            .map(|offset| self.peek_byte(start.wrapping_add(offset)))
+            .collect()
+    }
+
+    /// Cpu cycles run since power on
+    pub fn cpu_cycles(&self) -> u32 {
+        self.cpu.cycles
+    }
+
+    /// The current scanline, counted from the start of VSYNC
+    pub fn scanline(&self) -> usize {
+        self.tia.scanline()
+    }
+
+    /// Colour clocks into the current scanline
+    pub fn color_clock(&self) -> u8 {
+        self.tia.color_clock()
+    }
+
+    /// Colour clocks until the cpu is next clocked
+    pub fn color_clocks_until_cpu_cycle(&self) -> u8 {
+        self.cpu_clock_divider % COLOR_CLOCKS_PER_CPU_CYCLE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StopReason;
+    use crate::atari2600::tests::rom_with_program;
+    use crate::atari2600::Atari2600;
+    use crate::cpu::{Flag, Register};
+
+    /// LDX #$00, loop: INX, STX $80, JSR sub, JMP loop, sub: LDA $80, STA $81, RTS, NOP, KIL
+    const PROGRAM: [u8; 18] = [
+        0xA2, 0x00, 0xE8, 0x86, 0x80, 0x20, 0x0B, 0xF0, 0x4C, 0x02, 0xF0, 0xA5, 0x80, 0x85, 0x81,
+        0x60, 0xEA, 0x02,
+    ];
+
+    fn system() -> Atari2600 {
+        let system = Atari2600::new(&rom_with_program(&PROGRAM)).unwrap();
+        assert!(system.cpu.at_instruction_boundary());
+        assert_eq!(system.register(Register::ProgramCounter), 0xF000);
+        system
+    }
+
+    #[test]
+    fn test_step_instruction() {
+        let mut system = system();
+        assert_eq!(system.step_instruction(), StopReason::Completed);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF002);
+        assert_eq!(system.step_instruction(), StopReason::Completed);
+        assert_eq!(system.register(Register::X), 1);
+        assert!(!system.flag(Flag::Zero));
+    }
+
+    #[test]
+    fn test_registers_and_set_pc() {
+        let mut system = system();
+        for _ in 0..3 {
+            system.step_instruction();
+        }
+        let registers = system.registers();
+        assert_eq!(registers.x, 1);
+        assert_eq!(registers.program_counter, 0xF005);
+        assert_eq!(registers.status, system.register(Register::Status) as u8);
+
+        // Jump straight into the subroutine, LDA $80
+        system.set_pc(0xF00B);
+        assert_eq!(system.step_instruction(), StopReason::Completed);
+        assert_eq!(system.registers().a, 1);
+        assert_eq!(system.registers().program_counter, 0xF00D);
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut system = system();
+        for _ in 0..3 {
+            system.step_instruction();
+        }
+        assert_eq!(system.register(Register::ProgramCounter), 0xF005);
+
+        // Step over the JSR, the subroutine has run
+        assert_eq!(system.step_over(), StopReason::Completed);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF008);
+        assert_eq!(system.peek_memory(0x81, 1), vec![1]);
+
+        // Step into the subroutine and back out of it
+        system.step_over();
+        system.step_over();
+        system.step_over();
+        system.step_instruction();
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00B);
+        assert_eq!(system.step_out(), StopReason::Completed);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF008);
+    }
+
+    #[test]
+    fn test_step_out_when_the_stack_wraps() {
+        let mut system = system();
+        for _ in 0..3 {
+            system.step_instruction();
+        }
+
+        // The JSR pushes to $0100 & $01FF, leaving S at $FE inside the subroutine
+        system.set_register(Register::StackPointer, 0x00);
+        system.step_instruction();
+        assert_eq!(system.register(Register::StackPointer), 0xFE);
+
+        // Stops straight after the RTS, LDA $80 & STA $81 take 3 cycles each and RTS 6
+        let cycles = system.cpu_cycles();
+        assert_eq!(system.step_out(), StopReason::Completed);
+        assert_eq!(system.register(Register::StackPointer), 0x00);
+        assert_eq!(system.cpu_cycles() - cycles, 3 + 3 + 6);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut system = system();
+        system.add_breakpoint(0x100B);
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::Breakpoint);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00B);
+
+        // Continuing runs around the loop to the same breakpoint
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::Breakpoint);
+        assert_eq!(system.register(Register::X), 2);
+
+        system.clear_breakpoints();
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::Completed);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut system = system();
+        system.add_watchpoint(0x81, 0x81, false, true);
+        for _ in 0..3 {
+            assert_eq!(system.step_over(), StopReason::Completed);
+        }
+        assert_eq!(system.step_over(), StopReason::WriteWatchpoint);
+        assert_eq!(system.watchpoint_address(), Some(0x81));
+        assert_eq!(system.watchpoint_value(), Some(1));
+        // Stopped after the STA $81 has finished
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00F);
+
+        system.clear_watchpoints();
+        // Opcode fetches are reads too, watch the first instruction of the subroutine
+        system.add_watchpoint(0xF00B, 0xF00B, true, false);
+        system.step_out();
+        for _ in 0..3 {
+            assert_eq!(system.step_over(), StopReason::Completed);
+        }
+        // Stops inside the subroutine that was being stepped over
+        assert_eq!(system.step_over(), StopReason::ReadWatchpoint);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00D);
+        assert_eq!(system.watchpoint_address(), Some(0x100B));
+        assert_eq!(system.watchpoint_value(), Some(0xA5));
+    }
+
+    #[test]
+    fn test_fast_mode_stops_at_the_same_instructions() {
+        let mut system = system();
+        system.set_fast_mode(true);
+        system.add_breakpoint(0x100B);
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::Breakpoint);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00B);
+
+        system.clear_breakpoints();
+        system.add_watchpoint(0x81, 0x81, false, true);
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::WriteWatchpoint);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00F);
+
+        assert_eq!(system.step_instruction(), StopReason::Completed);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF008);
+    }
+
+    #[test]
+    fn test_opcode_breaks() {
+        let mut system = system();
+        system.add_opcode_break(0x60);
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::Opcode);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF00F);
+        system.remove_opcode_break(0x60);
+
+        // Jump to the NOP before the KIL at the end of the program
+        system.set_break_on_illegal_opcodes(true);
+        system.set_register(Register::ProgramCounter, 0xF010);
+        system.run_frame();
+        assert_eq!(system.stop_reason(), StopReason::IllegalOpcode);
+        assert_eq!(system.register(Register::ProgramCounter), 0xF011);
+        assert!(!system.is_jammed());
+    }
+
+    #[test]
+    fn test_run_to_scanline() {
+        let mut system = system();
+        assert_eq!(system.run_to_scanline(100), StopReason::Completed);
+        assert_eq!(system.scanline(), 100);
+        assert_eq!(system.run_to_scanline(1000), StopReason::Timeout);
+    }
+}
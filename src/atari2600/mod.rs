@@ -0,0 +1,1120 @@
+mod debugger;
+
+use wasm_bindgen::prelude::*;
+
+use crate::bus::{Bus, BusAccess, BusObserver, ObservedBus};
+use crate::cartridge::{new_cartridge, Cartridge, CartridgeType};
+use crate::cpu::disasm::{Line, Symbols};
+use crate::cpu::{Cpu, CpuVariant, Register};
+use crate::input::InputState;
+use crate::rewind::{RewindBuffer, DEFAULT_INTERVAL_FRAMES, DEFAULT_MEMORY_BUDGET};
+use crate::riot::Riot;
+use crate::save_state::{self, invalid_value, SaveState, StateReader, StateWriter};
+use crate::tia::{Tia, COLOR_CLOCKS_PER_SCANLINE, FRAME_HEIGHT};
+use crate::trace::{self, TraceSink, Tracer};
+use crate::utils::{init_logging, set_panic_hook};
+use debugger::{Debugger, StopReason};
+
+/// The cpu runs at a third of the speed of the TIA colour clock
+const COLOR_CLOCKS_PER_CPU_CYCLE: u8 = 3;
+
+/// Upper bound on the length of a frame in case a rom never strobes VSYNC
+const MAX_COLOR_CLOCKS_PER_FRAME: u32 = FRAME_HEIGHT as u32 * COLOR_CLOCKS_PER_SCANLINE as u32;
+
+/// The 6507 only has 13 address lines so everything above $1FFF is a mirror
+const ADDRESS_MASK: u16 = 0x1FFF;
+
+///
+/// The view of the system from the cpu's address lines, borrowed from the
+/// `Atari2600` for the duration of a single cpu cycle.
+///
+struct SystemBus<'a> {
+    tia: &'a mut Tia,
+    riot: &'a mut Riot,
+    cartridge: &'a mut dyn Cartridge,
+    debugger: &'a mut Debugger,
+}
+
+impl Bus for SystemBus<'_> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        let address = address & ADDRESS_MASK;
+        let a12 = address & 0b0001_0000_0000_0000 != 0;
+        let a7 = address & 0b0000_0000_1000_0000 != 0;
+
+        let value = match (a12, a7) {
+            (true, _) => self.cartridge.read_byte(address & 0xFFF),
+            (false, false) => self.tia.read_byte(address & 0xF),
+            // A9 selects between RAM and I/O inside the RIOT
+            (false, true) => self.riot.read_byte(address & 0x2FF),
+        };
+        self.cartridge.snoop(address, value, false);
+        if self.debugger.is_watching() {
+            self.debugger.check_access(address, value, false);
+        }
+
+        value
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        let address = address & ADDRESS_MASK;
+        let a12 = address & 0b0001_0000_0000_0000 != 0;
+        let a7 = address & 0b0000_0000_1000_0000 != 0;
+
+        match (a12, a7) {
+            (true, _) => self.cartridge.write_byte(address & 0xFFF, value),
+            (false, false) => self.tia.write_byte(address & 0x3F, value),
+            (false, true) => self.riot.write_byte(address & 0x2FF, value),
+        }
+        self.cartridge.snoop(address, value, true);
+        if self.debugger.is_watching() {
+            self.debugger.check_access(address, value, true);
+        }
+    }
+}
+
+///
+/// The system bus used to run a whole instruction at once. The TIA & RIOT
+/// fall behind the cpu and are only caught up before the cpu touches one of
+/// their registers, or before the first read after a TIA write which might
+/// have been a WSYNC, so every access sees exactly what it would if they
+/// were clocked in step.
+/// Cartridge and RIOT RAM accesses don't depend on the time so they don't
+/// need to wait for the chips to catch up.
+///
+struct CatchUpBus<'a> {
+    bus: SystemBus<'a>,
+    input: &'a InputState,
+    port_a_outputs: &'a mut u8,
+    // Cpu cycles which the TIA & RIOT haven't been clocked for yet
+    pending_cycles: u32,
+    // Cpu cycles the TIA & RIOT have been clocked for, including those where the cpu was halted
+    cycles: u32,
+    // Set by a TIA write, a WSYNC halts the cpu at its next read but not before
+    tia_written_since_read: bool,
+}
+
+impl CatchUpBus<'_> {
+    /// Clock everything except the cpu for one cpu cycle, in the order `clock_cycle` does
+    fn clock_peripherals(&mut self) {
+        self.bus.riot.clock();
+        if self.bus.riot.port_a_outputs() != *self.port_a_outputs {
+            apply_tia_input(self.bus.tia, self.bus.riot, self.input, self.port_a_outputs);
+        }
+        for _ in 0..COLOR_CLOCKS_PER_CPU_CYCLE {
+            self.bus.tia.clock();
+        }
+        self.cycles += 1;
+    }
+
+    /// Bring the TIA & RIOT up to date with the cpu
+    fn flush(&mut self) {
+        for _ in 0..self.pending_cycles {
+            self.clock_peripherals();
+        }
+        self.pending_cycles = 0;
+    }
+
+    /// Bring the TIA & RIOT up to date, then wait out any WSYNC as the cpu would
+    fn catch_up(&mut self) {
+        self.flush();
+
+        while self.bus.tia.cpu_halted() {
+            self.clock_peripherals();
+        }
+    }
+
+    /// Whether the address selects a TIA or RIOT register rather than RAM or the cartridge
+    fn is_tia_or_riot_io(address: u16) -> bool {
+        let address = address & ADDRESS_MASK;
+        let a12 = address & 0b0001_0000_0000_0000 != 0;
+        let a7 = address & 0b0000_0000_1000_0000 != 0;
+        let a9 = address & 0b0000_0010_0000_0000 != 0;
+
+        !a12 && (!a7 || a9)
+    }
+}
+
+impl Bus for CatchUpBus<'_> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        // RDY only halts the cpu on read cycles
+        if CatchUpBus::is_tia_or_riot_io(address) || self.tia_written_since_read {
+            self.catch_up();
+        }
+        self.tia_written_since_read = false;
+        self.pending_cycles += 1;
+
+        self.bus.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        // Writes go ahead even while a WSYNC is holding RDY low
+        if CatchUpBus::is_tia_or_riot_io(address) {
+            self.flush();
+        }
+        self.tia_written_since_read |= address & 0b0001_0000_1000_0000 == 0;
+        self.pending_cycles += 1;
+
+        self.bus.write_byte(address, value);
+    }
+}
+
+/// Recalculate the TIA input levels from the controllers and the RIOT's port A outputs
+fn apply_tia_input(tia: &mut Tia, riot: &Riot, input: &InputState, port_a_outputs: &mut u8) {
+    *port_a_outputs = riot.port_a_outputs();
+    tia.set_input_levels(
+        input.pot_charge_times(*port_a_outputs),
+        input.fire_levels(*port_a_outputs),
+    );
+}
+
+///
+/// The full console, owns each of the chips and the inserted cartridge and
+/// clocks them all from the TIA's colour clock.
+///
+#[wasm_bindgen]
+pub struct Atari2600 {
+    cpu: Cpu,
+    tia: Tia,
+    riot: Riot,
+    cartridge: Box<dyn Cartridge>,
+    input: InputState,
+    // Port A outputs the TIA input levels were last calculated with, keypads depend on them
+    port_a_outputs: u8,
+    // Colour clocks until the next cpu cycle
+    cpu_clock_divider: u8,
+    // Identifies the inserted rom so that save states can't be loaded into another game
+    rom_checksum: u32,
+    rewind: RewindBuffer,
+    debugger: Debugger,
+    stop_reason: StopReason,
+    tracer: Option<Tracer>,
+    bus_observer: Option<Box<dyn FnMut(BusAccess)>>,
+    // Run whole instructions at once where possible, see `set_fast_mode`
+    fast_mode: bool,
+}
+
+/// 32 bit FNV-1a hash of the rom
+fn rom_checksum(rom: &[u8]) -> u32 {
+    rom.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl Atari2600 {
+    fn bus(&mut self) -> (&mut Cpu, SystemBus<'_>) {
+        (
+            &mut self.cpu,
+            SystemBus {
+                tia: &mut self.tia,
+                riot: &mut self.riot,
+                cartridge: self.cartridge.as_mut(),
+                debugger: &mut self.debugger,
+            },
+        )
+    }
+
+    fn with_cartridge(rom: &[u8], mut cartridge: Box<dyn Cartridge>) -> Atari2600 {
+        set_panic_hook();
+        init_logging();
+
+        let mut tia = Tia::new();
+        let mut riot = Riot::new();
+        let mut debugger = Debugger::new();
+        let cpu = Cpu::new_with_variant(
+            CpuVariant::Mos6507,
+            0,
+            &mut SystemBus {
+                tia: &mut tia,
+                riot: &mut riot,
+                cartridge: cartridge.as_mut(),
+                debugger: &mut debugger,
+            },
+        );
+
+        let mut system = Atari2600 {
+            cpu,
+            tia,
+            riot,
+            cartridge,
+            input: InputState::new(),
+            port_a_outputs: 0xFF,
+            cpu_clock_divider: 0,
+            rom_checksum: rom_checksum(rom),
+            rewind: RewindBuffer::new(DEFAULT_INTERVAL_FRAMES, DEFAULT_MEMORY_BUDGET),
+            debugger,
+            stop_reason: StopReason::Completed,
+            tracer: None,
+            bus_observer: None,
+            fast_mode: false,
+        };
+        system.apply_input();
+
+        system
+    }
+
+    fn apply_input(&mut self) {
+        self.riot.set_swcha_input(self.input.swcha());
+        self.riot.set_swchb_input(self.input.swchb());
+        self.apply_tia_input();
+    }
+
+    fn apply_tia_input(&mut self) {
+        apply_tia_input(
+            &mut self.tia,
+            &self.riot,
+            &self.input,
+            &mut self.port_a_outputs,
+        );
+    }
+
+    ///
+    /// Move the whole system on by a single colour clock. Returns true if the
+    /// cpu was clocked and is now about to fetch the next opcode.
+    ///
+    fn clock_cycle(&mut self) -> bool {
+        let mut instruction_boundary = false;
+
+        if self.cpu_clock_divider == 0 {
+            self.cpu_clock_divider = COLOR_CLOCKS_PER_CPU_CYCLE;
+
+            // The TIA pulls RDY low after a WSYNC, halting the cpu at its next read
+            // until the next scanline. Writes aren't held up by RDY.
+            if !self.tia.cpu_halted() || self.cpu.next_cycle_is_write() {
+                if self.tracer.is_some() && self.cpu.at_instruction_boundary() {
+                    self.trace_instruction();
+                }
+
+                // Only pay for observing the bus while there's an observer
+                instruction_boundary = match self.bus_observer.take() {
+                    None => {
+                        let (cpu, mut bus) = self.bus();
+                        cpu.clock(&mut bus);
+                        cpu.at_instruction_boundary()
+                    }
+                    Some(mut observer) => {
+                        let (cpu, bus) = self.bus();
+                        cpu.clock(&mut ObservedBus {
+                            bus,
+                            observer: observer.as_mut(),
+                        });
+                        let instruction_boundary = cpu.at_instruction_boundary();
+                        self.bus_observer = Some(observer);
+                        instruction_boundary
+                    }
+                };
+            }
+            self.riot.clock();
+
+            // Keypad rows are selected by writing to SWCHA
+            if self.riot.port_a_outputs() != self.port_a_outputs {
+                self.apply_tia_input();
+            }
+        }
+        self.cpu_clock_divider -= 1;
+
+        self.tia.clock();
+
+        instruction_boundary
+    }
+
+    ///
+    /// Whether the next instruction can be run in one go by `run_instruction`,
+    /// which needs the cpu to be about to fetch an opcode on this colour
+    /// clock and nothing to be watching each individual bus cycle.
+    ///
+    fn can_run_instruction(&self) -> bool {
+        self.fast_mode
+            && self.bus_observer.is_none()
+            && self.cpu_clock_divider == 0
+            && self.cpu.at_instruction_boundary()
+    }
+
+    fn catch_up_bus(&mut self) -> (&mut Cpu, CatchUpBus<'_>) {
+        (
+            &mut self.cpu,
+            CatchUpBus {
+                bus: SystemBus {
+                    tia: &mut self.tia,
+                    riot: &mut self.riot,
+                    cartridge: self.cartridge.as_mut(),
+                    debugger: &mut self.debugger,
+                },
+                input: &self.input,
+                port_a_outputs: &mut self.port_a_outputs,
+                pending_cycles: 0,
+                cycles: 0,
+                tia_written_since_read: false,
+            },
+        )
+    }
+
+    ///
+    /// Wait out any WSYNC the cpu is halted by and then run the whole of the
+    /// next instruction, leaving the system exactly as clocking it a colour
+    /// clock at a time would. Returns the colour clocks that took.
+    ///
+    fn run_instruction(&mut self) -> u32 {
+        let (_, mut bus) = self.catch_up_bus();
+        bus.catch_up();
+        let halted_cycles = bus.cycles;
+
+        if self.tracer.is_some() {
+            self.trace_instruction();
+        }
+
+        let (cpu, mut bus) = self.catch_up_bus();
+        cpu.execute_instruction(&mut bus);
+        // Leave any WSYNC for the next instruction so the debugger sees the boundary before it
+        bus.flush();
+
+        (halted_cycles + bus.cycles) * COLOR_CLOCKS_PER_CPU_CYCLE as u32
+    }
+
+    /// Log the instruction about to be executed, as it's fetched
+    fn trace_instruction(&mut self) {
+        let pc = self.cpu.register(Register::ProgramCounter);
+        let bytes = self.peek_memory(pc, 3);
+        let line = trace::format_line(
+            &Line::decode(&bytes, pc, Symbols::None, self.cpu.variant()),
+            &self.cpu,
+            Some((self.tia.scanline(), self.tia.color_clock())),
+        );
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(line);
+        }
+    }
+
+    ///
+    /// Clock the system until `is_done` returns true, a breakpoint or
+    /// watchpoint is hit or `max_color_clocks` have run. `is_done` is called
+    /// after every colour clock with whether the cpu is at an instruction
+    /// boundary. Returns why it stopped and the colour clocks that were run.
+    ///
+    /// In fast mode whole instructions are run between calls to `is_done`
+    /// so it may stop up to an instruction later than it otherwise would.
+    ///
+    fn run_until<F: FnMut(&Atari2600, bool) -> bool>(
+        &mut self,
+        max_color_clocks: u32,
+        mut is_done: F,
+    ) -> (StopReason, u32) {
+        let mut color_clocks = 0;
+
+        while color_clocks < max_color_clocks {
+            let instruction_boundary = if self.can_run_instruction() {
+                color_clocks += self.run_instruction();
+                true
+            } else {
+                color_clocks += 1;
+                self.clock_cycle()
+            };
+
+            if instruction_boundary && self.debugger.is_active() {
+                let pc = self.cpu.register(Register::ProgramCounter);
+                let opcode = self.peek_byte(pc);
+                if let Some(reason) = self.debugger.check_instruction(pc, opcode) {
+                    return (reason, color_clocks);
+                }
+            }
+
+            if is_done(self, instruction_boundary) {
+                return (StopReason::Completed, color_clocks);
+            }
+        }
+
+        (StopReason::Timeout, color_clocks)
+    }
+
+    ///
+    /// The RGBA frame buffer, `frame_width` x `frame_height` pixels starting
+    /// at VSYNC. Native frontends read this directly rather than going
+    /// through `frame_buffer_ptr`.
+    ///
+    pub fn frame_buffer(&self) -> &[u8] {
+        self.tia.frame_buffer()
+    }
+
+    /// Call `observer` with every bus cycle the cpu makes, replacing any previous observer
+    pub fn set_bus_observer(&mut self, observer: Box<dyn FnMut(BusAccess)>) {
+        self.bus_observer = Some(observer);
+    }
+}
+
+///
+/// Controller input isn't part of the state, whatever the frontend last set
+/// is applied to the restored system.
+///
+impl SaveState for Atari2600 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.rom_checksum);
+        self.cpu.save_state(writer);
+        self.tia.save_state(writer);
+        self.riot.save_state(writer);
+        self.cartridge.save_state(writer);
+        writer.write_u8(self.cpu_clock_divider);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        if reader.read_u32()? != self.rom_checksum {
+            return Err("Save state is for a different rom".to_string());
+        }
+        self.cpu.load_state(reader)?;
+        self.tia.load_state(reader)?;
+        self.riot.load_state(reader)?;
+        self.cartridge.load_state(reader)?;
+        self.cpu_clock_divider = match reader.read_u8()? {
+            divider if divider < COLOR_CLOCKS_PER_CPU_CYCLE => divider,
+            divider => return Err(invalid_value("cpu clock divider", divider)),
+        };
+        self.apply_input();
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Atari2600 {
+    /// Create a system with the rom inserted, detecting which bank switching scheme it uses
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Atari2600, String> {
+        Ok(Atari2600::with_cartridge(rom, new_cartridge(rom, None)?))
+    }
+
+    /// Create a system with the rom inserted using a named bank switching scheme (e.g. "F8SC")
+    pub fn new_with_cartridge_type(rom: &[u8], cartridge_type: &str) -> Result<Atari2600, String> {
+        let cartridge_type = cartridge_type.parse::<CartridgeType>()?;
+
+        Ok(Atari2600::with_cartridge(
+            rom,
+            new_cartridge(rom, Some(cartridge_type))?,
+        ))
+    }
+
+    /// Move the whole system on by a single colour clock, ignoring any breakpoints
+    pub fn clock(&mut self) {
+        self.clock_cycle();
+    }
+
+    /// Run the system for the given number of cpu cycles (three colour clocks each)
+    pub fn run_cycles(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            for _ in 0..COLOR_CLOCKS_PER_CPU_CYCLE {
+                self.clock();
+            }
+        }
+    }
+
+    ///
+    /// Run the system until the TIA starts a new frame, either because the
+    /// rom started VSYNC or because a full frame's worth of scanlines passed
+    /// without one. Returns the number of colour clocks that were run.
+    ///
+    /// Snapshots for `rewind` are taken at the start of every few frames.
+    ///
+    /// Stops early if a breakpoint or watchpoint is hit, `stop_reason` says
+    /// why. Calling `run_frame` again continues to the end of the frame.
+    ///
+    pub fn run_frame(&mut self) -> u32 {
+        if self.rewind.wants_snapshot() {
+            let state = save_state::save(self);
+            self.rewind.push(state);
+        }
+
+        let frame_number = self.tia.frame_number();
+        let (reason, color_clocks) = self.run_until(MAX_COLOR_CLOCKS_PER_FRAME, |system, _| {
+            system.tia.frame_number() != frame_number
+        });
+
+        self.stop_reason = match reason {
+            StopReason::Timeout => StopReason::Completed,
+            reason => reason,
+        };
+        if self.stop_reason == StopReason::Completed {
+            self.rewind.frame_completed();
+        }
+
+        color_clocks
+    }
+
+    ///
+    /// Run the cpu a whole instruction at a time, catching the TIA & RIOT up
+    /// only when the cpu accesses them, instead of interleaving the chips a
+    /// colour clock at a time. The emulation is identical, but `run_frame`
+    /// and the debugger's stepping only check whether they're done between
+    /// instructions so can finish a few colour clocks later. Ignored while a
+    /// bus observer is attached as that needs every cycle in step.
+    ///
+    pub fn set_fast_mode(&mut self, enabled: bool) {
+        self.fast_mode = enabled;
+    }
+
+    ///
+    /// Snapshot every `interval_frames` frames for rewinding, keeping as many
+    /// as fit in `memory_budget` bytes. A budget of 0 disables rewinding.
+    /// Any existing snapshots are discarded.
+    ///
+    pub fn configure_rewind(&mut self, interval_frames: u32, memory_budget: usize) {
+        self.rewind.configure(interval_frames, memory_budget);
+    }
+
+    ///
+    /// Step the system back to the newest snapshot at least `frames` frames
+    /// ago, or as far back as the snapshots go. Returns the number of frames
+    /// actually rewound, which is a multiple of the snapshot interval.
+    ///
+    pub fn rewind(&mut self, frames: u32) -> u32 {
+        let (rewound, state) = match self.rewind.rewind(frames) {
+            Some((rewound, state)) => (rewound, state.to_vec()),
+            None => return 0,
+        };
+        save_state::load(self, &state).expect("Rewind snapshots are taken from this system");
+
+        rewound
+    }
+
+    /// How many frames back `rewind` can currently go
+    pub fn rewind_frames_available(&self) -> u32 {
+        self.rewind.frames_available()
+    }
+
+    ///
+    /// Start logging every instruction executed, keeping the most recent
+    /// `capacity` lines for `take_trace`. See `trace::format_line` for the
+    /// format, it's chosen to diff cleanly against nestest style logs.
+    ///
+    pub fn start_trace(&mut self, capacity: usize) {
+        self.tracer = Some(Tracer::buffered(capacity));
+    }
+
+    /// Start logging every instruction executed, passing each line to `sink` as it's produced
+    pub fn start_trace_streaming(&mut self, sink: TraceSink) {
+        self.tracer = Some(Tracer::streaming(Box::new(move |line| {
+            sink.trace_line(line)
+        })));
+    }
+
+    /// Stop tracing, any buffered lines are discarded
+    pub fn stop_trace(&mut self) {
+        self.tracer = None;
+    }
+
+    /// Remove the buffered trace lines, oldest first with a newline after each
+    pub fn take_trace(&mut self) -> String {
+        let lines = self.tracer.as_mut().map(Tracer::take_lines);
+
+        lines
+            .unwrap_or_default()
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    ///
+    /// Call `observer.on_bus_access(cycle, address, value, kind)` for every
+    /// bus cycle the cpu makes, e.g. to build access heatmaps. Costs a JS
+    /// call per cycle so stop it when it's no longer needed.
+    ///
+    pub fn start_bus_observer(&mut self, observer: BusObserver) {
+        self.set_bus_observer(Box::new(move |access| {
+            observer.on_bus_access(access.cycle, access.address, access.value, access.kind)
+        }));
+    }
+
+    pub fn stop_bus_observer(&mut self) {
+        self.bus_observer = None;
+    }
+
+    /// Press the console's reset line, this is different from the RESET switch which is read by the game
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    pub fn is_jammed(&self) -> bool {
+        self.cpu.is_jammed()
+    }
+
+    /// Update the controllers and console switches, typically called once per frame
+    pub fn set_input_state(&mut self, state: &InputState) {
+        self.input = *state;
+        self.apply_input();
+    }
+
+    /// Snapshot the entire machine, the result can be passed back to `load_state`
+    pub fn save_state(&self) -> Vec<u8> {
+        save_state::save(self)
+    }
+
+    ///
+    /// Restore a snapshot taken by `save_state` with the same rom inserted.
+    /// Snapshots from other roms or other versions of the emulator are
+    /// rejected and leave the system untouched.
+    ///
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        save_state::load(self, state)
+    }
+
+    /// Set the sample rate of the host's audio output, 44.1kHz by default
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.tia.set_audio_sample_rate(sample_rate);
+    }
+
+    ///
+    /// Take the audio samples generated since the last call, mono f32 in the
+    /// range 0-1 at the configured sample rate. Call once per frame to keep
+    /// up with the video.
+    ///
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.tia.take_audio_samples()
+    }
+
+    pub fn frame_number(&self) -> u32 {
+        self.tia.frame_number()
+    }
+
+    pub fn frame_buffer_ptr(&self) -> *const u8 {
+        self.tia.frame_buffer_ptr()
+    }
+
+    pub fn frame_width(&self) -> usize {
+        self.tia.frame_width()
+    }
+
+    pub fn frame_height(&self) -> usize {
+        self.tia.frame_height()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{Atari2600, Bus, InputState, MAX_COLOR_CLOCKS_PER_FRAME};
+    use crate::bus::BusAccessKind;
+
+    /// Build a 4K rom with the given program at $F000 and the reset vector pointing to it
+    pub(super) fn rom_with_program(program: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0xEA; 0x1000];
+        rom[..program.len()].copy_from_slice(program);
+        rom[0xFFC] = 0x00;
+        rom[0xFFD] = 0xF0;
+        rom
+    }
+
+    #[test]
+    fn test_rejects_unsupported_rom_size() {
+        assert!(Atari2600::new(&[0; 100]).is_err());
+        assert!(Atari2600::new(&[0; 0x800]).is_ok());
+    }
+
+    #[test]
+    fn test_cartridge_type_override() {
+        let rom = vec![0; 0x2000];
+        assert!(Atari2600::new_with_cartridge_type(&rom, "F8SC").is_ok());
+        assert!(Atari2600::new_with_cartridge_type(&rom, "F6").is_err());
+        assert!(Atari2600::new_with_cartridge_type(&rom, "nonsense").is_err());
+    }
+
+    #[test]
+    fn test_3f_bank_switching_through_the_bus() {
+        let mut rom = vec![0; 0x2000];
+        rom[0x800] = 0x12;
+        let mut system = Atari2600::new_with_cartridge_type(&rom, "3F").unwrap();
+        let (_, mut bus) = system.bus();
+
+        bus.write_byte(0x3F, 1);
+        assert_eq!(bus.read_byte(0xF000), 0x12);
+    }
+
+    #[test]
+    fn test_input_state_reaches_the_chips() {
+        let mut system = Atari2600::new(&rom_with_program(&[])).unwrap();
+        let mut input = InputState::new();
+        input.set_joystick(1, true, false, false, false, true);
+        input.set_console_switches(true, false, true, false, false);
+        system.set_input_state(&input);
+
+        let (_, mut bus) = system.bus();
+        assert_eq!(bus.read_byte(0x280), 0b1111_1110); // SWCHA
+        assert_eq!(bus.read_byte(0x282), 0b0000_1010); // SWCHB
+        assert_eq!(bus.read_byte(0x0C), 0x80); // INPT4
+        assert_eq!(bus.read_byte(0x0D), 0x00); // INPT5
+    }
+
+    #[test]
+    fn test_keypad_rows_selected_by_the_cpu() {
+        // LDA #$FF, STA SWACNT, LDA #$EF, STA SWCHA (select row 0), JMP $F00A
+        let program = [
+            0xA9, 0xFF, 0x8D, 0x81, 0x02, 0xA9, 0xEF, 0x8D, 0x80, 0x02, 0x4C, 0x0A, 0xF0,
+        ];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        let mut input = InputState::new();
+        // Key 3 - row 0, column 2
+        input.set_keypad(0, 1 << 2);
+        system.set_input_state(&input);
+
+        let (_, mut bus) = system.bus();
+        assert_eq!(bus.read_byte(0x0C), 0x80);
+        system.run_cycles(12);
+        let (_, mut bus) = system.bus();
+        assert_eq!(bus.read_byte(0x0C), 0x00);
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        // A loop which keeps writing to RAM and the TIA so the state changes every frame
+        // loop: INC $80, LDA $80, STA COLUBK, STA WSYNC, JMP loop
+        let program = [
+            0xE6, 0x80, 0xA5, 0x80, 0x85, 0x09, 0x85, 0x02, 0x4C, 0x00, 0xF0,
+        ];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        system.run_frame();
+        system.run_cycles(1001);
+
+        let state = system.save_state();
+        system.run_frame();
+        system.run_frame();
+        let expected = system.save_state();
+
+        system.load_state(&state).unwrap();
+        system.run_frame();
+        system.run_frame();
+        assert!(system.save_state() == expected);
+    }
+
+    #[test]
+    fn test_load_state_rejects_bad_states() {
+        let mut system = Atari2600::new(&rom_with_program(&[])).unwrap();
+        system.run_cycles(100);
+        let state = system.save_state();
+
+        let mut other_game = Atari2600::new(&rom_with_program(&[0xE8])).unwrap();
+        let original = other_game.save_state();
+        assert!(other_game.load_state(&state).is_err());
+        assert!(other_game.save_state() == original);
+
+        let mut old_version = state.clone();
+        old_version[4] = old_version[4].wrapping_add(1);
+        assert!(system.load_state(&old_version).is_err());
+        assert!(system.load_state(&state[..state.len() - 10]).is_err());
+        assert!(system.load_state(&[]).is_err());
+        assert!(system.save_state() == state);
+    }
+
+    #[test]
+    fn test_rewind_restores_exact_state() {
+        let program = [
+            0xE6, 0x80, 0xA5, 0x80, 0x85, 0x09, 0x85, 0x02, 0x4C, 0x00, 0xF0,
+        ];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        system.configure_rewind(2, 16 * 1024 * 1024);
+
+        let mut states = Vec::new();
+        for _ in 0..10 {
+            states.push(system.save_state());
+            system.run_frame();
+        }
+        assert_eq!(system.rewind_frames_available(), 10);
+
+        // Back 3 frames from frame 10 is frame 7, the snapshot before that is frame 6
+        assert_eq!(system.rewind(3), 4);
+        assert!(system.save_state() == states[6]);
+        assert_eq!(system.rewind_frames_available(), 6);
+
+        // Running forwards again takes new snapshots
+        system.run_frame();
+        system.run_frame();
+        assert!(system.save_state() == states[8]);
+        assert_eq!(system.rewind(100), 8);
+        assert!(system.save_state() == states[0]);
+    }
+
+    #[test]
+    fn test_trace_lines() {
+        // LDA #$42, STA WSYNC, LDX $80, JMP $F000
+        let program = [0xA9, 0x42, 0x85, 0x02, 0xA6, 0x80, 0x4C, 0x00, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        system.start_trace(100);
+        system.run_cycles(5 + 76);
+
+        let trace = system.take_trace();
+        let lines: Vec<&str> = trace.lines().collect();
+        // P has bit 5 set, as in nestest & Stella logs
+        assert_eq!(
+            lines[0],
+            "F000  A9 42     LDA #$42                        A:00 X:00 Y:00 P:24 SP:FD TIA:  0,  0 CYC:0"
+        );
+        assert_eq!(
+            lines[1],
+            "F002  85 02     STA $02                         A:42 X:00 Y:00 P:24 SP:FD TIA:  0,  6 CYC:2"
+        );
+        // The WSYNC halts the cpu until the start of the next scanline
+        assert!(lines[2].starts_with("F004  A6 80     LDX $80 "));
+        assert!(lines[2].contains(" TIA:  1,  "));
+
+        // Only the most recent lines are kept
+        system.start_trace(2);
+        system.run_cycles(20);
+        assert_eq!(system.take_trace().lines().count(), 2);
+
+        system.stop_trace();
+        system.run_cycles(10);
+        assert_eq!(system.take_trace(), "");
+    }
+
+    #[test]
+    fn test_bus_observer() {
+        // LDA #$42, STA $80, INC $81, JMP $F004
+        let program = [0xA9, 0x42, 0x85, 0x80, 0xE6, 0x81, 0x4C, 0x04, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let sink = observed.clone();
+        system.set_bus_observer(Box::new(move |access| sink.borrow_mut().push(access)));
+        let cpu_cycles = system.cpu.cycles;
+        system.run_cycles(10);
+
+        let accesses = observed.take();
+        let fetches: Vec<(u32, u16)> = accesses
+            .iter()
+            .filter(|access| access.kind == BusAccessKind::OpcodeFetch)
+            .map(|access| (access.cycle - cpu_cycles, access.address))
+            .collect();
+        // Addresses are as seen on the 6507's 13 address lines
+        assert_eq!(fetches, vec![(0, 0x1000), (2, 0x1002), (5, 0x1004)]);
+
+        let writes: Vec<(u16, u8, BusAccessKind)> = accesses
+            .iter()
+            .filter(|access| access.kind.is_write())
+            .map(|access| (access.address, access.value, access.kind))
+            .collect();
+        assert_eq!(
+            writes,
+            vec![
+                (0x80, 0x42, BusAccessKind::Write),
+                (0x81, 0x00, BusAccessKind::DummyWrite),
+                (0x81, 0x01, BusAccessKind::Write),
+            ]
+        );
+
+        system.stop_bus_observer();
+        system.run_cycles(10);
+        assert!(observed.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_address_decoding() {
+        let mut system = Atari2600::new(&rom_with_program(&[0x12, 0x34])).unwrap();
+        let (_, mut bus) = system.bus();
+
+        // Cartridge is mirrored wherever A12 is set
+        assert_eq!(bus.read_byte(0xF000), 0x12);
+        assert_eq!(bus.read_byte(0x1001), 0x34);
+        assert_eq!(bus.read_byte(0x3000), 0x12);
+
+        // RAM at $80 is mirrored at $180 (A8 is ignored)
+        bus.write_byte(0x80, 0x56);
+        assert_eq!(bus.read_byte(0x180), 0x56);
+
+        // TIA registers are mirrored everywhere A12 & A7 are low
+        bus.write_byte(0x40 | 0x2C, 0); // CXCLR
+        assert_eq!(bus.read_byte(0x0D), 0x80); // INPT5
+
+        // RIOT I/O lives where A9 & A7 are set
+        bus.write_byte(0x281, 0xAA); // SWACNT
+        assert_eq!(bus.read_byte(0x281), 0xAA);
+        assert_eq!(bus.read_byte(0x81), 0x00);
+    }
+
+    #[test]
+    fn test_cpu_runs_from_reset_vector() {
+        // LDA #$42, STA $80, INC $81, JMP $F004
+        let program = [0xA9, 0x42, 0x85, 0x80, 0xE6, 0x81, 0x4C, 0x04, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+
+        // LDA (2) + STA (3) + INC (5)
+        system.run_cycles(10);
+        let (_, mut bus) = system.bus();
+        assert_eq!(bus.read_byte(0x80), 0x42);
+        assert_eq!(bus.read_byte(0x81), 0x01);
+    }
+
+    #[test]
+    fn test_wsync_halts_cpu() {
+        // STA WSYNC, then loop
+        let program = [0x85, 0x02, 0x4C, 0x02, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+
+        system.run_cycles(3);
+        let cycles = system.cpu.cycles;
+        assert!(system.tia.cpu_halted());
+
+        // The rest of the 76 cycle scanline is spent with the cpu halted
+        system.run_cycles(72);
+        assert_eq!(system.cpu.cycles, cycles);
+        system.run_cycles(2);
+        assert!(system.cpu.cycles > cycles);
+    }
+
+    #[test]
+    fn test_wsync_lets_writes_finish() {
+        // INC WSYNC, NOP
+        let program = [0xE6, 0x02, 0xEA];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let sink = observed.clone();
+        system.set_bus_observer(Box::new(move |access| sink.borrow_mut().push(access)));
+
+        // Note the scanline each access is made on
+        let mut accesses = Vec::new();
+        while accesses.len() < 6 {
+            system.clock();
+            for access in observed.borrow_mut().drain(..) {
+                accesses.push((access.kind, system.tia.scanline()));
+            }
+        }
+
+        // The dummy write strobes WSYNC but the write after it still lands on the
+        // same scanline, it's the NOP's opcode fetch which waits for the next one
+        assert_eq!(
+            accesses,
+            vec![
+                (BusAccessKind::OpcodeFetch, 0),
+                (BusAccessKind::OperandRead, 0),
+                (BusAccessKind::OperandRead, 0),
+                (BusAccessKind::DummyWrite, 0),
+                (BusAccessKind::Write, 0),
+                (BusAccessKind::OpcodeFetch, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_frame_stops_at_vsync() {
+        // start: LDA #2, STA VSYNC, STA WSYNC x3, LDA #0, STA VSYNC
+        //        LDX #100, loop: STA WSYNC, DEX, BNE loop, JMP start
+        let program = [
+            0xA9, 0x02, 0x85, 0x00, 0x85, 0x02, 0x85, 0x02, 0x85, 0x02, 0xA9, 0x00, 0x85, 0x00,
+            0xA2, 0x64, 0x85, 0x02, 0xCA, 0xD0, 0xFB, 0x4C, 0x00, 0xF0,
+        ];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+
+        // The first frame starts from reset so isn't synchronised to a scanline
+        system.run_frame();
+        system.run_frame();
+        assert_eq!(system.frame_number(), 2);
+
+        // Each frame is 103 scanlines, 3 of VSYNC and 100 more
+        assert_eq!(system.run_frame(), 103 * 228);
+        assert_eq!(system.frame_number(), 3);
+    }
+
+    #[test]
+    fn test_audio_samples_produced_per_frame() {
+        // LDA #$0F, STA AUDV0, JMP $F004
+        let program = [0xA9, 0x0F, 0x85, 0x19, 0x4C, 0x04, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        system.set_audio_sample_rate(48_000);
+
+        system.run_frame();
+        system.take_audio_samples();
+        system.run_frame();
+        let samples = system.take_audio_samples();
+
+        // A full 312 line frame at 48kHz, with AUDC0 = 0 the output is constant
+        let expected = 48_000.0 * 312.0 * 228.0 / 3_579_545.0;
+        assert!((samples.len() as f64 - expected).abs() < 2.0);
+        assert!(samples.iter().all(|&sample| sample == 0.5));
+    }
+
+    ///
+    /// Runs a frame at a time in fast mode and the same number of colour
+    /// clocks one at a time, the two systems must stay identical. The
+    /// program hits the TIA & RIOT registers at different points of each
+    /// scanline, selects keypad rows and is halted by WSYNC part way through
+    /// an instruction, both before a TIA access and before a cartridge read.
+    ///
+    #[test]
+    fn test_fast_mode_matches_clocking_every_chip() {
+        let program = [
+            0xA9, 0x02, // start: LDA #2
+            0x85, 0x00, // STA VSYNC
+            0x85, 0x02, // STA WSYNC
+            0x85, 0x02, // STA WSYNC
+            0x85, 0x02, // STA WSYNC
+            0xA9, 0x00, // LDA #0
+            0x85, 0x00, // STA VSYNC
+            0xA9, 0xFF, // LDA #$FF
+            0x8D, 0x81, 0x02, // STA SWACNT
+            0x8D, 0x96, 0x02, // STA TIM64T
+            0xA2, 0x43, // LDX #$43
+            0x9A, // TXS
+            0x20, 0x1B, 0xF0, // JSR $F01B, pushes to the TIA at RSYNC and then WSYNC
+            0xA2, 0x00, // LDX #0
+            0x86, 0x09, // loop: STX COLUBK
+            0x86, 0x06, // STX COLUP0
+            0x85, 0x10, // STA RESP0
+            0x8E, 0x80, 0x02, // STX SWCHA
+            0xAD, 0x84, 0x02, // LDA INTIM
+            0x85, 0x1B, // STA GRP0
+            0xA5, 0x0C, // LDA INPT4
+            0x85, 0x80, // STA $80
+            0xA5, 0x07, // LDA CXPPMM
+            0x85, 0x81, // STA $81
+            0xE8, // INX
+            0x8A, // TXA
+            0x29, 0x03, // AND #3
+            0xD0, 0x02, // BNE skip
+            0xE6, 0x02, // INC WSYNC, both writes land before the halt
+            0xE0, 0xC8, // skip: CPX #200
+            0xD0, 0xDE, // BNE loop
+            0x4C, 0x00, 0xF0, // JMP start
+        ];
+        let rom = rom_with_program(&program);
+        let mut input = InputState::new();
+        input.set_keypad(0, 0b0101_0101_0101);
+
+        let mut fast = Atari2600::new(&rom).unwrap();
+        let mut slow = Atari2600::new(&rom).unwrap();
+        for system in [&mut fast, &mut slow] {
+            system.set_input_state(&input);
+            system.set_audio_sample_rate(48_000);
+        }
+        fast.set_fast_mode(true);
+
+        for _ in 0..5 {
+            let color_clocks = fast.run_frame();
+            for _ in 0..color_clocks {
+                slow.clock();
+            }
+
+            assert!(fast.save_state() == slow.save_state());
+            assert!(fast.frame_buffer() == slow.frame_buffer());
+            assert_eq!(fast.take_audio_samples(), slow.take_audio_samples());
+        }
+        assert_eq!(fast.frame_number(), 5);
+    }
+
+    #[test]
+    fn test_fast_mode_falls_back_with_a_bus_observer() {
+        // LDA #$42, STA $80, INC $81, JMP $F004
+        let program = [0xA9, 0x42, 0x85, 0x80, 0xE6, 0x81, 0x4C, 0x04, 0xF0];
+        let mut system = Atari2600::new(&rom_with_program(&program)).unwrap();
+        system.set_fast_mode(true);
+        let observed = Rc::new(RefCell::new(0));
+        let sink = observed.clone();
+        system.set_bus_observer(Box::new(move |_| *sink.borrow_mut() += 1));
+
+        let color_clocks = system.run_frame();
+        assert_eq!(*observed.borrow(), color_clocks / 3);
+    }
+
+    #[test]
+    fn test_run_frame_is_capped_without_vsync() {
+        // JMP $F000
+        let mut system = Atari2600::new(&rom_with_program(&[0x4C, 0x00, 0xF0])).unwrap();
+
+        system.run_frame();
+        assert_eq!(system.run_frame(), MAX_COLOR_CLOCKS_PER_FRAME);
+    }
+}
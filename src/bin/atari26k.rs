@@ -0,0 +1,274 @@
+//!
+//! Desktop frontend, runs a rom in a window without needing a browser.
+//!
+//! Usage: `atari26k <rom> [cartridge type]`, the optional cartridge type
+//! (e.g. "F8SC") overrides bank switching detection.
+//!
+//! Keys follow Stella's defaults:
+//!
+//! | Key                  | Action                           |
+//! |----------------------|----------------------------------|
+//! | Arrows & Space       | Left joystick & fire             |
+//! | Y, G, H, J & F       | Right joystick & fire            |
+//! | F1                   | Select                           |
+//! | F2                   | Reset                            |
+//! | F3 / F4              | Colour / black & white           |
+//! | F5 / F6              | Left difficulty A / B            |
+//! | F7 / F8              | Right difficulty A / B           |
+//! | Backspace (held)     | Rewind                           |
+//! | P                    | Pause                            |
+//! | Escape               | Quit                             |
+//!
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use atari_2600_rust_web_assembly::{Atari2600, InputState};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+/// The TIA frame buffer starts at VSYNC, skip VSYNC & VBLANK to get to the visible picture
+const FIRST_VISIBLE_SCANLINE: usize = 40;
+const VISIBLE_SCANLINES: usize = 192;
+
+/// TIA pixels are roughly twice as wide as they are tall so each is drawn twice
+const PIXEL_ASPECT: usize = 2;
+
+/// Drop audio once this many seconds are queued so the sound can't drift behind the picture
+const MAX_AUDIO_LATENCY_SECONDS: f32 = 0.1;
+
+/// The console switches, which are toggled by a key press rather than held
+struct Switches {
+    color: bool,
+    left_difficulty_a: bool,
+    right_difficulty_a: bool,
+}
+
+impl Switches {
+    fn update(&mut self, window: &Window) {
+        let pressed = |key| window.is_key_pressed(key, KeyRepeat::No);
+
+        if pressed(Key::F3) {
+            self.color = true;
+        }
+        if pressed(Key::F4) {
+            self.color = false;
+        }
+        if pressed(Key::F5) {
+            self.left_difficulty_a = true;
+        }
+        if pressed(Key::F6) {
+            self.left_difficulty_a = false;
+        }
+        if pressed(Key::F7) {
+            self.right_difficulty_a = true;
+        }
+        if pressed(Key::F8) {
+            self.right_difficulty_a = false;
+        }
+    }
+}
+
+fn input_state(window: &Window, switches: &Switches) -> InputState {
+    let down = |key| window.is_key_down(key);
+    let mut state = InputState::new();
+
+    state.set_joystick(
+        0,
+        down(Key::Up),
+        down(Key::Down),
+        down(Key::Left),
+        down(Key::Right),
+        down(Key::Space),
+    );
+    state.set_joystick(
+        1,
+        down(Key::Y),
+        down(Key::H),
+        down(Key::G),
+        down(Key::J),
+        down(Key::F),
+    );
+    state.set_console_switches(
+        down(Key::F2),
+        down(Key::F1),
+        switches.color,
+        switches.left_difficulty_a,
+        switches.right_difficulty_a,
+    );
+
+    state
+}
+
+/// Copy the visible part of the RGBA frame buffer into minifb's 0RGB pixels
+fn blit(system: &Atari2600, pixels: &mut [u32]) {
+    let width = system.frame_width();
+    let start = FIRST_VISIBLE_SCANLINE * width * 4;
+    let visible = &system.frame_buffer()[start..start + VISIBLE_SCANLINES * width * 4];
+
+    for (rgba, out) in visible
+        .chunks_exact(4)
+        .zip(pixels.chunks_exact_mut(PIXEL_ASPECT))
+    {
+        out.fill(u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]]));
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<Stream, String> {
+    let channels = config.channels as usize;
+    // Repeat the last sample when the emulator falls behind rather than clicking back to 0
+    let mut last_sample = 0.0;
+
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut samples = samples.lock().unwrap();
+                for frame in data.chunks_mut(channels) {
+                    last_sample = samples.pop_front().unwrap_or(last_sample);
+                    frame.fill(T::from_sample(last_sample));
+                }
+            },
+            |error| eprintln!("Audio error: {}", error),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// Plays the samples queued by the emulator on the default output device
+struct Audio {
+    // Playback stops when the stream is dropped
+    _stream: Stream,
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    max_queued_samples: usize,
+}
+
+impl Audio {
+    ///
+    /// Open the default output device and switch the system to its sample
+    /// rate.
+    ///
+    fn start(system: &mut Atari2600) -> Result<Audio, String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device")?;
+        let supported_config = device.default_output_config().map_err(|e| e.to_string())?;
+        let config: StreamConfig = supported_config.config();
+        let samples = Arc::new(Mutex::new(VecDeque::new()));
+
+        let stream = match supported_config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, samples.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, samples.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, samples.clone()),
+            format => Err(format!("Unsupported audio sample format {}", format)),
+        }?;
+        stream.play().map_err(|e| e.to_string())?;
+        system.set_audio_sample_rate(config.sample_rate.0);
+
+        Ok(Audio {
+            _stream: stream,
+            samples,
+            max_queued_samples: (config.sample_rate.0 as f32 * MAX_AUDIO_LATENCY_SECONDS) as usize,
+        })
+    }
+
+    fn queue(&self, frame_samples: Vec<f32>) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.extend(frame_samples);
+
+        let excess = samples.len().saturating_sub(self.max_queued_samples);
+        samples.drain(..excess);
+    }
+}
+
+fn run(rom_path: &str, cartridge_type: Option<&str>) -> Result<(), String> {
+    let rom = std::fs::read(rom_path).map_err(|e| format!("{}: {}", rom_path, e))?;
+    let mut system = match cartridge_type {
+        Some(cartridge_type) => Atari2600::new_with_cartridge_type(&rom, cartridge_type)?,
+        None => Atari2600::new(&rom)?,
+    };
+
+    let width = system.frame_width() * PIXEL_ASPECT;
+    let mut window = Window::new(
+        &format!("Atari 2600 - {}", rom_path),
+        width,
+        VISIBLE_SCANLINES,
+        WindowOptions {
+            scale: Scale::X2,
+            ..WindowOptions::default()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    window.set_target_fps(60);
+
+    // Carry on without sound rather than refusing to run
+    let audio = match Audio::start(&mut system) {
+        Ok(audio) => Some(audio),
+        Err(error) => {
+            eprintln!("Audio disabled: {}", error);
+            None
+        }
+    };
+
+    let mut pixels = vec![0; width * VISIBLE_SCANLINES];
+    let mut switches = Switches {
+        color: true,
+        left_difficulty_a: false,
+        right_difficulty_a: false,
+    };
+    let mut paused = false;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            paused = !paused;
+        }
+
+        if !paused {
+            switches.update(&window);
+            system.set_input_state(&input_state(&window, &switches));
+
+            if window.is_key_down(Key::Backspace) {
+                system.rewind(1);
+            } else {
+                system.run_frame();
+            }
+
+            // Always drain the samples so they don't build up when there's no audio device
+            let frame_samples = system.take_audio_samples();
+            if let Some(audio) = &audio {
+                audio.queue(frame_samples);
+            }
+
+            if system.is_jammed() {
+                eprintln!("CPU jammed by a KIL opcode, restart to recover");
+                paused = true;
+            }
+
+            blit(&system, &mut pixels);
+        }
+
+        window
+            .update_with_buffer(&pixels, width, VISIBLE_SCANLINES)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} <rom> [cartridge type]", args[0]);
+        std::process::exit(2);
+    }
+
+    if let Err(error) = run(&args[1], args.get(2).map(String::as_str)) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,102 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Device;
+
+/// Why the cpu put an address on the bus
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccessKind {
+    /// The first cycle of every instruction
+    OpcodeFetch = 0,
+    /// Any other read whose value is used, e.g. operands, pointers, stack pulls & vectors
+    OperandRead = 1,
+    /// A read made only because the cpu has to drive the bus every cycle, the value is discarded
+    DummyRead = 2,
+    /// The NMOS read modify write instructions write the unmodified value back before the result
+    DummyWrite = 3,
+    Write = 4,
+}
+
+impl BusAccessKind {
+    pub fn is_write(&self) -> bool {
+        matches!(self, BusAccessKind::DummyWrite | BusAccessKind::Write)
+    }
+}
+
+/// A single cycle of bus activity, as seen by an observer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusAccess {
+    /// The cpu cycle the access was made on
+    pub cycle: u32,
+    pub address: u16,
+    pub value: u8,
+    pub kind: BusAccessKind,
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// Any JS object with an `on_bus_access(cycle, address, value, kind)` method
+    pub type BusObserver;
+
+    #[wasm_bindgen(structural, method)]
+    pub fn on_bus_access(
+        this: &BusObserver,
+        cycle: u32,
+        address: u16,
+        value: u8,
+        kind: BusAccessKind,
+    );
+}
+
+///
+/// Everything the cpu can see on its address and data lines. The cpu is
+/// generic over this so that it can be driven by the emulated system, by
+/// test harnesses or (for backwards compatibility) by a JS object.
+///
+pub(crate) trait Bus {
+    fn read_byte(&mut self, address: u16) -> u8;
+
+    fn write_byte(&mut self, address: u16, value: u8);
+
+    ///
+    /// Called by the cpu straight after each read or write with the details
+    /// of the access. Does nothing by default, which the compiler removes
+    /// entirely, so buses which don't care about it cost nothing.
+    ///
+    #[inline(always)]
+    fn observe(&mut self, _access: BusAccess) {}
+}
+
+/// Wraps a bus to hand every access to an observer, for heatmaps, coverage etc.
+pub(crate) struct ObservedBus<'a, B: Bus> {
+    pub(crate) bus: B,
+    pub(crate) observer: &'a mut dyn FnMut(BusAccess),
+}
+
+impl<B: Bus> Bus for ObservedBus<'_, B> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.bus.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.bus.write_byte(address, value);
+    }
+
+    fn observe(&mut self, access: BusAccess) {
+        self.bus.observe(access);
+        (self.observer)(access);
+    }
+}
+
+/// Adapter allowing a JS object implementing the `Device` interface to act as the bus
+pub(crate) struct JsDevice<'a>(pub(crate) &'a Device);
+
+impl Bus for JsDevice<'_> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.0.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.0.write_byte(address, value);
+    }
+}
@@ -0,0 +1,202 @@
+use super::{CartridgeType, BANK_SIZE};
+
+// Byte sequences which are only likely to appear in roms using a particular
+// scheme, generally the instructions used to hit its hotspots. These are the
+// same signatures that Stella uses.
+
+/// STA $3F
+const SIGNATURES_3F: [&[u8]; 1] = [&[0x85, 0x3F]];
+
+/// STA $3E followed by LDA #$00
+const SIGNATURES_3E: [&[u8]; 1] = [&[0x85, 0x3E, 0xA9, 0x00]];
+
+const SIGNATURES_E0: [&[u8]; 8] = [
+    &[0x8D, 0xE0, 0x1F], // STA $1FE0
+    &[0x8D, 0xE0, 0x5F], // STA $5FE0
+    &[0x8D, 0xE9, 0xFF], // STA $FFE9
+    &[0x0C, 0xE0, 0x1F], // NOP $1FE0
+    &[0xAD, 0xE0, 0x1F], // LDA $1FE0
+    &[0xAD, 0xE9, 0xFF], // LDA $FFE9
+    &[0xAD, 0xED, 0xFF], // LDA $FFED
+    &[0xAD, 0xF3, 0xBF], // LDA $BFF3
+];
+
+const SIGNATURES_E7: [&[u8]; 7] = [
+    &[0xAD, 0xE2, 0xFF], // LDA $FFE2
+    &[0xAD, 0xE5, 0xFF], // LDA $FFE5
+    &[0xAD, 0xE5, 0x1F], // LDA $1FE5
+    &[0xAD, 0xE7, 0x1F], // LDA $1FE7
+    &[0x0C, 0xE7, 0x1F], // NOP $1FE7
+    &[0x8D, 0xE7, 0xFF], // STA $FFE7
+    &[0x8D, 0xE7, 0x1F], // STA $1FE7
+];
+
+const SIGNATURES_FE: [&[u8]; 4] = [
+    &[0x20, 0x00, 0xD0, 0xC6, 0xC5], // JSR $D000; DEC $C5
+    &[0x20, 0xC3, 0xF8, 0xA5, 0x82], // JSR $F8C3; LDA $82
+    &[0xD0, 0xFB, 0x20, 0x73, 0xFE], // BNE $FB; JSR $FE73
+    &[0x20, 0x00, 0xF0, 0x84, 0xD6], // JSR $F000; STY $D6
+];
+
+const SIGNATURES_UA: [&[u8]; 3] = [
+    &[0x8D, 0x40, 0x02], // STA $240
+    &[0xAD, 0x40, 0x02], // LDA $240
+    &[0xBD, 0x1F, 0x02], // LDA $21F,X
+];
+
+fn count_signature(rom: &[u8], signature: &[u8]) -> usize {
+    rom.windows(signature.len())
+        .filter(|window| *window == signature)
+        .count()
+}
+
+fn has_any_signature(rom: &[u8], signatures: &[&[u8]]) -> bool {
+    signatures
+        .iter()
+        .any(|signature| count_signature(rom, signature) > 0)
+}
+
+fn is_probably_3e(rom: &[u8]) -> bool {
+    has_any_signature(rom, &SIGNATURES_3E)
+}
+
+/// A single STA $3F could easily be coincidence so look for a couple
+fn is_probably_3f(rom: &[u8]) -> bool {
+    count_signature(rom, SIGNATURES_3F[0]) >= 2
+}
+
+///
+/// Superchip roms leave the area of each bank hidden by the RAM (the first
+/// 256 bytes) filled with a single value.
+///
+fn is_probably_superchip(rom: &[u8]) -> bool {
+    rom.chunks(BANK_SIZE).all(|bank| {
+        let first = bank[0];
+        bank[..256].iter().all(|&byte| byte == first)
+    })
+}
+
+///
+/// Pick the bank switching scheme for a rom from its size and, where several
+/// schemes share a size, the instructions it uses to access their hotspots.
+///
+pub(super) fn detect(rom: &[u8]) -> Result<CartridgeType, String> {
+    let cartridge_type = match rom.len() {
+        0x800 => CartridgeType::Rom2K,
+        0x1000 => CartridgeType::Rom4K,
+        0x2000 => {
+            if is_probably_3e(rom) {
+                CartridgeType::ThreeE
+            } else if is_probably_3f(rom) {
+                CartridgeType::ThreeF
+            } else if has_any_signature(rom, &SIGNATURES_E0) {
+                CartridgeType::E0
+            } else if has_any_signature(rom, &SIGNATURES_FE) {
+                CartridgeType::FE
+            } else if has_any_signature(rom, &SIGNATURES_UA) {
+                CartridgeType::UA
+            } else if is_probably_superchip(rom) {
+                CartridgeType::F8SC
+            } else {
+                CartridgeType::F8
+            }
+        }
+        0x3000 => CartridgeType::FA,
+        0x4000 => {
+            if is_probably_3e(rom) {
+                CartridgeType::ThreeE
+            } else if is_probably_3f(rom) {
+                CartridgeType::ThreeF
+            } else if has_any_signature(rom, &SIGNATURES_E7) {
+                CartridgeType::E7
+            } else if is_probably_superchip(rom) {
+                CartridgeType::F6SC
+            } else {
+                CartridgeType::F6
+            }
+        }
+        0x8000 => {
+            if is_probably_3e(rom) {
+                CartridgeType::ThreeE
+            } else if is_probably_3f(rom) {
+                CartridgeType::ThreeF
+            } else if is_probably_superchip(rom) {
+                CartridgeType::F4SC
+            } else {
+                CartridgeType::F4
+            }
+        }
+        size if size > 0x8000 && size.is_multiple_of(0x800) => match is_probably_3e(rom) {
+            true => CartridgeType::ThreeE,
+            false => CartridgeType::ThreeF,
+        },
+        size => {
+            return Err(format!(
+                "Unable to detect cartridge type of {} byte rom",
+                size
+            ))
+        }
+    };
+
+    Ok(cartridge_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect;
+    use crate::cartridge::CartridgeType;
+
+    /// A rom full of varied bytes which won't be mistaken for a Superchip rom
+    fn rom(size: usize) -> Vec<u8> {
+        (0..size).map(|ix| (ix % 251) as u8).collect()
+    }
+
+    fn rom_with_signature(size: usize, signature: &[u8], offset: usize) -> Vec<u8> {
+        let mut rom = rom(size);
+        rom[offset..offset + signature.len()].copy_from_slice(signature);
+        rom
+    }
+
+    #[test]
+    fn test_detect_by_size() {
+        assert_eq!(detect(&rom(0x800)), Ok(CartridgeType::Rom2K));
+        assert_eq!(detect(&rom(0x1000)), Ok(CartridgeType::Rom4K));
+        assert_eq!(detect(&rom(0x2000)), Ok(CartridgeType::F8));
+        assert_eq!(detect(&rom(0x3000)), Ok(CartridgeType::FA));
+        assert_eq!(detect(&rom(0x4000)), Ok(CartridgeType::F6));
+        assert_eq!(detect(&rom(0x8000)), Ok(CartridgeType::F4));
+        assert_eq!(detect(&rom(0x10000)), Ok(CartridgeType::ThreeF));
+        assert!(detect(&rom(1234)).is_err());
+    }
+
+    #[test]
+    fn test_detect_superchip() {
+        let mut rom = rom(0x4000);
+        for bank in rom.chunks_mut(0x1000) {
+            bank[..256].fill(0xFF);
+        }
+        assert_eq!(detect(&rom), Ok(CartridgeType::F6SC));
+    }
+
+    #[test]
+    fn test_detect_by_signature() {
+        let e0 = rom_with_signature(0x2000, &[0x8D, 0xE0, 0x1F], 0x100);
+        assert_eq!(detect(&e0), Ok(CartridgeType::E0));
+
+        let fe = rom_with_signature(0x2000, &[0x20, 0x00, 0xD0, 0xC6, 0xC5], 0x100);
+        assert_eq!(detect(&fe), Ok(CartridgeType::FE));
+
+        let ua = rom_with_signature(0x2000, &[0x8D, 0x40, 0x02], 0x100);
+        assert_eq!(detect(&ua), Ok(CartridgeType::UA));
+
+        let e7 = rom_with_signature(0x4000, &[0xAD, 0xE5, 0x1F], 0x100);
+        assert_eq!(detect(&e7), Ok(CartridgeType::E7));
+
+        let mut three_f = rom_with_signature(0x4000, &[0x85, 0x3F], 0x100);
+        three_f[0x200..0x202].copy_from_slice(&[0x85, 0x3F]);
+        assert_eq!(detect(&three_f), Ok(CartridgeType::ThreeF));
+
+        let three_e = rom_with_signature(0x8000, &[0x85, 0x3E, 0xA9, 0x00], 0x100);
+        assert_eq!(detect(&three_e), Ok(CartridgeType::ThreeE));
+    }
+}
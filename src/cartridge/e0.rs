@@ -0,0 +1,63 @@
+use super::Cartridge;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+const SLICE_SIZE: usize = 0x400;
+
+///
+/// Parker Brothers 8K scheme. The window is split into four 1K segments, the
+/// first three of which can each show any of the eight 1K slices of the rom
+/// by accessing $1FE0-$1FE7, $1FE8-$1FEF and $1FF0-$1FF7 respectively. The
+/// last segment is fixed to the last slice.
+///
+pub(super) struct E0 {
+    rom: Box<[u8]>,
+    slices: [usize; 4],
+}
+
+impl E0 {
+    pub(super) fn new(rom: &[u8]) -> Self {
+        E0 {
+            rom: rom.into(),
+            slices: [4, 5, 6, 7],
+        }
+    }
+
+    fn check_hotspot(&mut self, address: u16) {
+        if (0xFE0..=0xFF7).contains(&address) {
+            let segment = ((address - 0xFE0) / 8) as usize;
+            self.slices[segment] = (address & 0b111) as usize;
+        }
+    }
+}
+
+impl Cartridge for E0 {
+    fn peek_byte(&self, address: u16) -> u8 {
+        let slice = self.slices[address as usize / SLICE_SIZE];
+        self.rom[slice * SLICE_SIZE + (address as usize % SLICE_SIZE)]
+    }
+
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.check_hotspot(address);
+        self.peek_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, _value: u8) {
+        self.check_hotspot(address);
+    }
+}
+
+impl SaveState for E0 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for slice in self.slices {
+            writer.write_u32(slice as u32);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for slice in self.slices.iter_mut() {
+            *slice = reader.read_index(self.rom.len() / SLICE_SIZE, "slice")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use super::{Cartridge, OPEN_BUS};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+const BANK_SIZE: usize = 0x800;
+/// Selecting this bank with $1FE7 maps the 1K RAM into the lower segment instead of rom
+const RAM_BANK: usize = 7;
+const LOWER_RAM_SIZE: usize = 0x400;
+const UPPER_RAM_BANK_SIZE: usize = 0x100;
+
+///
+/// M-Network 16K scheme with 2K of RAM.
+///
+/// - $1000-$17FF shows one of the first seven 2K rom banks ($1FE0-$1FE6) or,
+///   after $1FE7, 1K of RAM written at $1000-$13FF and read at $1400-$17FF.
+/// - $1800-$19FF shows one of four 256 byte RAM banks ($1FE8-$1FEB) written
+///   at $1800-$18FF and read at $1900-$19FF.
+/// - $1A00-$1FFF is fixed to the end of the last rom bank.
+///
+pub(super) struct E7 {
+    rom: Box<[u8]>,
+    bank: usize,
+    upper_ram_bank: usize,
+    ram: Box<[u8]>,
+}
+
+impl E7 {
+    pub(super) fn new(rom: &[u8]) -> Self {
+        E7 {
+            rom: rom.into(),
+            bank: 0,
+            upper_ram_bank: 0,
+            ram: vec![0; LOWER_RAM_SIZE + 4 * UPPER_RAM_BANK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    fn check_hotspot(&mut self, address: u16) {
+        match address {
+            0xFE0..=0xFE7 => self.bank = (address & 0b111) as usize,
+            0xFE8..=0xFEB => self.upper_ram_bank = (address & 0b11) as usize,
+            _ => {}
+        }
+    }
+
+    fn upper_ram_offset(&self, address: u16) -> usize {
+        LOWER_RAM_SIZE + self.upper_ram_bank * UPPER_RAM_BANK_SIZE + (address as usize & 0xFF)
+    }
+}
+
+impl Cartridge for E7 {
+    fn peek_byte(&self, address: u16) -> u8 {
+        match address {
+            0x000..=0x3FF if self.bank == RAM_BANK => OPEN_BUS,
+            0x400..=0x7FF if self.bank == RAM_BANK => self.ram[address as usize - 0x400],
+            0x000..=0x7FF => self.rom[self.bank * BANK_SIZE + address as usize],
+            0x800..=0x8FF => OPEN_BUS,
+            0x900..=0x9FF => self.ram[self.upper_ram_offset(address)],
+            _ => self.rom[RAM_BANK * BANK_SIZE + (address as usize - 0x800)],
+        }
+    }
+
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.check_hotspot(address);
+        self.peek_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.check_hotspot(address);
+
+        match address {
+            0x000..=0x3FF if self.bank == RAM_BANK => self.ram[address as usize] = value,
+            0x800..=0x8FF => {
+                let offset = self.upper_ram_offset(address);
+                self.ram[offset] = value;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for E7 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.bank as u32);
+        writer.write_u32(self.upper_ram_bank as u32);
+        writer.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.bank = reader.read_index(RAM_BANK + 1, "bank")?;
+        self.upper_ram_bank = reader.read_index(4, "RAM bank")?;
+        reader.read_bytes_into(&mut self.ram)
+    }
+}
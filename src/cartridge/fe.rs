@@ -0,0 +1,57 @@
+use super::{Cartridge, BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+///
+/// Activision 8K scheme. There are no hotspots, instead the cartridge
+/// watches for the stack accesses made by JSR and RTS. The byte on the data
+/// bus after an access to $01FE is the high byte of the new program counter,
+/// bit 5 of which picks the bank ($Fxxx is bank 0, $Dxxx bank 1).
+///
+pub(super) struct FE {
+    rom: Box<[u8]>,
+    bank: usize,
+    last_access_was_01fe: bool,
+}
+
+impl FE {
+    pub(super) fn new(rom: &[u8]) -> Self {
+        FE {
+            rom: rom.into(),
+            bank: 0,
+            last_access_was_01fe: false,
+        }
+    }
+}
+
+impl Cartridge for FE {
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.rom[self.bank * BANK_SIZE + address as usize]
+    }
+
+    fn write_byte(&mut self, _address: u16, _value: u8) {}
+
+    fn snoop(&mut self, address: u16, value: u8, _is_write: bool) {
+        if self.last_access_was_01fe {
+            self.bank = match value & 0b0010_0000 {
+                0 => 1,
+                _ => 0,
+            };
+        }
+
+        self.last_access_was_01fe = address == 0x01FE;
+    }
+}
+
+impl SaveState for FE {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.bank as u32);
+        writer.write_bool(self.last_access_was_01fe);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.bank = reader.read_index(self.rom.len() / BANK_SIZE, "bank")?;
+        self.last_access_was_01fe = reader.read_bool()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+mod detection;
+mod e0;
+mod e7;
+mod fe;
+mod standard;
+mod tigervision;
+mod ua;
+mod unbanked;
+
+use std::str::FromStr;
+
+use crate::save_state::SaveState;
+
+use detection::detect;
+use e0::E0;
+use e7::E7;
+use fe::FE;
+use standard::Standard;
+use tigervision::Tigervision;
+use ua::UA;
+use unbanked::Unbanked;
+
+const BANK_SIZE: usize = 0x1000;
+
+/// Value returned when reading from a cartridge RAM write port, nothing drives the data bus
+const OPEN_BUS: u8 = 0x00;
+
+///
+/// A cartridge is mapped into the top 4K of the 6507's 8K address space (A12
+/// high). Anything larger than 4K uses one of many bank switching schemes to
+/// swap parts of the rom (or extra RAM) into that window.
+///
+/// The save state of a cartridge covers its bank selection and RAM, never
+/// the rom itself.
+///
+pub(crate) trait Cartridge: SaveState {
+    ///
+    /// Read from the cartridge without triggering any bank switching, used
+    /// by the debugger. The address is relative to the start of the 4K window.
+    ///
+    fn peek_byte(&self, address: u16) -> u8;
+
+    /// Read from the cartridge, the address is relative to the start of the 4K window
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.peek_byte(address)
+    }
+
+    /// Write to the cartridge, the address is relative to the start of the 4K window
+    fn write_byte(&mut self, address: u16, value: u8);
+
+    ///
+    /// Called with every access the cpu makes, including those outside of
+    /// the cartridge window, so that schemes which bank switch by watching
+    /// the bus (3F, 3E, FE, UA) can do so. The address is the full 13 bit
+    /// address and the value is whatever was read or written.
+    ///
+    fn snoop(&mut self, _address: u16, _value: u8, _is_write: bool) {}
+}
+
+///
+/// The supported bank switching schemes, named as they are in Stella. SC
+/// variants carry the 128 byte Superchip RAM.
+///
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CartridgeType {
+    Rom2K,
+    Rom4K,
+    F8,
+    F8SC,
+    F6,
+    F6SC,
+    F4,
+    F4SC,
+    E0,
+    E7,
+    FE,
+    ThreeF,
+    ThreeE,
+    FA,
+    UA,
+}
+
+impl FromStr for CartridgeType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_uppercase().as_str() {
+            "2K" => Ok(CartridgeType::Rom2K),
+            "4K" => Ok(CartridgeType::Rom4K),
+            "F8" => Ok(CartridgeType::F8),
+            "F8SC" => Ok(CartridgeType::F8SC),
+            "F6" => Ok(CartridgeType::F6),
+            "F6SC" => Ok(CartridgeType::F6SC),
+            "F4" => Ok(CartridgeType::F4),
+            "F4SC" => Ok(CartridgeType::F4SC),
+            "E0" => Ok(CartridgeType::E0),
+            "E7" => Ok(CartridgeType::E7),
+            "FE" => Ok(CartridgeType::FE),
+            "3F" => Ok(CartridgeType::ThreeF),
+            "3E" => Ok(CartridgeType::ThreeE),
+            "FA" => Ok(CartridgeType::FA),
+            "UA" => Ok(CartridgeType::UA),
+            _ => Err(format!("Unknown cartridge type {}", name)),
+        }
+    }
+}
+
+impl CartridgeType {
+    /// Whether a rom of the given size can be used with this scheme
+    fn supports_size(&self, size: usize) -> bool {
+        match self {
+            CartridgeType::Rom2K => size == 0x800,
+            CartridgeType::Rom4K => size == 0x1000,
+            CartridgeType::F8 | CartridgeType::F8SC => size == 0x2000,
+            CartridgeType::F6 | CartridgeType::F6SC => size == 0x4000,
+            CartridgeType::F4 | CartridgeType::F4SC => size == 0x8000,
+            CartridgeType::E0 | CartridgeType::FE | CartridgeType::UA => size == 0x2000,
+            CartridgeType::E7 => size == 0x4000,
+            CartridgeType::FA => size == 0x3000,
+            CartridgeType::ThreeF | CartridgeType::ThreeE => {
+                size >= 0x1000 && size.is_multiple_of(0x800)
+            }
+        }
+    }
+}
+
+///
+/// Build the cartridge for a rom, using the given bank switching scheme or
+/// detecting one from the contents of the rom if none is given.
+///
+pub(crate) fn new_cartridge(
+    rom: &[u8],
+    cartridge_type: Option<CartridgeType>,
+) -> Result<Box<dyn Cartridge>, String> {
+    let cartridge_type = match cartridge_type {
+        Some(cartridge_type) => cartridge_type,
+        None => detect(rom)?,
+    };
+
+    if !cartridge_type.supports_size(rom.len()) {
+        return Err(format!(
+            "A {} byte rom can't be used with cartridge type {:?}",
+            rom.len(),
+            cartridge_type
+        ));
+    }
+
+    Ok(match cartridge_type {
+        CartridgeType::Rom2K | CartridgeType::Rom4K => Box::new(Unbanked::new(rom)),
+        CartridgeType::F8 => Box::new(Standard::new(rom, 0xFF8, 0)),
+        CartridgeType::F8SC => Box::new(Standard::new(rom, 0xFF8, 128)),
+        CartridgeType::F6 => Box::new(Standard::new(rom, 0xFF6, 0)),
+        CartridgeType::F6SC => Box::new(Standard::new(rom, 0xFF6, 128)),
+        CartridgeType::F4 => Box::new(Standard::new(rom, 0xFF4, 0)),
+        CartridgeType::F4SC => Box::new(Standard::new(rom, 0xFF4, 128)),
+        CartridgeType::FA => Box::new(Standard::new(rom, 0xFF8, 256)),
+        CartridgeType::E0 => Box::new(E0::new(rom)),
+        CartridgeType::E7 => Box::new(E7::new(rom)),
+        CartridgeType::FE => Box::new(FE::new(rom)),
+        CartridgeType::ThreeF => Box::new(Tigervision::new(rom, false)),
+        CartridgeType::ThreeE => Box::new(Tigervision::new(rom, true)),
+        CartridgeType::UA => Box::new(UA::new(rom)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_cartridge, Cartridge, CartridgeType};
+
+    /// Build a rom where every byte of each 1K block contains the block number
+    fn numbered_rom(size: usize) -> Vec<u8> {
+        (0..size).map(|ix| (ix / 0x400) as u8).collect()
+    }
+
+    fn cartridge(size: usize, cartridge_type: CartridgeType) -> Box<dyn Cartridge> {
+        new_cartridge(&numbered_rom(size), Some(cartridge_type)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_cartridge_type() {
+        assert_eq!("f8sc".parse(), Ok(CartridgeType::F8SC));
+        assert_eq!("3E".parse(), Ok(CartridgeType::ThreeE));
+        assert!("XYZ".parse::<CartridgeType>().is_err());
+    }
+
+    #[test]
+    fn test_override_rejects_wrong_size() {
+        assert!(new_cartridge(&[0; 0x1000], Some(CartridgeType::F8)).is_err());
+    }
+
+    #[test]
+    fn test_2k_is_mirrored() {
+        let mut cart = cartridge(0x800, CartridgeType::Rom2K);
+        assert_eq!(cart.read_byte(0x7FF), 1);
+        assert_eq!(cart.read_byte(0xC00), 1);
+    }
+
+    #[test]
+    fn test_f8_switches_on_read_and_write() {
+        let mut cart = cartridge(0x2000, CartridgeType::F8);
+        // Starts in the last bank
+        assert_eq!(cart.read_byte(0x000), 4);
+        cart.read_byte(0xFF8);
+        assert_eq!(cart.read_byte(0x000), 0);
+        cart.write_byte(0xFF9, 0);
+        assert_eq!(cart.read_byte(0x400), 5);
+    }
+
+    #[test]
+    fn test_f6_and_f4_hotspots() {
+        let mut cart = cartridge(0x4000, CartridgeType::F6);
+        cart.read_byte(0xFF7);
+        assert_eq!(cart.read_byte(0x000), 4);
+
+        let mut cart = cartridge(0x8000, CartridgeType::F4);
+        cart.read_byte(0xFFA);
+        assert_eq!(cart.read_byte(0x000), 24);
+        // $1FFC is outside the F4 hotspots
+        cart.read_byte(0xFFC);
+        assert_eq!(cart.read_byte(0x000), 24);
+    }
+
+    #[test]
+    fn test_superchip_ram() {
+        let mut cart = cartridge(0x2000, CartridgeType::F8SC);
+        cart.write_byte(0x000, 0x12);
+        cart.write_byte(0x07F, 0x34);
+        assert_eq!(cart.read_byte(0x080), 0x12);
+        assert_eq!(cart.read_byte(0x0FF), 0x34);
+
+        // RAM is unaffected by bank switching
+        cart.read_byte(0xFF8);
+        assert_eq!(cart.read_byte(0x080), 0x12);
+    }
+
+    #[test]
+    fn test_fa_ram_and_banks() {
+        let mut cart = cartridge(0x3000, CartridgeType::FA);
+        cart.write_byte(0x0FF, 0x56);
+        assert_eq!(cart.read_byte(0x1FF), 0x56);
+        cart.read_byte(0xFF9);
+        assert_eq!(cart.read_byte(0x400), 5);
+    }
+
+    #[test]
+    fn test_peek_does_not_switch_banks() {
+        let mut cart = cartridge(0x2000, CartridgeType::F8);
+        assert_eq!(cart.peek_byte(0xFF8), 7);
+        assert_eq!(cart.read_byte(0x000), 4);
+    }
+
+    #[test]
+    fn test_e0_slices() {
+        let mut cart = cartridge(0x2000, CartridgeType::E0);
+        cart.read_byte(0xFE1); // Slice 1 into segment 0
+        cart.read_byte(0xFEA); // Slice 2 into segment 1
+        cart.read_byte(0xFF3); // Slice 3 into segment 2
+        assert_eq!(cart.read_byte(0x000), 1);
+        assert_eq!(cart.read_byte(0x400), 2);
+        assert_eq!(cart.read_byte(0x800), 3);
+        // The last segment is always fixed to slice 7
+        assert_eq!(cart.read_byte(0xC00), 7);
+    }
+
+    #[test]
+    fn test_e7_banks_and_ram() {
+        let mut cart = cartridge(0x4000, CartridgeType::E7);
+        cart.read_byte(0xFE3);
+        assert_eq!(cart.read_byte(0x000), 6);
+        assert_eq!(cart.read_byte(0xA00), 14);
+
+        // $1FE7 maps the 1K RAM into the lower segment
+        cart.read_byte(0xFE7);
+        cart.write_byte(0x000, 0x12);
+        assert_eq!(cart.read_byte(0x400), 0x12);
+
+        // 256 byte RAM banks
+        cart.read_byte(0xFE9);
+        cart.write_byte(0x810, 0x34);
+        assert_eq!(cart.read_byte(0x910), 0x34);
+        cart.read_byte(0xFE8);
+        assert_eq!(cart.read_byte(0x910), 0x00);
+    }
+
+    #[test]
+    fn test_fe_switches_on_stack_access() {
+        let mut cart = cartridge(0x2000, CartridgeType::FE);
+        assert_eq!(cart.read_byte(0x000), 0);
+
+        // JSR $D000 - pushes to $01FF, $01FE then reads the high byte of the target
+        cart.snoop(0x01FF, 0xF0, true);
+        cart.snoop(0x01FE, 0x12, true);
+        cart.snoop(0x1002, 0xD0, false);
+        assert_eq!(cart.read_byte(0x000), 4);
+
+        cart.snoop(0x01FE, 0x05, false);
+        cart.snoop(0x01FF, 0xF0, false);
+        assert_eq!(cart.read_byte(0x000), 0);
+    }
+
+    #[test]
+    fn test_3f_switches_on_tia_writes() {
+        let mut cart = cartridge(0x4000, CartridgeType::ThreeF);
+        cart.snoop(0x003F, 3, true);
+        assert_eq!(cart.read_byte(0x000), 6);
+        // The top 2K is fixed to the last bank
+        assert_eq!(cart.read_byte(0x800), 14);
+        // Reads don't switch
+        cart.snoop(0x0000, 1, false);
+        assert_eq!(cart.read_byte(0x000), 6);
+    }
+
+    #[test]
+    fn test_3e_ram() {
+        let mut cart = cartridge(0x8000, CartridgeType::ThreeE);
+        cart.snoop(0x003E, 1, true);
+        cart.write_byte(0x400, 0x12);
+        assert_eq!(cart.read_byte(0x000), 0x12);
+
+        cart.snoop(0x003E, 2, true);
+        assert_eq!(cart.read_byte(0x000), 0x00);
+
+        cart.snoop(0x003F, 1, true);
+        assert_eq!(cart.read_byte(0x000), 2);
+    }
+
+    #[test]
+    fn test_ua_hotspots() {
+        let mut cart = cartridge(0x2000, CartridgeType::UA);
+        cart.snoop(0x0240, 0, false);
+        assert_eq!(cart.read_byte(0x000), 4);
+        cart.snoop(0x0220, 0, true);
+        assert_eq!(cart.read_byte(0x000), 0);
+    }
+}
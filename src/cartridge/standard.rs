@@ -0,0 +1,77 @@
+use super::{Cartridge, BANK_SIZE, OPEN_BUS};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+///
+/// The Atari standard schemes (F8, F6, F4) and CBS RAM+ (FA). Accessing one
+/// of a run of hotspots at the top of the window selects which 4K bank is
+/// visible.
+///
+/// The optional RAM (128 bytes for Superchip, 256 for FA) sits at the start
+/// of the window with separate write and read ports, e.g. for Superchip
+/// writes go to $1000-$107F and reads come from $1080-$10FF.
+///
+pub(super) struct Standard {
+    rom: Box<[u8]>,
+    bank: usize,
+    first_hotspot: u16,
+    ram: Box<[u8]>,
+}
+
+impl Standard {
+    pub(super) fn new(rom: &[u8], first_hotspot: u16, ram_size: usize) -> Self {
+        Standard {
+            rom: rom.into(),
+            // Start in the last bank, it's the one which is guaranteed to contain a reset vector
+            bank: rom.len() / BANK_SIZE - 1,
+            first_hotspot,
+            ram: vec![0; ram_size].into_boxed_slice(),
+        }
+    }
+
+    fn check_hotspot(&mut self, address: u16) {
+        let bank_count = (self.rom.len() / BANK_SIZE) as u16;
+
+        if address >= self.first_hotspot && address < self.first_hotspot + bank_count {
+            self.bank = (address - self.first_hotspot) as usize;
+        }
+    }
+}
+
+impl Cartridge for Standard {
+    fn peek_byte(&self, address: u16) -> u8 {
+        let address = address as usize;
+        let ram_size = self.ram.len();
+        if address < ram_size {
+            OPEN_BUS
+        } else if address < ram_size * 2 {
+            self.ram[address - ram_size]
+        } else {
+            self.rom[self.bank * BANK_SIZE + address]
+        }
+    }
+
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.check_hotspot(address);
+        self.peek_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.check_hotspot(address);
+
+        if let Some(byte) = self.ram.get_mut(address as usize) {
+            *byte = value;
+        }
+    }
+}
+
+impl SaveState for Standard {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.bank as u32);
+        writer.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.bank = reader.read_index(self.rom.len() / BANK_SIZE, "bank")?;
+        reader.read_bytes_into(&mut self.ram)
+    }
+}
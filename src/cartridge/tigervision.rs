@@ -0,0 +1,98 @@
+use super::{Cartridge, OPEN_BUS};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+const BANK_SIZE: usize = 0x800;
+const RAM_BANK_SIZE: usize = 0x400;
+const RAM_BANKS: usize = 32;
+
+///
+/// Tigervision 3F scheme and its 3E extension. The window is split into two
+/// 2K segments, the upper one fixed to the last 2K of the rom and the lower
+/// one selected by writing the bank number to a TIA address.
+///
+/// - 3F switches on a write to any of $00-$3F.
+/// - 3E switches rom on a write to $3F and maps one of 32 1K RAM banks into
+///   the lower segment on a write to $3E. RAM is read from $1000-$13FF and
+///   written at $1400-$17FF.
+///
+pub(super) struct Tigervision {
+    rom: Box<[u8]>,
+    rom_bank: usize,
+    ram: Box<[u8]>,
+    ram_bank: Option<usize>,
+}
+
+impl Tigervision {
+    pub(super) fn new(rom: &[u8], with_ram: bool) -> Self {
+        Tigervision {
+            rom: rom.into(),
+            rom_bank: 0,
+            ram: match with_ram {
+                true => vec![0; RAM_BANKS * RAM_BANK_SIZE].into_boxed_slice(),
+                false => Box::new([]),
+            },
+            ram_bank: None,
+        }
+    }
+
+    fn bank_count(&self) -> usize {
+        self.rom.len() / BANK_SIZE
+    }
+}
+
+impl Cartridge for Tigervision {
+    fn peek_byte(&self, address: u16) -> u8 {
+        let address = address as usize;
+
+        match (address < BANK_SIZE, self.ram_bank) {
+            (true, Some(bank)) if address < RAM_BANK_SIZE => {
+                self.ram[bank * RAM_BANK_SIZE + address]
+            }
+            (true, Some(_)) => OPEN_BUS,
+            (true, None) => self.rom[self.rom_bank * BANK_SIZE + address],
+            (false, _) => self.rom[self.rom.len() - 2 * BANK_SIZE + address],
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        let address = address as usize;
+
+        if let Some(bank) = self.ram_bank {
+            if (RAM_BANK_SIZE..BANK_SIZE).contains(&address) {
+                self.ram[bank * RAM_BANK_SIZE + address - RAM_BANK_SIZE] = value;
+            }
+        }
+    }
+
+    fn snoop(&mut self, address: u16, value: u8, is_write: bool) {
+        if !is_write || address > 0x3F {
+            return;
+        }
+
+        match (self.ram.is_empty(), address) {
+            (true, _) | (false, 0x3F) => {
+                self.rom_bank = value as usize % self.bank_count();
+                self.ram_bank = None;
+            }
+            (false, 0x3E) => self.ram_bank = Some(value as usize % RAM_BANKS),
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for Tigervision {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.rom_bank as u32);
+        writer.write_bool(self.ram_bank.is_some());
+        writer.write_u32(self.ram_bank.unwrap_or(0) as u32);
+        writer.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.rom_bank = reader.read_index(self.bank_count(), "bank")?;
+        let has_ram_bank = reader.read_bool()?;
+        let ram_bank = reader.read_index(RAM_BANKS, "RAM bank")?;
+        self.ram_bank = (has_ram_bank && !self.ram.is_empty()).then_some(ram_bank);
+        reader.read_bytes_into(&mut self.ram)
+    }
+}
@@ -0,0 +1,48 @@
+use super::{Cartridge, BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+///
+/// UA Limited 8K scheme, the hotspots sit outside of the cartridge window
+/// so accessing $0220 selects bank 0 and $0240 selects bank 1.
+///
+pub(super) struct UA {
+    rom: Box<[u8]>,
+    bank: usize,
+}
+
+impl UA {
+    pub(super) fn new(rom: &[u8]) -> Self {
+        UA {
+            rom: rom.into(),
+            bank: 0,
+        }
+    }
+}
+
+impl Cartridge for UA {
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.rom[self.bank * BANK_SIZE + address as usize]
+    }
+
+    fn write_byte(&mut self, _address: u16, _value: u8) {}
+
+    fn snoop(&mut self, address: u16, _value: u8, _is_write: bool) {
+        match address {
+            0x0220 => self.bank = 0,
+            0x0240 => self.bank = 1,
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for UA {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.bank as u32);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.bank = reader.read_index(2, "bank")?;
+
+        Ok(())
+    }
+}
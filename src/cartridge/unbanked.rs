@@ -0,0 +1,31 @@
+use super::Cartridge;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// 2K & 4K roms with no bank switching, a 2K rom is mirrored into both halves of the window
+pub(super) struct Unbanked {
+    rom: Box<[u8]>,
+}
+
+impl Unbanked {
+    pub(super) fn new(rom: &[u8]) -> Self {
+        Unbanked { rom: rom.into() }
+    }
+}
+
+impl Cartridge for Unbanked {
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.rom[address as usize & (self.rom.len() - 1)]
+    }
+
+    fn write_byte(&mut self, _address: u16, _value: u8) {
+        // Writes to rom are ignored
+    }
+}
+
+impl SaveState for Unbanked {
+    fn save_state(&self, _writer: &mut StateWriter) {}
+
+    fn load_state(&mut self, _reader: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use super::test_bus::TestBus;
+use super::Cpu;
+
+///
+/// An NMOS 6502 wired straight to 64K of RAM, so the benchmarks can measure
+/// the core without the TIA & RIOT. This isn't part of the public API.
+///
+#[doc(hidden)]
+pub struct BenchCpu {
+    cpu: Cpu,
+    ram: TestBus,
+}
+
+impl BenchCpu {
+    /// Loads `program` at $F000 and points the reset vector at it
+    pub fn new(program: &[u8]) -> BenchCpu {
+        let mut ram = TestBus::with_program(0, 0xF000, program);
+        // Recording would measure the Vec rather than the cpu
+        ram.recording = false;
+        let cpu = Cpu::new(0, &mut ram);
+
+        BenchCpu { cpu, ram }
+    }
+
+    /// Runs for a number of cycles, returning the program counter so the work can't be skipped
+    pub fn run(&mut self, cycles: u32) -> u16 {
+        for _ in 0..cycles {
+            self.cpu.clock(&mut self.ram);
+        }
+
+        self.cpu.registers.program_counter
+    }
+}
@@ -0,0 +1,371 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use super::opcodes::{AddressingMode, InstructionLength, InstructionType, Operation};
+use super::CpuVariant;
+
+/// TIA registers as seen by writes, indexed by address & $3F
+const TIA_WRITE_SYMBOLS: [&str; 0x2D] = [
+    "VSYNC", "VBLANK", "WSYNC", "RSYNC", "NUSIZ0", "NUSIZ1", "COLUP0", "COLUP1", "COLUPF",
+    "COLUBK", "CTRLPF", "REFP0", "REFP1", "PF0", "PF1", "PF2", "RESP0", "RESP1", "RESM0", "RESM1",
+    "RESBL", "AUDC0", "AUDC1", "AUDF0", "AUDF1", "AUDV0", "AUDV1", "GRP0", "GRP1", "ENAM0",
+    "ENAM1", "ENABL", "HMP0", "HMP1", "HMM0", "HMM1", "HMBL", "VDELP0", "VDELP1", "VDELBL",
+    "RESMP0", "RESMP1", "HMOVE", "HMCLR", "CXCLR",
+];
+
+/// TIA registers as seen by reads, indexed by address & $0F
+const TIA_READ_SYMBOLS: [&str; 0x0E] = [
+    "CXM0P", "CXM1P", "CXP0FB", "CXP1FB", "CXM0FB", "CXM1FB", "CXBLPF", "CXPPMM", "INPT0", "INPT1",
+    "INPT2", "INPT3", "INPT4", "INPT5",
+];
+
+const RIOT_SYMBOLS: [(u16, &str); 10] = [
+    (0x280, "SWCHA"),
+    (0x281, "SWACNT"),
+    (0x282, "SWCHB"),
+    (0x283, "SWBCNT"),
+    (0x284, "INTIM"),
+    (0x285, "TIMINT"),
+    (0x294, "TIM1T"),
+    (0x295, "TIM8T"),
+    (0x296, "TIM64T"),
+    (0x297, "T1024T"),
+];
+
+/// Which names, if any, to use in place of addresses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Symbols {
+    None,
+    Atari2600,
+}
+
+impl Symbols {
+    fn lookup(&self, address: u16, is_write: bool) -> Option<&'static str> {
+        match self {
+            Symbols::None => None,
+            // The TIA registers are only named in the zero page, that's where they're always accessed
+            Symbols::Atari2600 if address < 0x80 => match is_write {
+                true => TIA_WRITE_SYMBOLS.get(address as usize & 0x3F).copied(),
+                false => TIA_READ_SYMBOLS.get(address as usize & 0x0F).copied(),
+            },
+            Symbols::Atari2600 => RIOT_SYMBOLS
+                .iter()
+                .find(|(riot_address, _)| *riot_address == address)
+                .map(|(_, symbol)| *symbol),
+        }
+    }
+
+    fn format(&self, address: u16, is_write: bool, zero_page: bool) -> String {
+        match (self.lookup(address, is_write), zero_page) {
+            (Some(symbol), _) => symbol.to_string(),
+            (None, true) => format!("${:02X}", address),
+            (None, false) => format!("${:04X}", address),
+        }
+    }
+}
+
+///
+/// A single disassembled instruction. Illegal opcodes are prefixed with a
+/// `*` in the text, and any bytes left over at the end of the input which
+/// don't make up a full instruction are shown as `.byte`.
+///
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    address: u16,
+    bytes: Vec<u8>,
+    text: String,
+    illegal: bool,
+}
+
+impl Line {
+    /// Decode the instruction at the start of `bytes`, which is located at `address`
+    pub(crate) fn decode(
+        bytes: &[u8],
+        address: u16,
+        symbols: Symbols,
+        variant: CpuVariant,
+    ) -> Line {
+        let opcode = &variant.opcode_table()[bytes[0] as usize];
+        let length = match opcode.address_mode.instruction_length() {
+            InstructionLength::One => 1,
+            InstructionLength::Two => 2,
+            InstructionLength::Three => 3,
+        };
+
+        if bytes.len() < length {
+            return Line {
+                address,
+                bytes: bytes[..1].to_vec(),
+                text: format!(".byte ${:02X}", bytes[0]),
+                illegal: false,
+            };
+        }
+
+        let byte = bytes.get(1).copied().unwrap_or(0);
+        let word = byte as u16 | ((bytes.get(2).copied().unwrap_or(0) as u16) << 8);
+        let is_write = matches!(
+            opcode.operation.instruction_type(),
+            InstructionType::Write | InstructionType::ReadModifyWrite
+        );
+        let zero_page = |address: u8| symbols.format(address as u16, is_write, true);
+        let absolute = |address: u16| symbols.format(address, is_write, false);
+
+        let operand = match opcode.address_mode {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", byte),
+            AddressingMode::ZeroPage => zero_page(byte),
+            AddressingMode::ZeroPageXIndexed => format!("{},X", zero_page(byte)),
+            AddressingMode::ZeroPageYIndexed => format!("{},Y", zero_page(byte)),
+            AddressingMode::Absolute => absolute(word),
+            AddressingMode::AbsoluteXIndexed => format!("{},X", absolute(word)),
+            AddressingMode::AbsoluteYIndexed => format!("{},Y", absolute(word)),
+            AddressingMode::Indirect => format!("(${:04X})", word),
+            AddressingMode::IndirectXIndexed => format!("(${:02X},X)", byte),
+            AddressingMode::IndirectYIndexed => format!("(${:02X}),Y", byte),
+            AddressingMode::Relative => {
+                let target = address.wrapping_add(2).wrapping_add(byte as i8 as u16);
+                format!("${:04X}", target)
+            }
+            AddressingMode::AbsoluteIndexedIndirect => format!("(${:04X},X)", word),
+            AddressingMode::ZeroPageIndirect => format!("(${:02X})", byte),
+            AddressingMode::ZeroPageRelative => {
+                let offset = bytes.get(2).copied().unwrap_or(0);
+                let target = address.wrapping_add(3).wrapping_add(offset as i8 as u16);
+                format!("{},${:04X}", zero_page(byte), target)
+            }
+        };
+
+        // The bit instructions are written with their bit number, e.g. RMB3
+        let bit = match opcode.operation {
+            Operation::RMB | Operation::SMB | Operation::BBR | Operation::BBS => {
+                opcode.bit_number().to_string()
+            }
+            _ => String::new(),
+        };
+        let mnemonic = format!(
+            "{}{:?}{}",
+            if opcode.is_illegal { "*" } else { "" },
+            opcode.operation,
+            bit
+        );
+        Line {
+            address,
+            bytes: bytes[..length].to_vec(),
+            text: match operand.is_empty() {
+                true => mnemonic,
+                false => format!("{} {}", mnemonic, operand),
+            },
+            illegal: opcode.is_illegal,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Line {
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    /// The instruction in standard assembler syntax, e.g. `LDA ($80),Y`
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    pub fn is_illegal(&self) -> bool {
+        self.illegal
+    }
+}
+
+/// Formats as a listing line, e.g. `F000  A9 12     LDA #$12`
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(
+            f,
+            "{:04X}  {:<8}  {}",
+            self.address,
+            bytes.join(" "),
+            self.text
+        )
+    }
+}
+
+pub(crate) fn disassemble_with_symbols(
+    bytes: &[u8],
+    origin: u16,
+    symbols: Symbols,
+    variant: CpuVariant,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let line = Line::decode(
+            &bytes[offset..],
+            origin.wrapping_add(offset as u16),
+            symbols,
+            variant,
+        );
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Disassemble a block of NMOS 6502 machine code which starts at `origin`
+#[wasm_bindgen]
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Line> {
+    disassemble_with_symbols(bytes, origin, Symbols::None, CpuVariant::Nmos6502)
+}
+
+/// As `disassemble` but using the instruction set of another cpu, e.g. the 65C02
+#[wasm_bindgen]
+pub fn disassemble_for_variant(bytes: &[u8], origin: u16, variant: CpuVariant) -> Vec<Line> {
+    disassemble_with_symbols(bytes, origin, Symbols::None, variant)
+}
+
+/// As `disassemble` but naming the TIA & RIOT registers, e.g. `STA WSYNC`
+#[wasm_bindgen]
+pub fn disassemble_2600(bytes: &[u8], origin: u16) -> Vec<Line> {
+    disassemble_with_symbols(bytes, origin, Symbols::Atari2600, CpuVariant::Mos6507)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, disassemble_2600, disassemble_for_variant};
+    use crate::cpu::CpuVariant;
+
+    fn texts(bytes: &[u8]) -> Vec<String> {
+        disassemble(bytes, 0xF000)
+            .iter()
+            .map(|line| line.text())
+            .collect()
+    }
+
+    #[test]
+    fn test_addressing_modes() {
+        let bytes = [
+            0xEA, // NOP
+            0x0A, // ASL A
+            0xA9, 0x12, // LDA #$12
+            0xA5, 0x80, // LDA $80
+            0xB5, 0x80, // LDA $80,X
+            0xB6, 0x80, // LDX $80,Y
+            0xAD, 0x34, 0x12, // LDA $1234
+            0xBD, 0x34, 0x12, // LDA $1234,X
+            0xB9, 0x34, 0x12, // LDA $1234,Y
+            0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+            0xA1, 0x80, // LDA ($80,X)
+            0xB1, 0x80, // LDA ($80),Y
+            0xD0, 0xFE, // BNE to itself
+        ];
+
+        assert_eq!(
+            texts(&bytes),
+            vec![
+                "NOP",
+                "ASL A",
+                "LDA #$12",
+                "LDA $80",
+                "LDA $80,X",
+                "LDX $80,Y",
+                "LDA $1234",
+                "LDA $1234,X",
+                "LDA $1234,Y",
+                "JMP ($FFFC)",
+                "LDA ($80,X)",
+                "LDA ($80),Y",
+                "BNE $F01A",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_illegal_opcodes_are_marked() {
+        let lines = disassemble(&[0xA7, 0x80, 0x02, 0xA9, 0x00], 0x1000);
+        assert_eq!(lines[0].text(), "*LAX $80");
+        assert!(lines[0].is_illegal());
+        assert_eq!(lines[1].text(), "*KIL");
+        assert!(!lines[2].is_illegal());
+    }
+
+    #[test]
+    fn test_65c02_opcodes() {
+        let bytes = [
+            0xB2, 0x80, // LDA ($80)
+            0x7C, 0x00, 0x20, // JMP ($2000,X)
+            0x37, 0x80, // RMB3 $80
+            0x8F, 0x80, 0xFD, // BBS0 $80 to itself
+            0x03, // Single cycle NOP
+            0xDA, // PHX
+        ];
+        let texts: Vec<String> = disassemble_for_variant(&bytes, 0xF000, CpuVariant::Cmos65C02)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+
+        assert_eq!(
+            texts,
+            vec![
+                "LDA ($80)",
+                "JMP ($2000,X)",
+                "RMB3 $80",
+                "BBS0 $80,$F007",
+                "*NOP",
+                "PHX"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_instruction() {
+        let lines = disassemble(&[0xEA, 0x8D, 0x00], 0x1000);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].text(), ".byte $8D");
+        assert_eq!(lines[2].address(), 0x1002);
+    }
+
+    #[test]
+    fn test_atari_2600_symbols() {
+        let bytes = [
+            0x85, 0x02, // STA WSYNC
+            0x24, 0x02, // BIT CXP0FB (reads see different registers)
+            0xA5, 0x3C, // LDA INPT4 through a mirror
+            0xAD, 0x84, 0x02, // LDA INTIM
+            0x8D, 0x96, 0x02, // STA TIM64T
+            0x95, 0x10, // STA RESP0,X
+            0xA5, 0x80, // LDA $80 is RAM
+        ];
+        let texts: Vec<String> = disassemble_2600(&bytes, 0xF000)
+            .iter()
+            .map(|line| line.text())
+            .collect();
+
+        assert_eq!(
+            texts,
+            vec![
+                "STA WSYNC",
+                "BIT CXP0FB",
+                "LDA INPT4",
+                "LDA INTIM",
+                "STA TIM64T",
+                "STA RESP0,X",
+                "LDA $80"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_listing_format() {
+        let lines = disassemble(&[0x4C, 0x00, 0xF0], 0xF000);
+        assert_eq!(lines[0].to_string(), "F000  4C 00 F0  JMP $F000");
+    }
+}
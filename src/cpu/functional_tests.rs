@@ -0,0 +1,198 @@
+//!
+//! Runs Klaus Dormann's 6502 functional test and Bruce Clark's decimal mode
+//! test (as distributed with Klaus's suite) against the cpu on a flat 64K
+//! RAM bus.
+//!
+//! The binaries aren't distributed with this repository so the tests are
+//! ignored by default. Assemble them, or download them from
+//! https://github.com/Klaus2m5/6502_65C02_functional_tests, into
+//! `tests/data/klaus_dormann` (or the directory named by the
+//! `KLAUS_DORMANN_DIR` environment variable) and run them with
+//! `cargo test -- --ignored`. They fail if the binaries aren't there.
+//!
+
+use std::path::PathBuf;
+
+use super::test_bus::TestBus;
+use super::{Cpu, CpuVariant, Register};
+
+/// Every test finishes within this many cycles, the functional test needs around 100 million
+const MAX_CYCLES: u64 = 200_000_000;
+
+/// The functional test is a full 64K image which starts at $0400
+const FUNCTIONAL_TEST_START: u16 = 0x0400;
+
+/// Address of the `JMP *` reached once every functional test has passed
+const FUNCTIONAL_TEST_SUCCESS: u16 = 0x3469;
+
+/// The functional test keeps the number of the test it's running here
+const FUNCTIONAL_TEST_CASE: u16 = 0x0200;
+
+/// The decimal test is assembled to run from $0200
+const DECIMAL_TEST_START: u16 = 0x0200;
+
+/// Klaus's build of the decimal test ends with the 65C02 STP instruction, a 6502 would run it as DCP
+const STP: u8 = 0xDB;
+
+/// The decimal test leaves 0 here if every result was correct
+const DECIMAL_TEST_ERROR: u16 = 0x000B;
+
+/// Builds of the decimal test which end with RTS return here, to a `JMP *` outside of any memory it uses
+const DECIMAL_TEST_DONE: u16 = 0xFFF0;
+
+/// Flat 64K RAM holding `image` at `origin`, which keeps its own vectors
+fn flat_bus(image: &[u8], origin: u16) -> TestBus {
+    let mut ram = vec![0; 0x10000];
+    let end = (origin as usize + image.len()).min(ram.len());
+    ram[origin as usize..end].copy_from_slice(&image[..end - origin as usize]);
+
+    TestBus {
+        ram,
+        accesses: Vec::new(),
+        // The tests run for millions of cycles
+        recording: false,
+    }
+}
+
+fn load_binary(name: &str) -> Vec<u8> {
+    let directory = match std::env::var_os("KLAUS_DORMANN_DIR") {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/klaus_dormann"),
+    };
+    let path = directory.join(name);
+
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path.display(), e))
+}
+
+///
+/// Run from `start` until the program traps, i.e. an instruction jumps or
+/// branches to itself, or is about to execute `stop_opcode`. Returns the
+/// address of the trap or an error if the cpu jams or never traps.
+///
+fn run_until_trap(
+    bus: &mut TestBus,
+    variant: CpuVariant,
+    start: u16,
+    stop_opcode: Option<u8>,
+) -> Result<u16, String> {
+    let mut cpu = Cpu::new_with_variant(variant, 0, bus);
+    cpu.set_register(Register::ProgramCounter, start);
+
+    let mut instruction_pc = start;
+    for _ in 0..MAX_CYCLES {
+        cpu.clock(bus);
+
+        if cpu.is_jammed() {
+            return Err(format!(
+                "Cpu jammed by the instruction at ${:04X}",
+                instruction_pc
+            ));
+        }
+        if cpu.at_instruction_boundary() {
+            let pc = cpu.register(Register::ProgramCounter);
+            if pc == instruction_pc || stop_opcode == Some(bus.ram[pc as usize]) {
+                return Ok(pc);
+            }
+            instruction_pc = pc;
+        }
+    }
+
+    Err(format!(
+        "Still running after {} cycles, at ${:04X}",
+        MAX_CYCLES, instruction_pc
+    ))
+}
+
+/// Run the functional test, on failure the error names the trap and the test case that hit it
+fn run_functional_test(image: &[u8], variant: CpuVariant, success: u16) -> Result<(), String> {
+    let mut bus = flat_bus(image, 0x0000);
+
+    match run_until_trap(&mut bus, variant, FUNCTIONAL_TEST_START, None)? {
+        trap if trap == success => Ok(()),
+        trap => Err(format!(
+            "Trapped at ${:04X} in test case ${:02X}",
+            trap, bus.ram[FUNCTIONAL_TEST_CASE as usize]
+        )),
+    }
+}
+
+fn run_decimal_test(binary: &[u8]) -> Result<(), String> {
+    let mut bus = flat_bus(binary, DECIMAL_TEST_START);
+    // JMP * to trap on, with its address (less one, as RTS expects) at the top of the stack
+    let [done_low, done_high] = DECIMAL_TEST_DONE.to_le_bytes();
+    bus.ram[DECIMAL_TEST_DONE as usize..DECIMAL_TEST_DONE as usize + 3]
+        .copy_from_slice(&[0x4C, done_low, done_high]);
+    let [return_low, return_high] = DECIMAL_TEST_DONE.wrapping_sub(1).to_le_bytes();
+    bus.ram[0x1FF] = return_high;
+    bus.ram[0x1FE] = return_low;
+
+    match run_until_trap(
+        &mut bus,
+        CpuVariant::Nmos6502,
+        DECIMAL_TEST_START,
+        Some(STP),
+    )? {
+        trap if trap == DECIMAL_TEST_DONE || bus.ram[trap as usize] == STP => {
+            match bus.ram[DECIMAL_TEST_ERROR as usize] {
+                0 => Ok(()),
+                _ => Err(format!(
+                    "Decimal test failed, last values tested were A=${:02X} operand=${:02X}",
+                    bus.ram[0x00], bus.ram[0x01]
+                )),
+            }
+        }
+        trap => Err(format!("Decimal test trapped at ${:04X}", trap)),
+    }
+}
+
+#[test]
+#[ignore = "needs the binaries in tests/data/klaus_dormann, see the module docs"]
+fn test_klaus_dormann_functional_test() {
+    let image = load_binary("6502_functional_test.bin");
+    if let Err(error) = run_functional_test(&image, CpuVariant::Nmos6502, FUNCTIONAL_TEST_SUCCESS) {
+        panic!("{}", error);
+    }
+}
+
+/// The functional test only uses documented opcodes so the 65C02 must pass it too
+#[test]
+#[ignore = "needs the binaries in tests/data/klaus_dormann, see the module docs"]
+fn test_klaus_dormann_functional_test_65c02() {
+    let image = load_binary("6502_functional_test.bin");
+    if let Err(error) = run_functional_test(&image, CpuVariant::Cmos65C02, FUNCTIONAL_TEST_SUCCESS)
+    {
+        panic!("{}", error);
+    }
+}
+
+#[test]
+#[ignore = "needs the binaries in tests/data/klaus_dormann, see the module docs"]
+fn test_decimal_mode_test() {
+    if let Err(error) = run_decimal_test(&load_binary("6502_decimal_test.bin")) {
+        panic!("{}", error);
+    }
+}
+
+/// Make sure the harness itself reports passes & failures when the binaries aren't available
+#[test]
+fn test_harness_reports_trapped_test_case() {
+    let mut image = vec![0; 0x10000];
+    // LDA #$2A, STA $0200, LDX #$05, loop: DEX, BNE loop, JMP *
+    let program = [
+        0xA9, 0x2A, 0x8D, 0x00, 0x02, 0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x4C, 0x0A, 0x04,
+    ];
+    image[0x400..0x400 + program.len()].copy_from_slice(&program);
+
+    assert_eq!(
+        run_functional_test(&image, CpuVariant::Nmos6502, 0x040A),
+        Ok(())
+    );
+    assert_eq!(
+        run_functional_test(&image, CpuVariant::Nmos6502, 0x3469),
+        Err("Trapped at $040A in test case $2A".to_string())
+    );
+
+    // Jam the cpu before it reaches any trap
+    image[0x400] = 0x02;
+    assert!(run_functional_test(&image, CpuVariant::Nmos6502, 0x040A).is_err());
+}
@@ -19,3 +19,46 @@ impl Interrupt {
         }
     }
 }
+
+///
+/// The IRQ & NMI inputs. Both are active low on the chip, here `true` means
+/// asserted. IRQ is level sensitive while NMI is edge triggered, an NMI is
+/// latched when the line is first asserted and stays pending until it's
+/// serviced.
+///
+/// The lines are sampled at the end of every cycle. Instructions poll on
+/// their last cycle, which sees the interrupt sampled at the end of the
+/// cycle before, i.e. the second to last cycle.
+///
+#[derive(Debug, Default, Copy, Clone)]
+pub(super) struct InterruptLines {
+    pub(super) irq: bool,
+    pub(super) nmi: bool,
+    // NMI level at the end of the previous cycle, for edge detection
+    pub(super) nmi_previous: bool,
+    pub(super) nmi_pending: bool,
+    pub(super) sampled: Option<Interrupt>,
+}
+
+impl InterruptLines {
+    /// Called at the end of every cycle with the current state of the I flag
+    pub(super) fn sample(&mut self, interrupts_disabled: bool, cycle: ClockCycle) {
+        if self.nmi && !self.nmi_previous {
+            self.nmi_pending = true;
+        }
+        self.nmi_previous = self.nmi;
+
+        self.sampled = if self.nmi_pending {
+            Some(Interrupt::NMI(cycle))
+        } else if self.irq && !interrupts_disabled {
+            Some(Interrupt::IRQ(cycle))
+        } else {
+            None
+        };
+    }
+
+    /// Clear a pending NMI once its vector has been chosen, returns whether there was one
+    pub(super) fn acknowledge_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+}
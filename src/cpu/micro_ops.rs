@@ -0,0 +1,870 @@
+use super::interrupts::Interrupt;
+use super::opcodes::{
+    AddressingMode, InstructionType, Opcode, Operation, OPCODE_TABLE, OPCODE_TABLE_65C02,
+};
+use super::status_flags::StatusFlags;
+use super::{Cpu, InterruptState, State};
+use crate::bus::Bus;
+
+///
+/// A single cycle of an instruction after the opcode fetch. Every micro-op
+/// makes exactly one bus access, the sequence for each opcode is worked out
+/// once from the opcode table rather than by re-decoding the addressing mode
+/// on every cycle.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MicroOp {
+    // Read the operand bytes which make up an address
+    FetchAddressLow,
+    FetchAddressHigh,
+    FetchAddressHighAddX,
+    FetchAddressHighAddY,
+    // Zero page indexing reads the unindexed address while adding, wrapping within the page
+    DummyReadAddX,
+    DummyReadAddY,
+    // Indirect modes read a zero page pointer then the address it points at
+    FetchPointer,
+    DummyReadPointerAddX,
+    ReadAddressLowFromPointer,
+    ReadAddressHighFromPointer,
+    ReadAddressHighFromPointerAddY,
+    // Indexing reads from the address before the high byte has been fixed up
+    DummyReadUncorrected,
+    // Reads which only take the extra cycle when indexing crosses a page
+    ReadIndexed,
+    ReadIndexedForModify,
+    // The last cycle of read & implied instructions
+    Read,
+    Immediate,
+    Implied,
+    Accumulator,
+    Store,
+    // Read modify write instructions
+    ReadData,
+    DummyWrite,
+    DummyReadModify,
+    WriteData,
+    DummyReadAddress,
+    // Branches
+    FetchBranchOffset,
+    FetchBitBranchOffset,
+    BranchTaken,
+    BranchFixPage,
+    // Jumps
+    Jump,
+    DummyReadLastOperand,
+    DummyReadLastOperandAddX,
+    ReadIndirectLow,
+    ReadIndirectHigh,
+    // Stack
+    DummyReadPc,
+    DummyReadStack,
+    Push,
+    Pull,
+    PushPch,
+    PushPcl,
+    PullStatus,
+    PullPcl,
+    PullPch,
+    IncrementPc,
+    Brk,
+}
+
+impl MicroOp {
+    /// Whether the cycle writes to the bus, the cpu ignores RDY on these
+    pub(super) fn is_write(&self) -> bool {
+        matches!(
+            self,
+            MicroOp::Store
+                | MicroOp::DummyWrite
+                | MicroOp::WriteData
+                | MicroOp::Push
+                | MicroOp::PushPch
+                | MicroOp::PushPcl
+        )
+    }
+}
+
+/// What the cpu does after a micro-op
+pub(super) enum Step {
+    Next,
+    // Jump over the next micro-op, used when a read turns out not to need a fix up cycle
+    Skip,
+    Done,
+    // A taken branch which stays on the same page doesn't poll for interrupts again
+    DoneWithoutPoll,
+    Goto(State),
+}
+
+/// Values carried from one cycle of an instruction to the next
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Latches {
+    pub(super) address: u16,
+    pub(super) pointer: u8,
+    pub(super) data: u8,
+    pub(super) page_crossed: bool,
+}
+
+///
+/// The cycles which follow the opcode fetch for an opcode. A combination of
+/// operation & addressing mode which isn't covered fails to compile as the
+/// tables are built in a const context.
+///
+const fn micro_ops(opcode: &Opcode, cmos: bool) -> &'static [MicroOp] {
+    use MicroOp::*;
+
+    match opcode.operation {
+        Operation::BRK => return &[Brk],
+        Operation::RTI => return &[DummyReadPc, DummyReadStack, PullStatus, PullPcl, PullPch],
+        Operation::RTS => return &[DummyReadPc, DummyReadStack, PullPcl, PullPch, IncrementPc],
+        Operation::PHA | Operation::PHP | Operation::PHX | Operation::PHY => {
+            return &[DummyReadPc, Push]
+        }
+        Operation::PLA | Operation::PLP | Operation::PLX | Operation::PLY => {
+            return &[DummyReadPc, DummyReadStack, Pull]
+        }
+        // The high byte of the address is read last, after the return address has been pushed
+        Operation::JSR => return &[FetchAddressLow, DummyReadStack, PushPch, PushPcl, Jump],
+        _ => {}
+    }
+
+    let is_cmos_shift = cmos && !matches!(opcode.operation, Operation::INC | Operation::DEC);
+
+    match (opcode.address_mode, opcode.operation.instruction_type()) {
+        // The 65C02's undefined one byte opcodes are NOPs which take a single cycle
+        (AddressingMode::Implied, _) if cmos && opcode.is_illegal => &[],
+        (AddressingMode::Implied, _) => &[Implied],
+        (AddressingMode::Accumulator, _) => &[Accumulator],
+        (AddressingMode::Immediate, _) => &[Immediate],
+        (AddressingMode::Relative, _) => &[FetchBranchOffset, BranchTaken, BranchFixPage],
+        (AddressingMode::ZeroPageRelative, _) => &[
+            FetchAddressLow,
+            ReadData,
+            DummyReadAddress,
+            FetchBitBranchOffset,
+            BranchTaken,
+            BranchFixPage,
+        ],
+        (AddressingMode::Absolute, InstructionType::Jump) => &[FetchAddressLow, Jump],
+        // The 65C02 spends an extra cycle, re-reading the high byte, on fixing the page wrap bug
+        (AddressingMode::Indirect, _) if cmos => &[
+            FetchAddressLow,
+            FetchAddressHigh,
+            DummyReadLastOperand,
+            ReadIndirectLow,
+            ReadIndirectHigh,
+        ],
+        (AddressingMode::Indirect, _) => &[
+            FetchAddressLow,
+            FetchAddressHigh,
+            ReadIndirectLow,
+            ReadIndirectHigh,
+        ],
+        (AddressingMode::AbsoluteIndexedIndirect, _) => &[
+            FetchAddressLow,
+            FetchAddressHigh,
+            DummyReadLastOperandAddX,
+            ReadIndirectLow,
+            ReadIndirectHigh,
+        ],
+
+        (AddressingMode::ZeroPage, InstructionType::Read) => &[FetchAddressLow, Read],
+        (AddressingMode::ZeroPage, InstructionType::Write) => &[FetchAddressLow, Store],
+        (AddressingMode::ZeroPage, InstructionType::ReadModifyWrite) if cmos => {
+            &[FetchAddressLow, ReadData, DummyReadModify, WriteData]
+        }
+        (AddressingMode::ZeroPage, InstructionType::ReadModifyWrite) => {
+            &[FetchAddressLow, ReadData, DummyWrite, WriteData]
+        }
+
+        (AddressingMode::ZeroPageXIndexed, InstructionType::Read) => {
+            &[FetchAddressLow, DummyReadAddX, Read]
+        }
+        (AddressingMode::ZeroPageXIndexed, InstructionType::Write) => {
+            &[FetchAddressLow, DummyReadAddX, Store]
+        }
+        (AddressingMode::ZeroPageXIndexed, InstructionType::ReadModifyWrite) if cmos => &[
+            FetchAddressLow,
+            DummyReadAddX,
+            ReadData,
+            DummyReadModify,
+            WriteData,
+        ],
+        (AddressingMode::ZeroPageXIndexed, InstructionType::ReadModifyWrite) => &[
+            FetchAddressLow,
+            DummyReadAddX,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::ZeroPageYIndexed, InstructionType::Read) => {
+            &[FetchAddressLow, DummyReadAddY, Read]
+        }
+        (AddressingMode::ZeroPageYIndexed, InstructionType::Write) => {
+            &[FetchAddressLow, DummyReadAddY, Store]
+        }
+
+        (AddressingMode::Absolute, InstructionType::Read) => {
+            &[FetchAddressLow, FetchAddressHigh, Read]
+        }
+        (AddressingMode::Absolute, InstructionType::Write) => {
+            &[FetchAddressLow, FetchAddressHigh, Store]
+        }
+        (AddressingMode::Absolute, InstructionType::ReadModifyWrite) if cmos => &[
+            FetchAddressLow,
+            FetchAddressHigh,
+            ReadData,
+            DummyReadModify,
+            WriteData,
+        ],
+        (AddressingMode::Absolute, InstructionType::ReadModifyWrite) => &[
+            FetchAddressLow,
+            FetchAddressHigh,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::AbsoluteXIndexed, InstructionType::Read) => {
+            &[FetchAddressLow, FetchAddressHighAddX, ReadIndexed, Read]
+        }
+        (AddressingMode::AbsoluteXIndexed, InstructionType::Write) => &[
+            FetchAddressLow,
+            FetchAddressHighAddX,
+            DummyReadUncorrected,
+            Store,
+        ],
+        // The 65C02 only takes the extra cycle for shifts & rotates if the page
+        // changes, INC & DEC always take it
+        (AddressingMode::AbsoluteXIndexed, InstructionType::ReadModifyWrite) if is_cmos_shift => &[
+            FetchAddressLow,
+            FetchAddressHighAddX,
+            ReadIndexedForModify,
+            ReadData,
+            DummyReadModify,
+            WriteData,
+        ],
+        (AddressingMode::AbsoluteXIndexed, InstructionType::ReadModifyWrite) if cmos => &[
+            FetchAddressLow,
+            FetchAddressHighAddX,
+            DummyReadUncorrected,
+            ReadData,
+            DummyReadModify,
+            WriteData,
+        ],
+        (AddressingMode::AbsoluteXIndexed, InstructionType::ReadModifyWrite) => &[
+            FetchAddressLow,
+            FetchAddressHighAddX,
+            DummyReadUncorrected,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::AbsoluteYIndexed, InstructionType::Read) => {
+            &[FetchAddressLow, FetchAddressHighAddY, ReadIndexed, Read]
+        }
+        (AddressingMode::AbsoluteYIndexed, InstructionType::Write) => &[
+            FetchAddressLow,
+            FetchAddressHighAddY,
+            DummyReadUncorrected,
+            Store,
+        ],
+        (AddressingMode::AbsoluteYIndexed, InstructionType::ReadModifyWrite) if !cmos => &[
+            FetchAddressLow,
+            FetchAddressHighAddY,
+            DummyReadUncorrected,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::IndirectXIndexed, InstructionType::Read) => &[
+            FetchPointer,
+            DummyReadPointerAddX,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointer,
+            Read,
+        ],
+        (AddressingMode::IndirectXIndexed, InstructionType::Write) => &[
+            FetchPointer,
+            DummyReadPointerAddX,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointer,
+            Store,
+        ],
+        (AddressingMode::IndirectXIndexed, InstructionType::ReadModifyWrite) if !cmos => &[
+            FetchPointer,
+            DummyReadPointerAddX,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointer,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::IndirectYIndexed, InstructionType::Read) => &[
+            FetchPointer,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointerAddY,
+            ReadIndexed,
+            Read,
+        ],
+        (AddressingMode::IndirectYIndexed, InstructionType::Write) => &[
+            FetchPointer,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointerAddY,
+            DummyReadUncorrected,
+            Store,
+        ],
+        (AddressingMode::IndirectYIndexed, InstructionType::ReadModifyWrite) if !cmos => &[
+            FetchPointer,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointerAddY,
+            DummyReadUncorrected,
+            ReadData,
+            DummyWrite,
+            WriteData,
+        ],
+
+        (AddressingMode::ZeroPageIndirect, InstructionType::Read) => &[
+            FetchPointer,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointer,
+            Read,
+        ],
+        (AddressingMode::ZeroPageIndirect, InstructionType::Write) => &[
+            FetchPointer,
+            ReadAddressLowFromPointer,
+            ReadAddressHighFromPointer,
+            Store,
+        ],
+
+        _ => panic!("No micro-ops for this addressing mode & instruction type"),
+    }
+}
+
+const fn micro_op_table(opcodes: &[Opcode; 0x100], cmos: bool) -> [&'static [MicroOp]; 0x100] {
+    let mut table: [&'static [MicroOp]; 0x100] = [&[]; 0x100];
+    let mut ix = 0;
+    while ix < 0x100 {
+        table[ix] = micro_ops(&opcodes[ix], cmos);
+        ix += 1;
+    }
+
+    table
+}
+
+pub(super) static MICRO_OP_TABLE: [&[MicroOp]; 0x100] = micro_op_table(&OPCODE_TABLE, false);
+
+pub(super) static MICRO_OP_TABLE_65C02: [&[MicroOp]; 0x100] =
+    micro_op_table(&OPCODE_TABLE_65C02, true);
+
+impl Cpu {
+    ///
+    /// The address read while indexing is still fixing up the high byte. The
+    /// NMOS parts read from the un-carried address, the 65C02 reads the last
+    /// byte of the instruction again instead.
+    ///
+    fn uncorrected_address(&self) -> u16 {
+        match (self.latches.page_crossed, self.variant.is_cmos()) {
+            (false, _) => self.latches.address,
+            (true, false) => self.latches.address.wrapping_sub(0x100),
+            (true, true) => self.registers.program_counter.wrapping_sub(1),
+        }
+    }
+
+    /// Adds an index to the address latch, noting whether the high byte needs a carry
+    fn index_address(&mut self, base: u16, index: u8) {
+        let address = base.wrapping_add(index as u16);
+        self.latches.page_crossed = (base ^ address) & 0xFF00 != 0;
+        self.latches.address = address;
+    }
+
+    fn fetch_address_high<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let high_byte = self.read_and_inc_program_counter(bus);
+
+        self.latches.address | ((high_byte as u16) << 8)
+    }
+
+    fn read_address_high_from_pointer<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let high_byte = self.read(bus, self.latches.pointer.wrapping_add(1) as u16);
+
+        self.latches.address | ((high_byte as u16) << 8)
+    }
+
+    /// Runs one cycle of the instruction `opcode`
+    pub(super) fn run_micro_op<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        opcode: &'static Opcode,
+        micro_op: MicroOp,
+    ) -> Step {
+        match micro_op {
+            MicroOp::FetchAddressLow => {
+                self.latches.address = self.read_and_inc_program_counter(bus) as u16;
+            }
+            MicroOp::FetchAddressHigh => self.latches.address = self.fetch_address_high(bus),
+            MicroOp::FetchAddressHighAddX => {
+                let base = self.fetch_address_high(bus);
+                self.index_address(base, self.registers.x);
+            }
+            MicroOp::FetchAddressHighAddY => {
+                let base = self.fetch_address_high(bus);
+                self.index_address(base, self.registers.y);
+            }
+            MicroOp::DummyReadAddX => {
+                self.dummy_read(bus, self.latches.address);
+                self.latches.address =
+                    (self.latches.address as u8).wrapping_add(self.registers.x) as u16;
+            }
+            MicroOp::DummyReadAddY => {
+                self.dummy_read(bus, self.latches.address);
+                self.latches.address =
+                    (self.latches.address as u8).wrapping_add(self.registers.y) as u16;
+            }
+            MicroOp::FetchPointer => self.latches.pointer = self.read_and_inc_program_counter(bus),
+            MicroOp::DummyReadPointerAddX => {
+                self.dummy_read(bus, self.latches.pointer as u16);
+                self.latches.pointer = self.latches.pointer.wrapping_add(self.registers.x);
+            }
+            MicroOp::ReadAddressLowFromPointer => {
+                self.latches.address = self.read(bus, self.latches.pointer as u16) as u16;
+            }
+            MicroOp::ReadAddressHighFromPointer => {
+                self.latches.address = self.read_address_high_from_pointer(bus);
+            }
+            MicroOp::ReadAddressHighFromPointerAddY => {
+                let base = self.read_address_high_from_pointer(bus);
+                self.index_address(base, self.registers.y);
+            }
+            MicroOp::DummyReadUncorrected => {
+                self.dummy_read(bus, self.uncorrected_address());
+            }
+            MicroOp::ReadIndexed => {
+                if self.latches.page_crossed {
+                    self.dummy_read(bus, self.uncorrected_address());
+                } else {
+                    let operand = self.read(bus, self.latches.address);
+                    return opcode.execute(self, operand);
+                }
+            }
+            MicroOp::ReadIndexedForModify => {
+                if self.latches.page_crossed {
+                    self.dummy_read(bus, self.uncorrected_address());
+                } else {
+                    self.latches.data = self.read(bus, self.latches.address);
+                    return Step::Skip;
+                }
+            }
+            MicroOp::Read => {
+                let operand = self.read(bus, self.latches.address);
+                return opcode.execute(self, operand);
+            }
+            MicroOp::Immediate => {
+                let operand = self.read_and_inc_program_counter(bus);
+                return opcode.execute(self, operand);
+            }
+            MicroOp::Implied => {
+                let operand = self.dummy_read(bus, self.registers.program_counter);
+                return opcode.execute(self, operand);
+            }
+            MicroOp::Accumulator => {
+                self.dummy_read(bus, self.registers.program_counter);
+                self.registers.a = opcode.modify(self, self.registers.a);
+            }
+            MicroOp::Store => {
+                let (address, value) = opcode.store(self, self.latches.address);
+                self.write(bus, address, value);
+            }
+            MicroOp::ReadData => self.latches.data = self.read(bus, self.latches.address),
+            MicroOp::DummyWrite => {
+                // The NMOS parts write the unmodified value back while working out the new one
+                self.dummy_write(bus, self.latches.address, self.latches.data);
+                self.latches.data = opcode.modify(self, self.latches.data);
+            }
+            MicroOp::DummyReadModify => {
+                // The 65C02 reads the address again instead of writing back the original value
+                self.dummy_read(bus, self.latches.address);
+                self.latches.data = opcode.modify(self, self.latches.data);
+            }
+            MicroOp::WriteData => self.write(bus, self.latches.address, self.latches.data),
+            MicroOp::DummyReadAddress => {
+                self.dummy_read(bus, self.latches.address);
+            }
+            MicroOp::FetchBranchOffset => {
+                self.latches.data = self.read_and_inc_program_counter(bus);
+                if !opcode.branch_taken(self) {
+                    return Step::Done;
+                }
+                // Taken branches poll for interrupts here as well as on their last cycle
+                self.poll_interrupts();
+            }
+            MicroOp::FetchBitBranchOffset => {
+                let bit_set = self.latches.data & (1 << opcode.bit_number()) != 0;
+                self.latches.data = self.read_and_inc_program_counter(bus);
+                if bit_set != (opcode.operation == Operation::BBS) {
+                    return Step::Done;
+                }
+                self.poll_interrupts();
+            }
+            MicroOp::BranchTaken => {
+                self.dummy_read(bus, self.registers.program_counter);
+                let pc = self.registers.program_counter;
+                let address = pc.wrapping_add((self.latches.data as i8) as u16);
+                if (address ^ pc) & 0xFF00 == 0 {
+                    // A taken branch which stays on the same page doesn't poll again
+                    // on its last cycle, delaying any interrupt by an instruction
+                    self.registers.program_counter = address;
+                    return Step::DoneWithoutPoll;
+                }
+                // The low byte is updated first, the carry into the high byte takes another cycle
+                self.registers.program_counter = (pc & 0xFF00) | (address & 0x00FF);
+                self.latches.address = address;
+            }
+            MicroOp::BranchFixPage => {
+                self.dummy_read(bus, self.registers.program_counter);
+                self.registers.program_counter = self.latches.address;
+            }
+            MicroOp::Jump => self.registers.program_counter = self.fetch_address_high(bus),
+            MicroOp::DummyReadLastOperand => {
+                self.dummy_read(bus, self.registers.program_counter.wrapping_sub(1));
+            }
+            MicroOp::DummyReadLastOperandAddX => {
+                self.dummy_read(bus, self.registers.program_counter.wrapping_sub(1));
+                self.latches.address = self.latches.address.wrapping_add(self.registers.x as u16);
+            }
+            MicroOp::ReadIndirectLow => self.latches.data = self.read(bus, self.latches.address),
+            MicroOp::ReadIndirectHigh => {
+                // Note - this is deliberately "bugged" on NMOS parts, JMP ($01FF) reads the
+                // high byte from $0100 rather than $0200. The 65C02 carries into the high byte.
+                let address = self.latches.address;
+                let high_byte_address = match self.variant.is_cmos() {
+                    true => address.wrapping_add(1),
+                    false => (address & 0xFF00) | ((address as u8).wrapping_add(1) as u16),
+                };
+                let high_byte = self.read(bus, high_byte_address);
+                self.registers.program_counter =
+                    self.latches.data as u16 | ((high_byte as u16) << 8);
+            }
+            MicroOp::DummyReadPc => {
+                self.dummy_read(bus, self.registers.program_counter);
+            }
+            MicroOp::DummyReadStack => {
+                self.dummy_read(bus, self.registers.stack_pointer as u16 | 0x0100);
+            }
+            MicroOp::Push => self.push_to_stack(bus, opcode.push_value(self)),
+            MicroOp::Pull => {
+                let value = self.pop_from_stack(bus);
+                opcode.pull(self, value);
+            }
+            MicroOp::PushPch => {
+                self.push_to_stack(bus, (self.registers.program_counter >> 8) as u8);
+            }
+            MicroOp::PushPcl => self.push_to_stack(bus, self.registers.program_counter as u8),
+            MicroOp::PullStatus => {
+                self.registers.status_register =
+                    StatusFlags::from_bits_truncate(self.pop_from_stack(bus) & 0b1100_1111);
+            }
+            MicroOp::PullPcl => self.latches.data = self.pop_from_stack(bus),
+            MicroOp::PullPch => {
+                let pch = self.pop_from_stack(bus);
+                self.registers.program_counter = ((pch as u16) << 8) | self.latches.data as u16;
+            }
+            MicroOp::IncrementPc => {
+                self.dummy_read(bus, self.registers.program_counter);
+                self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
+            }
+            MicroOp::Brk => {
+                // BRK skips the byte after it, then joins the interrupt sequence
+                self.dummy_read(bus, self.registers.program_counter);
+                self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
+
+                return Step::Goto(State::Interrupt(InterruptState::PushPCH(
+                    Interrupt::IRQ_BRK(0),
+                )));
+            }
+        }
+
+        Step::Next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::opcodes::{AddressingMode, Operation, OPCODE_TABLE};
+    use super::super::test_bus::{step, TestBus};
+    use super::super::{Cpu, CpuVariant};
+    use crate::bus::BusAccessKind;
+
+    /// Cycles taken by each NMOS opcode when no page is crossed, zeros are KIL & branches
+    #[rustfmt::skip]
+    const NMOS_CYCLES: [u32; 0x100] = [
+        7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+        0, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+        0, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+        0, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    ];
+
+    ///
+    /// Runs the first `instructions` instructions of a program at `address`,
+    /// returning the accesses made by the last one & the cycles it took.
+    /// Every cycle makes exactly one access, so the accesses are also the
+    /// cycles of the instruction in order.
+    ///
+    fn run_program(
+        variant: CpuVariant,
+        address: u16,
+        program: &[u8],
+        instructions: usize,
+    ) -> (Vec<(u16, u8, BusAccessKind)>, u32) {
+        let mut bus = TestBus::with_program(0, address, program);
+        let mut cpu = Cpu::new_with_variant(variant, 0, &mut bus);
+        let mut cycles = 0;
+        for _ in 0..instructions {
+            bus.accesses.clear();
+            let start = cpu.cycles;
+            cycles = step(&mut cpu, &mut bus);
+
+            let access_cycles: Vec<u32> = bus.accesses.iter().map(|access| access.cycle).collect();
+            assert_eq!(access_cycles, (start..start + cycles).collect::<Vec<_>>());
+        }
+
+        let accesses = bus
+            .accesses
+            .iter()
+            .map(|access| (access.address, access.value, access.kind))
+            .collect();
+        (accesses, cycles)
+    }
+
+    #[test]
+    fn test_nmos_cycle_counts() {
+        for opcode in OPCODE_TABLE.iter().filter(|opcode| {
+            opcode.operation != Operation::KIL && opcode.address_mode != AddressingMode::Relative
+        }) {
+            let (_, cycles) = run_program(CpuVariant::Nmos6502, 0x0400, &[opcode.opcode], 1);
+
+            assert_eq!(
+                cycles, NMOS_CYCLES[opcode.opcode as usize],
+                "{:02X} {:?}",
+                opcode.opcode, opcode
+            );
+        }
+    }
+
+    #[test]
+    fn test_store_only_writes() {
+        // STA $80
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &[0x85, 0x80], 1);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0x85, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x80, BusAccessKind::OperandRead),
+                (0x0080, 0x00, BusAccessKind::Write),
+            ]
+        );
+
+        // LDX #$05, STA $80,X
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &[0xA2, 0x05, 0x95, 0x80], 2);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0402, 0x95, BusAccessKind::OpcodeFetch),
+                (0x0403, 0x80, BusAccessKind::OperandRead),
+                (0x0080, 0x00, BusAccessKind::DummyRead),
+                (0x0085, 0x00, BusAccessKind::Write),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_modify_write_dummy_access_has_its_own_cycle() {
+        // The NMOS cpu writes the unmodified value back, the 65C02 reads it again
+        for (variant, kind) in [
+            (CpuVariant::Nmos6502, BusAccessKind::DummyWrite),
+            (CpuVariant::Cmos65C02, BusAccessKind::DummyRead),
+        ] {
+            // INC $80
+            let (accesses, cycles) = run_program(variant, 0x0400, &[0xE6, 0x80], 1);
+
+            assert_eq!(cycles, 5);
+            assert_eq!(
+                accesses,
+                vec![
+                    (0x0400, 0xE6, BusAccessKind::OpcodeFetch),
+                    (0x0401, 0x80, BusAccessKind::OperandRead),
+                    (0x0080, 0x00, BusAccessKind::OperandRead),
+                    (0x0080, 0x00, kind),
+                    (0x0080, 0x01, BusAccessKind::Write),
+                ],
+                "{:?}",
+                variant
+            );
+        }
+    }
+
+    #[test]
+    fn test_pull_reads_stack_while_incrementing_pointer() {
+        // PLA
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &[0x68], 1);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0x68, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x00, BusAccessKind::DummyRead),
+                (0x01FD, 0x00, BusAccessKind::DummyRead),
+                (0x01FE, 0x00, BusAccessKind::OperandRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rts_reads_bus_on_internal_cycles() {
+        // JSR $0405, BRK, BRK, RTS
+        let program = [0x20, 0x05, 0x04, 0x00, 0x00, 0x60];
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &program, 2);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0405, 0x60, BusAccessKind::OpcodeFetch),
+                (0x0406, 0x00, BusAccessKind::DummyRead),
+                (0x01FB, 0x00, BusAccessKind::DummyRead),
+                (0x01FC, 0x02, BusAccessKind::OperandRead),
+                (0x01FD, 0x04, BusAccessKind::OperandRead),
+                (0x0402, 0x04, BusAccessKind::DummyRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_indirect_reads_pointer_while_adding_x() {
+        // LDA ($80,X)
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &[0xA1, 0x80], 1);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0xA1, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x80, BusAccessKind::OperandRead),
+                (0x0080, 0x00, BusAccessKind::DummyRead),
+                (0x0080, 0x00, BusAccessKind::OperandRead),
+                (0x0081, 0x00, BusAccessKind::OperandRead),
+                (0x0000, 0x00, BusAccessKind::OperandRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indirect_indexed_read_modify_write_always_takes_fix_up_cycle() {
+        // SLO ($80),Y doesn't cross a page but still reads before writing
+        let (accesses, cycles) = run_program(CpuVariant::Nmos6502, 0x0400, &[0x13, 0x80], 1);
+
+        assert_eq!(cycles, 8);
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0x13, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x80, BusAccessKind::OperandRead),
+                (0x0080, 0x00, BusAccessKind::OperandRead),
+                (0x0081, 0x00, BusAccessKind::OperandRead),
+                (0x0000, 0x00, BusAccessKind::DummyRead),
+                (0x0000, 0x00, BusAccessKind::OperandRead),
+                (0x0000, 0x00, BusAccessKind::DummyWrite),
+                (0x0000, 0x00, BusAccessKind::Write),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jsr_reads_high_byte_after_pushing() {
+        // JSR $1234
+        let (accesses, _) = run_program(CpuVariant::Nmos6502, 0x0400, &[0x20, 0x34, 0x12], 1);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0x20, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x34, BusAccessKind::OperandRead),
+                (0x01FD, 0x00, BusAccessKind::DummyRead),
+                (0x01FD, 0x04, BusAccessKind::Write),
+                (0x01FC, 0x02, BusAccessKind::Write),
+                (0x0402, 0x12, BusAccessKind::OperandRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taken_branch_reads_next_opcode() {
+        // BNE +$02
+        let (accesses, cycles) = run_program(CpuVariant::Nmos6502, 0x0400, &[0xD0, 0x02], 1);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0xD0, BusAccessKind::OpcodeFetch),
+                (0x0401, 0x02, BusAccessKind::OperandRead),
+                (0x0402, 0x00, BusAccessKind::DummyRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch_across_page_reads_uncorrected_pc() {
+        // BPL +$10 from $04FA lands on $050C, via $040C before the high byte is fixed
+        let (accesses, cycles) = run_program(CpuVariant::Nmos6502, 0x04FA, &[0x10, 0x10], 1);
+
+        assert_eq!(cycles, 4);
+        assert_eq!(
+            accesses,
+            vec![
+                (0x04FA, 0x10, BusAccessKind::OpcodeFetch),
+                (0x04FB, 0x10, BusAccessKind::OperandRead),
+                (0x04FC, 0x00, BusAccessKind::DummyRead),
+                (0x040C, 0x00, BusAccessKind::DummyRead),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_read_across_page_reads_uncorrected_address() {
+        // LDX #$02, LDA $12FF,X
+        let (accesses, cycles) = run_program(
+            CpuVariant::Nmos6502,
+            0x0400,
+            &[0xA2, 0x02, 0xBD, 0xFF, 0x12],
+            2,
+        );
+
+        assert_eq!(cycles, 5);
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0402, 0xBD, BusAccessKind::OpcodeFetch),
+                (0x0403, 0xFF, BusAccessKind::OperandRead),
+                (0x0404, 0x12, BusAccessKind::OperandRead),
+                (0x1201, 0x00, BusAccessKind::DummyRead),
+                (0x1301, 0x00, BusAccessKind::OperandRead),
+            ]
+        );
+    }
+}
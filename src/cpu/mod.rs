@@ -1,24 +1,50 @@
+#[cfg(feature = "bench")]
+mod bench;
+pub(crate) mod disasm;
+#[cfg(test)]
+mod functional_tests;
 pub(crate) mod interrupts;
+mod micro_ops;
 mod opcodes;
+#[cfg(test)]
+mod processor_tests;
 mod registers;
+mod save_state;
 mod status_flags;
+#[cfg(any(test, feature = "bench"))]
+mod test_bus;
+mod variant;
 
 use std::panic;
 
-use interrupts::Interrupt;
-use log::{debug, info};
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use bench::BenchCpu;
+use interrupts::{Interrupt, InterruptLines};
+use log::info;
+use micro_ops::{Latches, Step};
 use opcodes::Opcode;
-use opcodes::{AddressingMode, InstructionType, Operation, OPCODE_TABLE};
+use opcodes::OPCODE_TABLE;
 use registers::Registers;
+pub use registers::{Register, RegisterSnapshot};
 use status_flags::StatusFlags;
+pub use status_flags::Flag;
+use variant::AddressLines;
+pub(crate) use variant::CpuVariant;
 use wasm_bindgen::prelude::*;
 
+use crate::bus::{Bus, BusAccess, BusAccessKind, JsDevice};
+use crate::utils::init_logging;
 use crate::Device;
 
 #[derive(Debug, Copy, Clone)]
 enum State {
     Interrupt(InterruptState),
     Cpu(CpuState),
+    // A KIL opcode has locked up the cpu, only a RESET will recover it
+    Jammed,
+    // The 65C02's WAI, stopped until an interrupt line is asserted
+    Waiting,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -39,60 +65,10 @@ enum InterruptState {
 enum CpuState {
     // Cycle 1 is always reading the PC and incrementing it
     FetchOpcode,
-    // Cycle 2 always reads the (incremented) PC, but for implied &
-    // accumulator modes this value is then discarded and the PC is not
-    // incremented
-    ThrowawayRead {
-        opcode: &'static Opcode,
-        operand: Option<u8>,
-    },
-    // Cycles 2-5 cover reading the operand & address depending on the addressing mode
-    ReadingOperand {
-        opcode: &'static Opcode,
-        address_low_byte: Option<u8>,
-        address_high_byte: Option<u8>,
-        pointer: Option<u8>,
-        indirect_address_low_byte: Option<u8>,
-        indirect_address_high_byte: Option<u8>,
-        checked_page_boundary: bool,
-    },
-    BranchCrossesPageBoundary {
-        opcode: &'static Opcode,
-        address: Option<u16>,
-        operand: Option<u8>,
-    },
-    PushRegisterOnStack {
-        value: u8,
-    },
-    PreIncrementStackPointer {
-        operation: Operation,
-    },
-    PullRegisterFromStack {
-        operation: Operation,
-    },
-    PullPCLFromStack {
-        operation: Operation,
-    },
-    PullPCHFromStack {
-        operation: Operation,
-        pcl: u8,
-    },
-    IncrementProgramCounter,
-    WritePCHToStack {
-        address: u16,
-    },
-    WritePCLToStack {
-        address: u16,
-    },
-    SetProgramCounter {
-        address: u16,
-        was_branch_instruction: bool,
-    },
-    WritingResult {
-        address: u16,
-        value: u8,
-        dummy: bool,
-    },
+    // The remaining cycles run through the opcode's micro-ops, `step` is the next one to run
+    Executing { opcode: &'static Opcode, step: u8 },
+    // The 65C02 takes an extra cycle to fix up the flags after decimal mode ADC & SBC
+    DecimalAdjust,
 }
 
 pub(crate) type CpuCycle = u32;
@@ -103,28 +79,126 @@ pub struct Cpu {
     registers: Registers,
     pub cycles: CpuCycle,
     cpu_cycle_counter: u8,
+    // Interrupt chosen on the last cycle of the current instruction, serviced once it completes
     polled_interrupt: Option<Interrupt>,
+    interrupt_lines: InterruptLines,
+    magic_constant: u8,
+    variant: CpuVariant,
+    latches: Latches,
 }
 
+/// The value most commonly observed being ORed into A by XAA and LAX #imm
+const DEFAULT_MAGIC_CONSTANT: u8 = 0xEE;
+
 impl Cpu {
-    fn push_to_stack(&mut self, device: &Device, value: u8) {
-        device.write_byte(self.registers.stack_pointer as u16 | 0x0100, value);
+    /// Let the bus see the access which was just made and what it was for
+    fn observe<B: Bus>(&self, bus: &mut B, address: u16, value: u8, kind: BusAccessKind) {
+        bus.observe(BusAccess {
+            cycle: self.cycles,
+            address,
+            value,
+            kind,
+        });
+    }
+
+    /// Read a byte which the instruction goes on to use
+    fn read<B: Bus>(&self, bus: &mut B, address: u16) -> u8 {
+        let value = bus.read_byte(address);
+        self.observe(bus, address, value, BusAccessKind::OperandRead);
+
+        value
+    }
+
+    /// Read a byte only because the cpu always drives the bus, the value is discarded
+    fn dummy_read<B: Bus>(&self, bus: &mut B, address: u16) -> u8 {
+        let value = bus.read_byte(address);
+        self.observe(bus, address, value, BusAccessKind::DummyRead);
+
+        value
+    }
+
+    fn write<B: Bus>(&self, bus: &mut B, address: u16, value: u8) {
+        bus.write_byte(address, value);
+        self.observe(bus, address, value, BusAccessKind::Write);
+    }
+
+    /// The NMOS read modify write instructions write the unmodified value back first
+    fn dummy_write<B: Bus>(&self, bus: &mut B, address: u16, value: u8) {
+        bus.write_byte(address, value);
+        self.observe(bus, address, value, BusAccessKind::DummyWrite);
+    }
+
+    fn push_to_stack<B: Bus>(&mut self, bus: &mut B, value: u8) {
+        self.write(bus, self.registers.stack_pointer as u16 | 0x0100, value);
         self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(1);
     }
 
-    fn pop_from_stack(&mut self, device: &Device) -> u8 {
+    fn pop_from_stack<B: Bus>(&mut self, bus: &mut B) -> u8 {
         self.registers.stack_pointer = self.registers.stack_pointer.wrapping_add(1);
-        device.read_byte(self.registers.stack_pointer as u16 | 0x0100)
+        self.read(bus, self.registers.stack_pointer as u16 | 0x0100)
     }
 
-    fn read_and_inc_program_counter(&mut self, device: &Device) -> u8 {
-        let value = device.read_byte(self.registers.program_counter);
+    ///
+    /// RESET runs through the same sequence as the other interrupts but with
+    /// the R/W line held high, so the stack "pushes" are reads and only the
+    /// stack pointer changes.
+    ///
+    fn push_interrupt_value_to_stack<B: Bus>(&mut self, bus: &mut B, interrupt: Interrupt, value: u8) {
+        match interrupt {
+            Interrupt::RESET(_) => {
+                self.dummy_read(bus, self.registers.stack_pointer as u16 | 0x0100);
+                self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(1);
+            }
+            _ => self.push_to_stack(bus, value),
+        }
+    }
+
+    /// Called on the last cycle of each instruction to decide whether to service an interrupt after it
+    fn poll_interrupts(&mut self) {
+        self.polled_interrupt = self.interrupt_lines.sampled;
+    }
+
+    fn read_and_inc_program_counter<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = self.read(bus, self.registers.program_counter);
         self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
 
         value
     }
 
+    fn fetch_opcode<B: Bus>(&mut self, bus: &mut B) -> &'static Opcode {
+        let value = bus.read_byte(self.registers.program_counter);
+        self.observe(bus, self.registers.program_counter, value, BusAccessKind::OpcodeFetch);
+        self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
+
+        &self.variant.opcode_table()[value as usize]
+    }
+
+    /// The 2A03 has a D flag but the circuitry which acts on it isn't connected
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal_mode()
+            && self
+                .registers
+                .status_register
+                .contains(StatusFlags::DECIMAL_FLAG)
+    }
+
     fn adc(&mut self, operand: u8) {
+        if self.decimal_mode() {
+            self.adc_decimal(operand);
+        } else {
+            self.adc_binary(operand);
+        }
+    }
+
+    fn sbc(&mut self, operand: u8) {
+        if self.decimal_mode() {
+            self.sbc_decimal(operand);
+        } else {
+            self.adc_binary(!operand);
+        }
+    }
+
+    fn adc_binary(&mut self, operand: u8) {
         let result: u16 = match self
             .registers
             .status_register
@@ -150,6 +224,176 @@ impl Cpu {
             .set(StatusFlags::CARRY_FLAG, result > u8::MAX as u16);
     }
 
+    ///
+    /// Decimal mode addition as performed by the NMOS 6502/6507.
+    ///
+    /// The accumulator and carry are the BCD result, but the flags are quirky:
+    /// Z comes from the binary sum and N/V come from the intermediate value
+    /// after the low nibble has been adjusted but before the high nibble is.
+    /// See <http://www.6502.org/tutorials/decimal_mode.html> appendix A.
+    ///
+    /// The 65C02 fixes N & Z so they reflect the BCD result, V is unchanged.
+    ///
+    fn adc_decimal(&mut self, operand: u8) {
+        let a = self.registers.a;
+        let carry = self
+            .registers
+            .status_register
+            .contains(StatusFlags::CARRY_FLAG) as u16;
+
+        let binary_result = (a as u16 + operand as u16 + carry) as u8;
+
+        let mut low = (a & 0x0F) as u16 + (operand & 0x0F) as u16 + carry;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+
+        // N & V are taken from the signed sum with only the low nibble adjusted
+        let signed_intermediate =
+            (a & 0xF0) as i8 as i16 + (operand & 0xF0) as i8 as i16 + low as i16;
+
+        let mut result = (a & 0xF0) as u16 + (operand & 0xF0) as u16 + low;
+        if result >= 0xA0 {
+            result += 0x60;
+        }
+
+        self.registers.a = (result & 0xFF) as u8;
+        self.registers
+            .status_register
+            .set(StatusFlags::ZERO_FLAG, binary_result == 0);
+        self.registers
+            .status_register
+            .set(StatusFlags::NEGATIVE_FLAG, signed_intermediate & 0x80 != 0);
+        self.registers.status_register.set(
+            StatusFlags::OVERFLOW_FLAG,
+            !(-128..=127).contains(&signed_intermediate),
+        );
+        self.registers
+            .status_register
+            .set(StatusFlags::CARRY_FLAG, result >= 0x100);
+
+        if self.variant.is_cmos() {
+            self.set_negative_zero_flags(self.registers.a);
+        }
+    }
+
+    ///
+    /// Decimal mode subtraction as performed by the NMOS 6502/6507.
+    ///
+    /// Unlike addition all of the flags are set exactly as they would be for
+    /// a binary subtraction, only the accumulator gets the BCD result.
+    ///
+    /// The 65C02 adjusts the whole result rather than nibble by nibble, which
+    /// only differs for invalid BCD, and sets N & Z from the BCD result.
+    ///
+    fn sbc_decimal(&mut self, operand: u8) {
+        let a = self.registers.a;
+        let borrow = !self
+            .registers
+            .status_register
+            .contains(StatusFlags::CARRY_FLAG) as i16;
+
+        let mut low = (a & 0x0F) as i16 - (operand & 0x0F) as i16 - borrow;
+        let result = if self.variant.is_cmos() {
+            let mut result = a as i16 - operand as i16 - borrow;
+            if result < 0 {
+                result -= 0x60;
+            }
+            if low < 0 {
+                result -= 0x06;
+            }
+            result
+        } else {
+            if low < 0 {
+                low = ((low - 0x06) & 0x0F) - 0x10;
+            }
+
+            let mut result = (a & 0xF0) as i16 - (operand & 0xF0) as i16 + low;
+            if result < 0 {
+                result -= 0x60;
+            }
+            result
+        };
+
+        // Flags are identical to the binary operation
+        self.adc_binary(!operand);
+        self.registers.a = (result & 0xFF) as u8;
+
+        if self.variant.is_cmos() {
+            self.set_negative_zero_flags(self.registers.a);
+        }
+    }
+
+    ///
+    /// ADC & SBC finish here. The 65C02 takes an extra cycle in decimal mode,
+    /// so polls for interrupts on that instead.
+    ///
+    fn finish_arithmetic(&mut self) -> Step {
+        if self.variant.is_cmos() && self.decimal_mode() {
+            Step::Goto(State::Cpu(CpuState::DecimalAdjust))
+        } else {
+            Step::Done
+        }
+    }
+
+    ///
+    /// ARR is an AND followed by ROR but the flags come from the adder, so C
+    /// is bit 6 of the result and V is bit 6 XOR bit 5. On NMOS parts it also
+    /// applies a decimal correction when the D flag is set.
+    ///
+    fn arr(&mut self, operand: u8) {
+        let carry = self
+            .registers
+            .status_register
+            .contains(StatusFlags::CARRY_FLAG);
+        let and = self.registers.a & operand;
+        let mut result = (and >> 1) | ((carry as u8) << 7);
+
+        self.set_negative_zero_flags(result);
+        self.registers.status_register.set(
+            StatusFlags::OVERFLOW_FLAG,
+            (and ^ result) & 0b0100_0000 != 0,
+        );
+
+        if self.decimal_mode() {
+            if (and & 0x0F) + (and & 0x01) > 0x05 {
+                result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+            }
+
+            let decimal_carry = (and & 0xF0) as u16 + (and & 0x10) as u16 > 0x50;
+            if decimal_carry {
+                result = result.wrapping_add(0x60);
+            }
+            self.registers
+                .status_register
+                .set(StatusFlags::CARRY_FLAG, decimal_carry);
+        } else {
+            self.registers
+                .status_register
+                .set(StatusFlags::CARRY_FLAG, result & 0b0100_0000 != 0);
+        }
+
+        self.registers.a = result;
+    }
+
+    ///
+    /// SHX, SHY, AHX & TAS store a value ANDed with the high byte of the base
+    /// address plus one. When the indexing crosses a page the value is also
+    /// driven onto the high byte of the address bus, so the write lands
+    /// somewhere quite different to where it was aimed.
+    ///
+    fn unstable_store(&self, value: u8, address: u16, index: u8) -> (u16, u8) {
+        let base_address = address.wrapping_sub(index as u16);
+        let value = value & ((base_address >> 8) as u8).wrapping_add(1);
+        let address = if (base_address ^ address) & 0xFF00 != 0 {
+            (address & 0x00FF) | ((value as u16) << 8)
+        } else {
+            address
+        };
+
+        (address, value)
+    }
+
     fn compare(&mut self, operand: u8, register: u8) {
         let result = register.wrapping_sub(operand);
         self.registers
@@ -181,181 +425,49 @@ impl Cpu {
             .set(StatusFlags::NEGATIVE_FLAG, operand & 0b1000_0000 != 0);
     }
 
-    fn next_absolute_mode_state(
-        &mut self,
-        device: &Device,
-        opcode: &'static Opcode,
-        address_low_byte: Option<u8>,
-        address_high_byte: Option<u8>,
-    ) -> State {
-        match (address_low_byte, address_high_byte) {
-            // Cycle 2 - Read low byte
-            (None, _) => State::Cpu(CpuState::ReadingOperand {
-                opcode,
-                address_low_byte: Some(self.read_and_inc_program_counter(device)),
-                address_high_byte,
-                pointer: None,
-                indirect_address_low_byte: None,
-                indirect_address_high_byte: None,
-                checked_page_boundary: false,
-            }),
-            // Cycle 3 - Read high byte
-            (Some(low_byte), None) => {
-                let high_byte = self.read_and_inc_program_counter(device);
-
-                match opcode.operation.instruction_type() {
-                    // Some instructions don't make use of the value at the absolute address, some do
-                    InstructionType::Jump | InstructionType::Write => opcode.execute(
-                        self,
-                        device,
-                        None,
-                        Some(low_byte as u16 | ((high_byte as u16) << 8)),
-                    ),
-                    _ => State::Cpu(CpuState::ReadingOperand {
-                        opcode,
-                        address_low_byte,
-                        address_high_byte: Some(high_byte),
-                        pointer: None,
-                        indirect_address_low_byte: None,
-                        indirect_address_high_byte: None,
-                        checked_page_boundary: false,
-                    }),
-                }
-            }
-            // Cycle 4 - Read $HHLL from memory as operand
-            (Some(low_byte), Some(high_byte)) => {
-                let address = low_byte as u16 | ((high_byte as u16) << 8);
-                let value = Some(device.read_byte(address));
-                opcode.execute(self, device, value, Some(address))
-            }
-        }
-    }
-
-    fn next_absolute_indexed_mode_state(
-        &mut self,
-        device: &Device,
-        opcode: &'static Opcode,
-        address_low_byte: Option<u8>,
-        address_high_byte: Option<u8>,
-        checked_page_boundary: bool,
-        index: u8,
-    ) -> State {
-        match (address_low_byte, address_high_byte) {
-            // Cycle 2 - Read low byte
-            (None, None) => State::Cpu(CpuState::ReadingOperand {
-                opcode,
-                address_low_byte: Some(self.read_and_inc_program_counter(device)),
-                address_high_byte,
-                pointer: None,
-                indirect_address_low_byte: None,
-                indirect_address_high_byte: None,
-                checked_page_boundary: false,
-            }),
-            // Cycle 3 - Read high byte
-            (Some(_), None) => State::Cpu(CpuState::ReadingOperand {
-                opcode,
-                address_low_byte,
-                address_high_byte: Some(self.read_and_inc_program_counter(device)),
-                pointer: None,
-                indirect_address_low_byte: None,
-                indirect_address_high_byte: None,
-                checked_page_boundary: false,
-            }),
-            // Cycle 4 - Read $HHLL from memory as operand
-            (Some(low_byte), Some(high_byte)) => {
-                let unindexed_address = low_byte as u16 | ((high_byte as u16) << 8);
-                let correct_address = unindexed_address.wrapping_add(index as u16);
-
-                if checked_page_boundary {
-                    let value = Some(device.read_byte(correct_address));
-                    opcode.execute(self, device, value, Some(correct_address))
-                } else {
-                    let first_read_address =
-                        low_byte.wrapping_add(index) as u16 | ((high_byte as u16) << 8);
-
-                    match opcode.operation.instruction_type() {
-                        InstructionType::Read => {
-                            if correct_address == first_read_address {
-                                let value = Some(device.read_byte(correct_address));
-                                opcode.execute(self, device, value, Some(correct_address))
-                            } else {
-                                // Dummy read, we're going to go read from the right address next
-                                let _ = device.read_byte(first_read_address);
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte,
-                                    pointer: None,
-                                    indirect_address_low_byte: None,
-                                    indirect_address_high_byte: None,
-                                    checked_page_boundary: true,
-                                })
-                            }
-                        }
-                        InstructionType::ReadModifyWrite => {
-                            // Dummy read, we're going to go read from the right address next
-                            let _ = device.read_byte(first_read_address);
-
-                            // Instructions which both read & write will always read twice
-                            State::Cpu(CpuState::ReadingOperand {
-                                opcode,
-                                address_low_byte,
-                                address_high_byte,
-                                pointer: None,
-                                indirect_address_low_byte: None,
-                                indirect_address_high_byte: None,
-                                checked_page_boundary: true,
-                            })
-                        }
-                        _ => {
-                            let value = Some(device.read_byte(first_read_address));
-                            opcode.execute(self, device, value, Some(correct_address))
-                        }
-                    }
-                }
-            }
-            (_, _) => panic!(), // Coding bug, can't read high byte first
-        }
-    }
-
-    fn step_interrupt_handler(&mut self, device: &Device, state: InterruptState) -> State {
+    fn step_interrupt_handler<B: Bus>(&mut self, bus: &mut B, state: InterruptState) -> State {
         info!("Interrupt state: {:?} at cycle {}", state, self.cycles);
 
         match state {
-            InterruptState::InternalOps1(i) => State::Interrupt(InterruptState::InternalOps2(i)),
-            InterruptState::InternalOps2(i) => State::Interrupt(InterruptState::PushPCH(i)),
+            // The opcode fetch & operand read are done as normal but the results are discarded
+            InterruptState::InternalOps1(i) => {
+                self.dummy_read(bus, self.registers.program_counter);
+                State::Interrupt(InterruptState::InternalOps2(i))
+            }
+            InterruptState::InternalOps2(i) => {
+                self.dummy_read(bus, self.registers.program_counter);
+                State::Interrupt(InterruptState::PushPCH(i))
+            }
             InterruptState::PushPCH(i) => {
-                self.push_to_stack(device, (self.registers.program_counter >> 8) as u8);
+                self.push_interrupt_value_to_stack(
+                    bus,
+                    i,
+                    (self.registers.program_counter >> 8) as u8,
+                );
 
                 State::Interrupt(InterruptState::PushPCL(i))
             }
             InterruptState::PushPCL(i) => {
-                self.push_to_stack(device, self.registers.program_counter as u8);
+                self.push_interrupt_value_to_stack(bus, i, self.registers.program_counter as u8);
                 State::Interrupt(InterruptState::PushStatusRegister(i))
             }
             InterruptState::PushStatusRegister(i) => {
-                device.poll_for_interrupts(false);
-
-                // Since we've just polled for interrupts this may affect which interrupt is now actually executed
-                // NMI overrides BRK & IRQ,
-                // IRQ overrides BRK
-                let i = match (i, self.polled_interrupt) {
-                    (_, None) => i,
-                    (Interrupt::NMI(_), _) => i,
-                    (Interrupt::RESET(_), _) => i,
-                    (Interrupt::IRQ_BRK(_), Some(interrupt)) => {
-                        info!("Interrupt {:?} overrode {:?}", interrupt, i);
-                        interrupt
-                    }
-                    (Interrupt::IRQ(_), Some(interrupt)) => {
-                        info!("Interrupt {:?} overrode {:?}", interrupt, i);
-                        interrupt
+                // An NMI detected before this point hijacks a BRK or IRQ, which then
+                // continues with the NMI vector (BRK still pushes the B flag)
+                let vector = match i {
+                    Interrupt::RESET(_) => i,
+                    _ if self.interrupt_lines.acknowledge_nmi() => {
+                        if !matches!(i, Interrupt::NMI(_)) {
+                            info!("NMI hijacked {:?}", i);
+                        }
+                        Interrupt::NMI(self.cycles)
                     }
+                    _ => i,
                 };
-                self.polled_interrupt = None;
 
-                self.push_to_stack(
-                    device,
+                self.push_interrupt_value_to_stack(
+                    bus,
+                    i,
                     match i {
                         Interrupt::IRQ_BRK(_) => {
                             self.registers.status_register.bits() | 0b0011_0000
@@ -368,681 +480,1469 @@ impl Cpu {
                 self.registers
                     .status_register
                     .insert(StatusFlags::INTERRUPT_DISABLE_FLAG);
+                // The 65C02 also leaves decimal mode so that handlers needn't CLD
+                if self.variant.is_cmos() {
+                    self.registers.status_register.remove(StatusFlags::DECIMAL_FLAG);
+                }
 
-                State::Interrupt(InterruptState::PullIRQVecHigh(i))
+                State::Interrupt(InterruptState::PullIRQVecHigh(vector))
             }
             InterruptState::PullIRQVecHigh(i) => {
-                self.registers.program_counter = device.read_byte(i.offset()) as u16;
+                self.registers.program_counter = self.read(bus, i.offset()) as u16;
 
                 State::Interrupt(InterruptState::PullIRQVecLow(i))
             }
             InterruptState::PullIRQVecLow(i) => {
                 self.registers.program_counter = (self.registers.program_counter & 0b1111_1111)
-                    | ((device.read_byte(i.offset().wrapping_add(1)) as u16) << 8);
+                    | ((self.read(bus, i.offset().wrapping_add(1)) as u16) << 8);
 
                 State::Cpu(CpuState::FetchOpcode)
             }
         }
     }
 
-    fn step_cpu(&mut self, device: &Device, state: CpuState) -> State {
-        match state {
+    fn step_jammed<B: Bus>(&mut self, bus: &mut B) -> State {
+        // The jammed cpu is stuck with $FFFF on the address bus and keeps
+        // reading it every cycle until it is reset
+        self.dummy_read(bus, 0xFFFF);
+
+        State::Jammed
+    }
+
+    fn step_cpu<B: Bus>(&mut self, bus: &mut B, state: CpuState) -> State {
+        let (opcode, next_step) = match state {
             CpuState::FetchOpcode => {
-                let opcode = &OPCODE_TABLE[self.read_and_inc_program_counter(device) as usize];
+                let opcode = self.fetch_opcode(bus);
 
                 info!("Opcode: {:?} at cycle {}", opcode, self.cycles);
 
-                match opcode.address_mode {
-                    AddressingMode::Accumulator => State::Cpu(CpuState::ThrowawayRead {
-                        opcode,
-                        operand: Some(self.registers.a),
-                    }),
-                    AddressingMode::Implied => State::Cpu(CpuState::ThrowawayRead {
-                        opcode,
-                        operand: None,
-                    }),
-                    _ => State::Cpu(CpuState::ReadingOperand {
-                        opcode,
-                        address_low_byte: None,
-                        address_high_byte: None,
-                        pointer: None,
-                        indirect_address_low_byte: None,
-                        indirect_address_high_byte: None,
-                        checked_page_boundary: false,
-                    }),
+                (opcode, 0)
+            }
+            CpuState::Executing { opcode, step } => {
+                let micro_op = self.variant.micro_op_table()[opcode.opcode as usize][step as usize];
+
+                match self.run_micro_op(bus, opcode, micro_op) {
+                    Step::Next => (opcode, step as usize + 1),
+                    Step::Skip => (opcode, step as usize + 2),
+                    Step::Done => (opcode, usize::MAX),
+                    Step::DoneWithoutPoll => return State::Cpu(CpuState::FetchOpcode),
+                    Step::Goto(state) => return state,
                 }
             }
-            CpuState::ReadingOperand {
+            CpuState::DecimalAdjust => {
+                self.dummy_read(bus, self.registers.program_counter);
+                self.poll_interrupts();
+
+                return State::Cpu(CpuState::FetchOpcode);
+            }
+        };
+
+        if next_step < self.variant.micro_op_table()[opcode.opcode as usize].len() {
+            State::Cpu(CpuState::Executing {
                 opcode,
-                address_low_byte,
-                address_high_byte,
-                pointer,
-                indirect_address_low_byte,
-                indirect_address_high_byte,
-                checked_page_boundary,
-            } => {
-                match opcode.address_mode {
-                    AddressingMode::Absolute => self.next_absolute_mode_state(
-                        device,
-                        opcode,
-                        address_low_byte,
-                        address_high_byte,
-                    ),
-                    AddressingMode::AbsoluteXIndexed => self.next_absolute_indexed_mode_state(
-                        device,
-                        opcode,
-                        address_low_byte,
-                        address_high_byte,
-                        checked_page_boundary,
-                        self.registers.x,
-                    ),
-                    AddressingMode::AbsoluteYIndexed => self.next_absolute_indexed_mode_state(
-                        device,
-                        opcode,
-                        address_low_byte,
-                        address_high_byte,
-                        checked_page_boundary,
-                        self.registers.y,
-                    ),
-                    AddressingMode::Immediate => {
-                        let operand = Some(self.read_and_inc_program_counter(device));
-                        opcode.execute(
-                            self,
-                            device,
-                            operand,
-                            Some(self.registers.program_counter.wrapping_sub(1)),
-                        )
-                    }
-                    AddressingMode::Indirect => {
-                        match (
-                            indirect_address_low_byte,
-                            indirect_address_high_byte,
-                            address_low_byte,
-                        ) {
-                            (None, _, _) => {
-                                // Cycle 1 - Read the indirect address low byte
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: None,
-                                    address_high_byte: None,
-                                    pointer: None,
-                                    indirect_address_low_byte: Some(
-                                        self.read_and_inc_program_counter(device),
-                                    ),
-                                    indirect_address_high_byte: None,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(_), None, _) => {
-                                // Cycle 2 - Read the indirect address high byte
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: None,
-                                    address_high_byte: None,
-                                    pointer: None,
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte: Some(
-                                        self.read_and_inc_program_counter(device),
-                                    ),
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), Some(indirect_high_byte), None) => {
-                                let indirect_address =
-                                    (indirect_low_byte as u16) | ((indirect_high_byte as u16) << 8);
-
-                                // Cycle 3 - Read the address low byte from the indirect address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: Some(device.read_byte(indirect_address)),
-                                    address_high_byte: None,
-                                    pointer: None,
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), Some(indirect_high_byte), Some(low_byte)) => {
-                                // Cycle 4 - Read the address high byte from the indirect address and immediately set the PC as this is always a JMP instruction
-                                // Note - this is deliberately "bugged", JMP (0x01FF) will jump to 0x01FF | 0x0100 << 8 NOT 0x01FF | 0x0200 << 8 as you might imagine (this is a known 6502 cpu bug)
-                                let indirect_address = (indirect_low_byte.wrapping_add(1) as u16)
-                                    | ((indirect_high_byte as u16) << 8);
-                                let high_byte = device.read_byte(indirect_address);
-
-                                opcode.execute(
-                                    self,
-                                    device,
-                                    None,
-                                    Some((low_byte as u16) | ((high_byte as u16) << 8)),
-                                )
-                            }
-                        }
-                    }
-                    AddressingMode::IndirectXIndexed => {
-                        match (
-                            indirect_address_low_byte,
-                            pointer,
-                            address_low_byte,
-                            address_high_byte,
-                        ) {
-                            (None, _, _, _) => {
-                                // Cycle 1 - Read the low byte of the indirect address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte,
-                                    pointer: None,
-                                    indirect_address_low_byte: Some(
-                                        self.read_and_inc_program_counter(device),
-                                    ),
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(_), None, _, _) => {
-                                // Cycle 2 - Construct the pointer to the actual address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte,
-                                    pointer: indirect_address_low_byte,
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), Some(_), None, _) => {
-                                // Cycle 3 - Read the low byte of the actual address
-                                let address =
-                                    indirect_low_byte.wrapping_add(self.registers.x) as u16;
-
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: Some(device.read_byte(address)),
-                                    address_high_byte,
-                                    pointer,
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), Some(_), Some(address_low_byte), None) => {
-                                // Cycle 4 - Read the high byte of the actual address
-                                let indirect_address_high_byte = indirect_low_byte
-                                    .wrapping_add(self.registers.x)
-                                    .wrapping_add(1)
-                                    as u16;
-                                let address_high_byte =
-                                    device.read_byte(indirect_address_high_byte);
-
-                                match opcode.operation.instruction_type() {
-                                    InstructionType::Write => {
-                                        let address = (address_low_byte as u16)
-                                            | ((address_high_byte as u16) << 8);
-                                        opcode.execute(self, device, None, Some(address))
-                                    }
-                                    _ => State::Cpu(CpuState::ReadingOperand {
-                                        opcode,
-                                        address_low_byte: Some(address_low_byte),
-                                        address_high_byte: Some(address_high_byte),
-                                        pointer,
-                                        indirect_address_low_byte,
-                                        indirect_address_high_byte: Some(
-                                            indirect_address_high_byte as u8,
-                                        ),
-                                        checked_page_boundary: false,
-                                    }),
-                                }
-                            }
-                            (Some(_), Some(_), Some(low_byte), Some(high_byte)) => {
-                                let address = (low_byte as u16) | ((high_byte as u16) << 8);
-                                let value = Some(device.read_byte(address));
-
-                                // Cycle 5 - Read the operand and execute operation
-                                opcode.execute(self, device, value, Some(address))
-                            }
-                        }
-                    }
-                    AddressingMode::IndirectYIndexed => {
-                        match (
-                            indirect_address_low_byte,
-                            address_low_byte,
-                            address_high_byte,
-                        ) {
-                            (None, _, _) => {
-                                // Cycle 2 - Read the low byte of the indirect address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte,
-                                    pointer: None,
-                                    indirect_address_low_byte: Some(
-                                        self.read_and_inc_program_counter(device),
-                                    ),
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), None, _) => {
-                                // Cycle 3 - Read the low byte of the actual address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: Some(
-                                        device.read_byte(indirect_low_byte as u16),
-                                    ),
-                                    address_high_byte,
-                                    pointer: None,
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(indirect_low_byte), Some(address_low_byte), None) => {
-                                // Cycle 4 - Read the high byte of the actual address
-                                State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: Some(address_low_byte),
-                                    address_high_byte: Some(
-                                        device.read_byte(indirect_low_byte.wrapping_add(1) as u16),
-                                    ),
-                                    pointer: Some(indirect_low_byte),
-                                    indirect_address_low_byte,
-                                    indirect_address_high_byte,
-                                    checked_page_boundary: false,
-                                })
-                            }
-                            (Some(_), Some(low_byte), Some(high_byte)) => {
-                                // Cycle 5(/6) - Read the operand and execute the operation checking for crossing page boundary
-                                let unindexed_address =
-                                    (low_byte as u16) | ((high_byte as u16) << 8);
-                                let dummy_read_address = low_byte.wrapping_add(self.registers.y)
-                                    as u16
-                                    | ((high_byte as u16) << 8);
-                                let address =
-                                    unindexed_address.wrapping_add(self.registers.y as u16);
-
-                                match opcode.operation.instruction_type() {
-                                    InstructionType::Write => {
-                                        // Dummy read of address without fixing the high byte (so without wrap)
-                                        let _ = Some(device.read_byte(dummy_read_address));
-                                        opcode.execute(self, device, None, Some(address))
-                                    }
-                                    _ => {
-                                        if checked_page_boundary || (dummy_read_address == address)
-                                        {
-                                            let value = Some(device.read_byte(address));
-                                            opcode.execute(self, device, value, Some(address))
-                                        } else {
-                                            // Dummy read of address without fixing the high byte (so without wrap)
-                                            let _ = Some(device.read_byte(dummy_read_address));
-
-                                            State::Cpu(CpuState::ReadingOperand {
-                                                opcode,
-                                                address_low_byte: Some(low_byte),
-                                                address_high_byte: Some(high_byte),
-                                                pointer: None,
-                                                indirect_address_low_byte,
-                                                indirect_address_high_byte,
-                                                checked_page_boundary: true,
-                                            })
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    AddressingMode::Relative => {
-                        // Cycle 2 - Get the relative index and store it in the operand for use in the instruction (it'll be a signed 8 bit relative index)
-                        let relative_operand = self.read_and_inc_program_counter(device);
-
-                        let branch = match opcode.operation {
-                            Operation::BCC => !self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::CARRY_FLAG),
-                            Operation::BCS => self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::CARRY_FLAG),
-                            Operation::BEQ => self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::ZERO_FLAG),
-                            Operation::BMI => self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::NEGATIVE_FLAG),
-                            Operation::BNE => !self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::ZERO_FLAG),
-                            Operation::BPL => !self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::NEGATIVE_FLAG),
-                            Operation::BVC => !self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::OVERFLOW_FLAG),
-                            Operation::BVS => self
-                                .registers
-                                .status_register
-                                .contains(StatusFlags::OVERFLOW_FLAG),
-                            _ => panic!(),
-                        };
-
-                        if !branch {
-                            State::Cpu(CpuState::FetchOpcode)
-                        } else {
-                            let address = self
-                                .registers
-                                .program_counter
-                                .wrapping_add((relative_operand as i8) as u16);
-
-                            if (address >> 8) != (self.registers.program_counter >> 8) {
-                                State::Cpu(CpuState::BranchCrossesPageBoundary {
-                                    opcode,
-                                    operand: Some(relative_operand),
-                                    address: Some(address),
-                                })
-                            } else {
-                                opcode.execute(self, device, Some(relative_operand), Some(address))
-                            }
-                        }
-                    }
-                    AddressingMode::ZeroPage => match address_low_byte {
-                        None => {
-                            let operand = self.read_and_inc_program_counter(device);
-
-                            match opcode.operation.instruction_type() {
-                                InstructionType::Write => {
-                                    let address = operand as u16;
-                                    let value = Some(device.read_byte(address));
-
-                                    opcode.execute(self, device, value, Some(address))
-                                }
-                                _ => State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte: Some(operand),
-                                    address_high_byte: None,
-                                    pointer: None,
-                                    indirect_address_low_byte: None,
-                                    indirect_address_high_byte: None,
-                                    checked_page_boundary: false,
-                                }),
-                            }
-                        }
-                        Some(low_byte) => {
-                            let address = low_byte as u16;
-                            let value = Some(device.read_byte(address));
+                step: next_step as u8,
+            })
+        } else {
+            // Instructions poll for interrupts on their last cycle
+            self.poll_interrupts();
+            State::Cpu(CpuState::FetchOpcode)
+        }
+    }
 
-                            opcode.execute(self, device, value, Some(address))
-                        }
-                    },
-                    AddressingMode::ZeroPageXIndexed => match (address_low_byte, address_high_byte)
-                    {
-                        (None, _) => {
-                            // Cycle 2 - Read the zero page low byte
-                            State::Cpu(CpuState::ReadingOperand {
-                                opcode,
-                                address_low_byte: Some(self.read_and_inc_program_counter(device)),
-                                address_high_byte: None,
-                                pointer: None,
-                                indirect_address_low_byte: None,
-                                indirect_address_high_byte: None,
-                                checked_page_boundary: false,
-                            })
-                        }
-                        (Some(low_byte), None) => {
-                            // Cycle 3 - Dummy read of the unindexed address
-                            let _ = device.read_byte(low_byte as u16);
-
-                            match opcode.operation.instruction_type() {
-                                InstructionType::Write => {
-                                    let address = low_byte.wrapping_add(self.registers.x) as u16;
-                                    let value = Some(device.read_byte(address));
-
-                                    opcode.execute(self, device, value, Some(address))
-                                }
-                                _ => State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte: Some(0x0),
-                                    pointer: None,
-                                    indirect_address_low_byte: None,
-                                    indirect_address_high_byte: None,
-                                    checked_page_boundary: false,
-                                }),
-                            }
-                        }
-                        (Some(low_byte), Some(_)) => {
-                            // Cycle 4 - Read operand from the indexed zero page address
-                            let address = low_byte.wrapping_add(self.registers.x) as u16;
-                            let value = Some(device.read_byte(address));
+    ///
+    /// WAI stops the clock until an interrupt line is asserted. An IRQ while
+    /// interrupts are disabled still ends the wait, execution then carries on
+    /// after the WAI without servicing it.
+    ///
+    fn step_waiting(&mut self) -> State {
+        if self.interrupt_lines.sampled.is_some() {
+            self.poll_interrupts();
+            State::Cpu(CpuState::FetchOpcode)
+        } else if self.interrupt_lines.irq {
+            State::Cpu(CpuState::FetchOpcode)
+        } else {
+            State::Waiting
+        }
+    }
+}
 
-                            opcode.execute(self, device, value, Some(address))
-                        }
-                    },
-                    AddressingMode::ZeroPageYIndexed => match (address_low_byte, address_high_byte)
-                    {
-                        (None, _) => {
-                            // Cycle 2 - Read the zero page low byte
-                            State::Cpu(CpuState::ReadingOperand {
-                                opcode,
-                                address_low_byte: Some(self.read_and_inc_program_counter(device)),
-                                address_high_byte: None,
-                                pointer: None,
-                                indirect_address_low_byte: None,
-                                indirect_address_high_byte: None,
-                                checked_page_boundary: false,
-                            })
-                        }
-                        (Some(low_byte), None) => {
-                            // Cycle 3 - Dummy read of the unindexed address
-                            let _ = device.read_byte(low_byte as u16);
-
-                            match opcode.operation.instruction_type() {
-                                InstructionType::Write => {
-                                    let address = low_byte.wrapping_add(self.registers.y) as u16;
-                                    let _ = Some(device.read_byte(address));
-
-                                    opcode.execute(self, device, None, Some(address))
-                                }
-                                _ => State::Cpu(CpuState::ReadingOperand {
-                                    opcode,
-                                    address_low_byte,
-                                    address_high_byte: Some(0x0),
-                                    pointer: None,
-                                    indirect_address_low_byte: None,
-                                    indirect_address_high_byte: None,
-                                    checked_page_boundary: false,
-                                }),
-                            }
-                        }
-                        (Some(low_byte), Some(_)) => {
-                            // Cycle 4 - Read operand from the indexed zero page address
-                            let address = low_byte.wrapping_add(self.registers.y) as u16;
-                            let value = Some(device.read_byte(address));
+impl Cpu {
+    pub(crate) fn new<B: Bus>(initial_cycles: u32, bus: &mut B) -> Cpu {
+        Cpu::new_with_variant(CpuVariant::Nmos6502, initial_cycles, bus)
+    }
 
-                            opcode.execute(self, device, value, Some(address))
-                        }
-                    },
-                    _ => panic!(
-                        "Invalid, can't read operand for addressing mode {:?}",
-                        opcode.address_mode
-                    ),
-                }
-            }
-            CpuState::ThrowawayRead { opcode, operand } => {
-                // BRK does a throwaway read but does increment the PC
-                // Normal implied operations do a throwaway the read and don't increment the PC
-                if opcode.operation == Operation::BRK {
-                    self.read_and_inc_program_counter(device);
-                } else {
-                    device.read_byte(self.registers.program_counter);
-                }
+    pub(crate) fn new_with_variant<B: Bus>(
+        variant: CpuVariant,
+        initial_cycles: u32,
+        bus: &mut B,
+    ) -> Cpu {
+        let mut bus = AddressLines {
+            bus,
+            mask: variant.address_mask(),
+        };
 
-                opcode.execute(self, device, operand, None)
-            }
-            CpuState::PushRegisterOnStack { value } => {
-                self.push_to_stack(device, value);
+        // The processor starts at the RESET interrupt handler address
+        let pc = bus.read_byte(Interrupt::RESET(0).offset()) as u16
+            | ((bus.read_byte(Interrupt::RESET(0).offset().wrapping_add(1)) as u16) << 8);
+
+        Cpu {
+            state: State::Cpu(CpuState::FetchOpcode),
+            registers: Registers::new(pc),
+            cycles: initial_cycles,
+            cpu_cycle_counter: 1,
+            polled_interrupt: None,
+            interrupt_lines: InterruptLines::default(),
+            magic_constant: DEFAULT_MAGIC_CONSTANT,
+            variant,
+            latches: Latches::default(),
+        }
+    }
 
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            CpuState::PreIncrementStackPointer { operation } => match operation {
-                Operation::PLA | Operation::PLP | Operation::RTI => {
-                    State::Cpu(CpuState::PullRegisterFromStack { operation })
-                }
-                Operation::RTS => State::Cpu(CpuState::PullPCLFromStack { operation }),
-                _ => panic!(
-                    "Attempt to access stack from invalid instruction {:?}",
-                    operation
-                ),
-            },
-            CpuState::PullRegisterFromStack { operation } => match operation {
-                Operation::PLA => {
-                    device.poll_for_interrupts(true);
-                    self.registers.a = self.pop_from_stack(device);
-                    self.set_negative_zero_flags(self.registers.a);
-                    State::Cpu(CpuState::FetchOpcode)
-                }
-                Operation::PLP => {
-                    device.poll_for_interrupts(true);
-                    self.registers.status_register =
-                        StatusFlags::from_bits_truncate(self.pop_from_stack(device) & 0b1100_1111);
+    pub(crate) fn variant(&self) -> CpuVariant {
+        self.variant
+    }
 
-                    State::Cpu(CpuState::FetchOpcode)
-                }
-                Operation::RTI => {
-                    self.registers.status_register =
-                        StatusFlags::from_bits_truncate(self.pop_from_stack(device) & 0b1100_1111);
+    /// Move the cpu on by a single CPU clock cycle
+    pub(crate) fn clock<B: Bus>(&mut self, bus: &mut B) {
+        let mut bus = AddressLines {
+            bus,
+            mask: self.variant.address_mask(),
+        };
 
-                    State::Cpu(CpuState::PullPCLFromStack { operation })
-                }
-                _ => panic!(
-                    "Attempt to access stack from invalid instruction {:?}",
-                    operation
-                ),
-            },
-            CpuState::PullPCLFromStack { operation } => State::Cpu(CpuState::PullPCHFromStack {
-                operation,
-                pcl: self.pop_from_stack(device),
-            }),
-            CpuState::PullPCHFromStack { operation, pcl } => {
-                let pch = self.pop_from_stack(device);
-                self.registers.program_counter = ((pch as u16) << 8) | pcl as u16;
-
-                match operation {
-                    Operation::RTS => State::Cpu(CpuState::IncrementProgramCounter),
-                    Operation::RTI => {
-                        device.poll_for_interrupts(true);
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => panic!(
-                        "Attempt to access stack from invalid instruction {:?}",
-                        operation
-                    ),
-                }
-            }
-            CpuState::IncrementProgramCounter => {
-                device.poll_for_interrupts(true);
-                self.registers.program_counter = self.registers.program_counter.wrapping_add(1);
+        self.step(&mut bus);
+    }
 
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            CpuState::WritePCHToStack { address } => {
-                self.push_to_stack(
-                    device,
-                    (self.registers.program_counter.wrapping_sub(1) >> 8) as u8,
-                );
+    ///
+    /// Run the next instruction, along with any interrupt sequence which
+    /// follows it, and return how many cycles that took including any page
+    /// crossing or branch penalties.
+    ///
+    /// The opcode is decoded once and its micro-ops run back to back, rather
+    /// than going back through the state machine on every cycle as `clock`
+    /// does. Every bus access is still made, as reads of I/O registers can
+    /// have side effects, so the bus sees exactly what it would from `clock`.
+    ///
+    /// Part way through an instruction or interrupt sequence the rest of it
+    /// is stepped through a cycle at a time. A jammed or waiting cpu never
+    /// reaches the end of an instruction so only moves on by a single cycle.
+    ///
+    pub(crate) fn execute_instruction<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        let mut bus = AddressLines {
+            bus,
+            mask: self.variant.address_mask(),
+        };
+
+        let start = self.cycles;
+        if self.at_instruction_boundary() {
+            self.run_opcode(&mut bus);
+        } else {
+            self.step(&mut bus);
+        }
+
+        // Finish off a decimal adjust cycle or an interrupt taken after the instruction
+        while !self.between_instructions() {
+            self.step(&mut bus);
+        }
+
+        self.cycles.wrapping_sub(start)
+    }
 
-                State::Cpu(CpuState::WritePCLToStack { address })
+    /// Whether the cpu is about to fetch an opcode, or has stopped and never will
+    fn between_instructions(&self) -> bool {
+        matches!(self.state, State::Cpu(CpuState::FetchOpcode) | State::Jammed | State::Waiting)
+    }
+
+    /// Run a whole instruction from its opcode fetch, the cycles `step_cpu` would run one by one
+    fn run_opcode<B: Bus>(&mut self, bus: &mut B) {
+        let opcode = self.fetch_opcode(bus);
+
+        info!("Opcode: {:?} at cycle {}", opcode, self.cycles);
+
+        let micro_ops = self.variant.micro_op_table()[opcode.opcode as usize];
+        let mut step = 0;
+        self.state = loop {
+            if step >= micro_ops.len() {
+                // Instructions poll for interrupts on their last cycle
+                self.poll_interrupts();
+                break State::Cpu(CpuState::FetchOpcode);
             }
-            CpuState::WritePCLToStack { address } => {
-                self.push_to_stack(
-                    device,
-                    (self.registers.program_counter.wrapping_sub(1) & 0xFF) as u8,
-                );
 
-                State::Cpu(CpuState::SetProgramCounter {
-                    address,
-                    was_branch_instruction: false,
-                })
+            self.end_cycle();
+            step = match self.run_micro_op(bus, opcode, micro_ops[step]) {
+                Step::Next => step + 1,
+                Step::Skip => step + 2,
+                Step::Done => usize::MAX,
+                Step::DoneWithoutPoll => break State::Cpu(CpuState::FetchOpcode),
+                Step::Goto(state) => break state,
+            };
+        };
+
+        self.take_polled_interrupt();
+        self.end_cycle();
+    }
+
+    fn step<B: Bus>(&mut self, bus: &mut B) {
+        self.state = match self.state {
+            State::Cpu(state) => self.step_cpu(bus, state),
+            State::Interrupt(state) => self.step_interrupt_handler(bus, state),
+            State::Jammed => self.step_jammed(bus),
+            State::Waiting => self.step_waiting(),
+        };
+
+        self.take_polled_interrupt();
+        self.end_cycle();
+    }
+
+    /// Start the interrupt sequence for an interrupt polled by the instruction which just finished
+    fn take_polled_interrupt(&mut self) {
+        if let State::Cpu(CpuState::FetchOpcode) = self.state {
+            if let Some(interrupt) = self.polled_interrupt {
+                self.polled_interrupt = None;
+
+                self.state = State::Interrupt(InterruptState::InternalOps1(interrupt));
             }
-            CpuState::SetProgramCounter {
-                address,
-                was_branch_instruction,
-            } => {
-                device.poll_for_interrupts(true);
-                self.registers.program_counter = address;
+        }
+    }
 
-                State::Cpu(CpuState::FetchOpcode)
+    /// Sample the interrupt lines at the end of the cycle, then move on to the next one
+    fn end_cycle(&mut self) {
+        let interrupts_disabled = self
+            .registers
+            .status_register
+            .contains(StatusFlags::INTERRUPT_DISABLE_FLAG);
+        self.interrupt_lines.sample(interrupts_disabled, self.cycles);
+
+        self.cycles += 1;
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.polled_interrupt = None;
+        self.interrupt_lines.nmi_pending = false;
+        self.state = State::Interrupt(InterruptState::InternalOps1(Interrupt::RESET(self.cycles)));
+    }
+
+    pub(crate) fn is_jammed(&self) -> bool {
+        matches!(self.state, State::Jammed)
+    }
+
+    ///
+    /// Interrupts are taken after each instruction for as long as IRQ is
+    /// asserted and I is clear. Ignored by the 6507, which has no IRQ pin.
+    ///
+    pub(crate) fn set_irq_line(&mut self, asserted: bool) {
+        self.interrupt_lines.irq = asserted && self.variant.has_interrupt_pins();
+    }
+
+    ///
+    /// An interrupt is taken each time NMI goes from released to asserted,
+    /// regardless of I. Ignored by the 6507, which has no NMI pin.
+    ///
+    pub(crate) fn set_nmi_line(&mut self, asserted: bool) {
+        self.interrupt_lines.nmi = asserted && self.variant.has_interrupt_pins();
+    }
+
+    /// Whether the next cycle will fetch an opcode, i.e. no instruction or interrupt is part way through
+    pub(crate) fn at_instruction_boundary(&self) -> bool {
+        matches!(self.state, State::Cpu(CpuState::FetchOpcode))
+    }
+
+    ///
+    /// Whether the next cycle writes to the bus. RDY only halts the cpu on
+    /// read cycles, so a write still goes ahead while it's held low.
+    ///
+    pub(crate) fn next_cycle_is_write(&self) -> bool {
+        match self.state {
+            State::Cpu(CpuState::Executing { opcode, step }) => {
+                self.variant.micro_op_table()[opcode.opcode as usize][step as usize].is_write()
             }
-            CpuState::BranchCrossesPageBoundary {
-                opcode,
-                operand,
-                address,
-            } => opcode.execute(self, device, operand, address),
-            CpuState::WritingResult {
-                value,
-                address,
-                dummy: true,
-            } => State::Cpu(CpuState::WritingResult {
-                value,
-                address,
-                dummy: false,
-            }),
-            CpuState::WritingResult {
-                value,
-                address,
-                dummy: false,
-            } => {
-                // Crucially this _must_ happen before the write_byte.
-                device.poll_for_interrupts(true);
-
-                device.write_byte(address, value);
+            // RESET reads the stack instead of pushing to it
+            State::Interrupt(
+                InterruptState::PushPCH(interrupt)
+                | InterruptState::PushPCL(interrupt)
+                | InterruptState::PushStatusRegister(interrupt),
+            ) => !matches!(interrupt, Interrupt::RESET(_)),
+            _ => false,
+        }
+    }
 
-                State::Cpu(CpuState::FetchOpcode)
+    /// Every register at once
+    pub fn registers(&self) -> RegisterSnapshot {
+        RegisterSnapshot::from(&self.registers)
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        match register {
+            Register::A => self.registers.a as u16,
+            Register::X => self.registers.x as u16,
+            Register::Y => self.registers.y as u16,
+            Register::StackPointer => self.registers.stack_pointer as u16,
+            Register::ProgramCounter => self.registers.program_counter,
+            Register::Status => self.registers.status_register.bits() as u16,
+        }
+    }
+
+    ///
+    /// Overwrite a register, 8 bit registers take the low byte of the value.
+    /// Changing the program counter part way through an instruction only
+    /// affects the remaining cycles of that instruction.
+    ///
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        match register {
+            Register::A => self.registers.a = value as u8,
+            Register::X => self.registers.x = value as u8,
+            Register::Y => self.registers.y = value as u8,
+            Register::StackPointer => self.registers.stack_pointer = value as u8,
+            Register::ProgramCounter => self.registers.program_counter = value,
+            Register::Status => {
+                self.registers.status_register = StatusFlags::from_bits_truncate(value as u8)
             }
         }
     }
+
+    ///
+    /// Jump to `address`, abandoning any instruction or interrupt sequence
+    /// part way through, so that the next cycle fetches the opcode there.
+    /// Unlike a RESET nothing else changes, including a jammed cpu's lock up.
+    ///
+    pub fn set_pc(&mut self, address: u16) {
+        self.registers.program_counter = address;
+        if !self.is_jammed() {
+            self.state = State::Cpu(CpuState::FetchOpcode);
+        }
+    }
+
+    pub fn flag(&self, flag: Flag) -> bool {
+        self.registers.status_register.contains(flag.status_flag())
+    }
+
+    pub fn set_flag(&mut self, flag: Flag, value: bool) {
+        self.registers.status_register.set(flag.status_flag(), value);
+    }
+}
+
+/// Whether an opcode is one of the undocumented NMOS opcodes
+pub(crate) fn is_illegal_opcode(opcode: u8) -> bool {
+    OPCODE_TABLE[opcode as usize].is_illegal
 }
 
 // CPU constructor because I couldn't work out how to get wasm-buildgen to behave with it in the impl
 #[wasm_bindgen]
 pub fn new_cpu(initial_cycles: u32, device: &Device) -> Cpu {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
+    init_logging();
+
+    Cpu::new(initial_cycles, &mut JsDevice(device))
+}
 
-    wasm_logger::init(wasm_logger::Config::default());
+/// Create a cpu which behaves as another member of the 6502 family, `new_cpu` creates an NMOS 6502
+#[wasm_bindgen]
+pub fn new_cpu_with_variant(variant: CpuVariant, initial_cycles: u32, device: &Device) -> Cpu {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    init_logging();
 
-    // The processor starts at the RESET interrupt handler address
-    let pc = device.read_byte(Interrupt::RESET(0).offset()) as u16
-        | ((device.read_byte(Interrupt::RESET(0).offset().wrapping_add(1)) as u16) << 8);
+    Cpu::new_with_variant(variant, initial_cycles, &mut JsDevice(device))
+}
 
-    Cpu {
-        state: State::Cpu(CpuState::FetchOpcode),
-        registers: Registers::new(pc),
-        cycles: initial_cycles,
-        cpu_cycle_counter: 1,
-        polled_interrupt: None,
-    }
+#[wasm_bindgen]
+pub fn cpu_variant(cpu: &Cpu) -> CpuVariant {
+    cpu.variant()
+}
+
+/// Set the "magic constant" ORed into A by the unstable XAA and LAX #imm opcodes.
+/// This varies between physical chips (and with temperature), $EE is the default.
+#[wasm_bindgen]
+pub fn set_magic_constant(cpu: &mut Cpu, value: u8) {
+    cpu.magic_constant = value;
+}
+
+/// Assert the RESET line. The cpu abandons whatever it was doing, runs the
+/// reset sequence and then continues from the address in the reset vector.
+/// This is the only way to recover a jammed cpu.
+#[wasm_bindgen]
+pub fn reset(cpu: &mut Cpu) {
+    cpu.reset();
+}
+
+/// Drive the (level sensitive) IRQ input, `true` asserts it. Ignored by the 6507, it has no IRQ pin
+#[wasm_bindgen]
+pub fn set_irq_line(cpu: &mut Cpu, asserted: bool) {
+    cpu.set_irq_line(asserted);
+}
+
+/// Drive the (edge triggered) NMI input, `true` asserts it. Ignored by the 6507, it has no NMI pin
+#[wasm_bindgen]
+pub fn set_nmi_line(cpu: &mut Cpu, asserted: bool) {
+    cpu.set_nmi_line(asserted);
+}
+
+/// Whether the cpu has executed a KIL opcode and is halted waiting for a reset
+#[wasm_bindgen]
+pub fn is_jammed(cpu: &Cpu) -> bool {
+    cpu.is_jammed()
 }
 
-/// Move the cpu on by a single CPU clock cycle
+/// Every register at once, for showing the cpu in a debugger
+#[wasm_bindgen]
+pub fn cpu_registers(cpu: &Cpu) -> RegisterSnapshot {
+    cpu.registers()
+}
+
+#[wasm_bindgen]
+pub fn cpu_register(cpu: &Cpu, register: Register) -> u16 {
+    cpu.register(register)
+}
+
+/// Change a register, only the low byte is used for 8 bit registers
+#[wasm_bindgen]
+pub fn set_register(cpu: &mut Cpu, register: Register, value: u16) {
+    cpu.set_register(register, value);
+}
+
+/// Jump to `address`, abandoning any instruction part way through so that it's fetched next
+#[wasm_bindgen]
+pub fn set_pc(cpu: &mut Cpu, address: u16) {
+    cpu.set_pc(address);
+}
+
+#[wasm_bindgen]
+pub fn cpu_flag(cpu: &Cpu, flag: Flag) -> bool {
+    cpu.flag(flag)
+}
+
+#[wasm_bindgen]
+pub fn set_flag(cpu: &mut Cpu, flag: Flag, value: bool) {
+    cpu.set_flag(flag, value);
+}
+
+/// Snapshot the cpu, including any partially executed instruction
+#[wasm_bindgen]
+pub fn save_cpu_state(cpu: &Cpu) -> Vec<u8> {
+    crate::save_state::save(cpu)
+}
+
+/// Restore a snapshot taken by `save_cpu_state`, the cpu is unchanged if it's rejected
+#[wasm_bindgen]
+pub fn load_cpu_state(cpu: &mut Cpu, state: &[u8]) -> Result<(), String> {
+    crate::save_state::load(cpu, state)
+}
+
+/// Move the cpu on by a single CPU clock cycle, using a JS object as the bus
 #[wasm_bindgen]
 pub fn clock(cpu: &mut Cpu, device: &Device) {
-    cpu.state = match cpu.state {
-        State::Cpu(state) => cpu.step_cpu(device, state),
-        State::Interrupt(state) => cpu.step_interrupt_handler(device, state),
-    };
+    cpu.clock(&mut JsDevice(device));
+}
 
-    if let State::Cpu(CpuState::FetchOpcode) = cpu.state {
-        if let Some(interrupt) = cpu.polled_interrupt {
-            cpu.polled_interrupt = None;
+/// Run the cpu to the end of the current instruction, returning the number of cycles taken
+#[wasm_bindgen]
+pub fn execute_instruction(cpu: &mut Cpu, device: &Device) -> u32 {
+    cpu.execute_instruction(&mut JsDevice(device))
+}
 
-            cpu.state = State::Interrupt(InterruptState::InternalOps1(interrupt));
+#[cfg(test)]
+impl Cpu {
+    fn new_for_test() -> Self {
+        Cpu {
+            state: State::Cpu(CpuState::FetchOpcode),
+            registers: Registers::new(0x0000),
+            cycles: 0,
+            cpu_cycle_counter: 1,
+            polled_interrupt: None,
+            interrupt_lines: InterruptLines::default(),
+            magic_constant: DEFAULT_MAGIC_CONSTANT,
+            variant: CpuVariant::Nmos6502,
+            latches: Latches::default(),
         }
     }
+}
+
+#[cfg(test)]
+mod decimal_mode_tests {
+    use super::{Cpu, StatusFlags};
+
+    fn cpu_in_decimal_mode(a: u8, carry: bool) -> Cpu {
+        let mut cpu = Cpu::new_for_test();
+        cpu.registers.a = a;
+        cpu.registers.status_register = StatusFlags::DECIMAL_FLAG;
+        cpu.registers
+            .status_register
+            .set(StatusFlags::CARRY_FLAG, carry);
+
+        cpu
+    }
 
-    cpu.cycles += 1;
-}
\ No newline at end of file
+    fn assert_flags(cpu: &Cpu, carry: bool, zero: bool, overflow: bool, negative: bool) {
+        let flags = &cpu.registers.status_register;
+        assert_eq!(flags.contains(StatusFlags::CARRY_FLAG), carry, "carry");
+        assert_eq!(flags.contains(StatusFlags::ZERO_FLAG), zero, "zero");
+        assert_eq!(
+            flags.contains(StatusFlags::OVERFLOW_FLAG),
+            overflow,
+            "overflow"
+        );
+        assert_eq!(
+            flags.contains(StatusFlags::NEGATIVE_FLAG),
+            negative,
+            "negative"
+        );
+    }
+
+    #[test]
+    fn test_adc_decimal_results() {
+        // (a, operand, carry in, result, carry out)
+        let vectors = [
+            (0x00, 0x01, false, 0x01, false),
+            (0x12, 0x34, false, 0x46, false),
+            (0x15, 0x26, false, 0x41, false),
+            (0x58, 0x46, true, 0x05, true),
+            (0x81, 0x92, false, 0x73, true),
+            (0x99, 0x99, true, 0x99, true),
+        ];
+
+        for (a, operand, carry, result, carry_out) in vectors {
+            let mut cpu = cpu_in_decimal_mode(a, carry);
+            cpu.adc(operand);
+            assert_eq!(
+                cpu.registers.a, result,
+                "{:02X} + {:02X} + {}",
+                a, operand, carry
+            );
+            assert_eq!(
+                cpu.registers
+                    .status_register
+                    .contains(StatusFlags::CARRY_FLAG),
+                carry_out
+            );
+        }
+    }
+
+    #[test]
+    fn test_adc_decimal_zero_flag_uses_binary_result() {
+        // 99 + 01 = 00 in BCD but the binary sum is $9A so Z is clear
+        let mut cpu = cpu_in_decimal_mode(0x99, false);
+        cpu.adc(0x01);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_flags(&cpu, true, false, false, true);
+    }
+
+    #[test]
+    fn test_adc_decimal_negative_and_overflow_flags() {
+        // 79 + 00 + 1 = 80, the intermediate $80 sets both N and V
+        let mut cpu = cpu_in_decimal_mode(0x79, true);
+        cpu.adc(0x00);
+        assert_eq!(cpu.registers.a, 0x80);
+        assert_flags(&cpu, false, false, true, true);
+
+        // 50 + 50 = 00 with carry, the intermediate $A0 sets N and V
+        let mut cpu = cpu_in_decimal_mode(0x50, false);
+        cpu.adc(0x50);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_flags(&cpu, true, false, true, true);
+    }
+
+    #[test]
+    fn test_adc_decimal_invalid_bcd() {
+        // Non-BCD digits are still "adjusted" the way the NMOS ALU does it
+        let mut cpu = cpu_in_decimal_mode(0x0F, false);
+        cpu.adc(0x0F);
+        assert_eq!(cpu.registers.a, 0x14);
+
+        let mut cpu = cpu_in_decimal_mode(0xFF, false);
+        cpu.adc(0xFF);
+        assert_eq!(cpu.registers.a, 0x54);
+        assert!(cpu
+            .registers
+            .status_register
+            .contains(StatusFlags::CARRY_FLAG));
+    }
+
+    #[test]
+    fn test_sbc_decimal_results() {
+        // (a, operand, carry in, result, carry out)
+        let vectors = [
+            (0x46, 0x12, true, 0x34, true),
+            (0x40, 0x13, true, 0x27, true),
+            (0x32, 0x02, false, 0x29, true),
+            (0x12, 0x21, true, 0x91, false),
+            (0x21, 0x34, true, 0x87, false),
+            (0x00, 0x01, true, 0x99, false),
+        ];
+
+        for (a, operand, carry, result, carry_out) in vectors {
+            let mut cpu = cpu_in_decimal_mode(a, carry);
+            cpu.sbc(operand);
+            assert_eq!(
+                cpu.registers.a, result,
+                "{:02X} - {:02X} - {}",
+                a, operand, !carry
+            );
+            assert_eq!(
+                cpu.registers
+                    .status_register
+                    .contains(StatusFlags::CARRY_FLAG),
+                carry_out
+            );
+        }
+    }
+
+    #[test]
+    fn test_sbc_decimal_flags_match_binary() {
+        // 00 - 01 = 99 in BCD, flags are those of $00 - $01 = $FF
+        let mut cpu = cpu_in_decimal_mode(0x00, true);
+        cpu.sbc(0x01);
+        assert_flags(&cpu, false, false, false, true);
+
+        // 80 - 01 = 79, the binary subtraction overflows
+        let mut cpu = cpu_in_decimal_mode(0x80, true);
+        cpu.sbc(0x01);
+        assert_eq!(cpu.registers.a, 0x79);
+        assert_flags(&cpu, true, false, true, false);
+
+        // 21 - 21 = 00 sets Z
+        let mut cpu = cpu_in_decimal_mode(0x21, true);
+        cpu.sbc(0x21);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_flags(&cpu, true, true, false, false);
+    }
+
+    #[test]
+    fn test_binary_mode_unaffected() {
+        let mut cpu = cpu_in_decimal_mode(0x09, false);
+        cpu.registers
+            .status_register
+            .remove(StatusFlags::DECIMAL_FLAG);
+        cpu.adc(0x01);
+        assert_eq!(cpu.registers.a, 0x0A);
+        cpu.sbc(0x01);
+        assert_eq!(cpu.registers.a, 0x08);
+    }
+}
+
+#[cfg(test)]
+mod illegal_opcode_tests {
+    use super::test_bus::{cpu_with_program, step, TestBus};
+    use super::{Cpu, CpuVariant, StatusFlags};
+    use crate::bus::BusAccessKind::{self, *};
+
+    fn flags(cpu: &Cpu) -> u8 {
+        cpu.registers.status_register.bits()
+    }
+
+    ///
+    /// Run the first instruction of `program` with A, X, Y & S set, returning
+    /// its bus accesses. Zero page $40 & $42 point to $1210 & $12F0.
+    ///
+    fn run(program: &[u8], [a, x, y, s]: [u8; 4]) -> (Cpu, TestBus, Vec<(u16, u8, BusAccessKind)>) {
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Nmos6502, program);
+        bus.ram[0x40..0x44].copy_from_slice(&[0x10, 0x12, 0xF0, 0x12]);
+        cpu.registers.a = a;
+        cpu.registers.x = x;
+        cpu.registers.y = y;
+        cpu.registers.stack_pointer = s;
+        bus.accesses.clear();
+        step(&mut cpu, &mut bus);
+
+        let accesses = bus
+            .accesses
+            .iter()
+            .map(|access| (access.address, access.value, access.kind))
+            .collect();
+        (cpu, bus, accesses)
+    }
+
+    #[test]
+    fn test_arr_binary() {
+        let mut cpu = Cpu::new_for_test();
+        cpu.registers.a = 0xFF;
+        cpu.registers.status_register = StatusFlags::CARRY_FLAG;
+        cpu.arr(0xC0);
+        assert_eq!(cpu.registers.a, 0xE0);
+        assert_eq!(
+            flags(&cpu),
+            (StatusFlags::CARRY_FLAG | StatusFlags::NEGATIVE_FLAG).bits()
+        );
+
+        cpu.registers.a = 0xFF;
+        cpu.registers.status_register = StatusFlags::empty();
+        cpu.arr(0x40);
+        assert_eq!(cpu.registers.a, 0x20);
+        assert_eq!(flags(&cpu), StatusFlags::OVERFLOW_FLAG.bits());
+    }
+
+    #[test]
+    fn test_arr_decimal() {
+        let mut cpu = Cpu::new_for_test();
+        cpu.registers.a = 0xFF;
+        cpu.registers.status_register = StatusFlags::DECIMAL_FLAG;
+        cpu.arr(0xFF);
+        // $7F, the low nibble is corrected to $75 and then the high nibble to $D5
+        assert_eq!(cpu.registers.a, 0xD5);
+        assert_eq!(
+            flags(&cpu),
+            (StatusFlags::DECIMAL_FLAG | StatusFlags::CARRY_FLAG).bits()
+        );
+    }
+
+    #[test]
+    fn test_unstable_store_without_page_cross() {
+        let mut cpu = Cpu::new_for_test();
+        cpu.registers.x = 0xFF;
+        assert_eq!(cpu.unstable_store(cpu.registers.x, 0x1210, 0x10), (0x1210, 0x13));
+    }
+
+    #[test]
+    fn test_unstable_store_with_page_cross() {
+        let cpu = Cpu::new_for_test();
+        // Base address $12F0 indexed by $20 crosses into page $13
+        assert_eq!(cpu.unstable_store(0xFF, 0x1310, 0x20), (0x1310, 0x13));
+        assert_eq!(cpu.unstable_store(0x0E, 0x1310, 0x20), (0x0210, 0x02));
+    }
+
+    #[test]
+    fn test_unstable_stores_write_cycle() {
+        // (name, program, [A, X, Y, S]), the value stored is ANDed with $12 + 1
+        let not_crossing: [(&str, &[u8], [u8; 4]); 5] = [
+            ("SHY $1210,X", &[0x9C, 0x10, 0x12], [0x00, 0x10, 0xFF, 0xFD]),
+            ("SHX $1210,Y", &[0x9E, 0x10, 0x12], [0x00, 0xFF, 0x10, 0xFD]),
+            ("AHX $1210,Y", &[0x9F, 0x10, 0x12], [0xF7, 0x3F, 0x10, 0xFD]),
+            ("AHX ($40),Y", &[0x93, 0x40], [0xF7, 0x3F, 0x10, 0xFD]),
+            ("TAS $1210,Y", &[0x9B, 0x10, 0x12], [0xF7, 0x3F, 0x10, 0xFD]),
+        ];
+        // Crossing into page $13 also drives the value onto the high byte of the address
+        let crossing: [(&str, &[u8], [u8; 4]); 5] = [
+            ("SHY $12F0,X", &[0x9C, 0xF0, 0x12], [0x00, 0x20, 0x0E, 0xFD]),
+            ("SHX $12F0,Y", &[0x9E, 0xF0, 0x12], [0x00, 0x0E, 0x20, 0xFD]),
+            ("AHX $12F0,Y", &[0x9F, 0xF0, 0x12], [0x0F, 0xFE, 0x20, 0xFD]),
+            ("AHX ($42),Y", &[0x93, 0x42], [0x0F, 0xFE, 0x20, 0xFD]),
+            ("TAS $12F0,Y", &[0x9B, 0xF0, 0x12], [0x0F, 0xFE, 0x20, 0xFD]),
+        ];
+
+        let expected_writes = [(not_crossing, (0x1220, 0x13)), (crossing, (0x0210, 0x02))];
+        for (cases, (address, value)) in expected_writes {
+            for (name, program, registers) in cases {
+                let (_, bus, accesses) = run(program, registers);
+
+                // The write is the instruction's last cycle and its only write
+                let writes: Vec<_> = accesses.iter().filter(|access| access.2.is_write()).collect();
+                assert_eq!(writes, vec![&(address, value, Write)], "{}", name);
+                assert_eq!(accesses.last(), Some(&(address, value, Write)), "{}", name);
+                assert_eq!(bus.ram[address as usize], value, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_xaa_bus_activity() {
+        // XAA #$5F, A is ORed with the magic constant $EE before being ANDed with X & the operand
+        let (cpu, _, accesses) = run(&[0x8B, 0x5F], [0x01, 0xF3, 0x00, 0xFD]);
+
+        assert_eq!(
+            accesses,
+            vec![(0x0400, 0x8B, OpcodeFetch), (0x0401, 0x5F, OperandRead)]
+        );
+        assert_eq!(cpu.registers.a, 0x43);
+    }
+
+    #[test]
+    fn test_las_bus_activity() {
+        // LAS $1210,Y, the value read is ANDed with S and loaded into A, X & S
+        let (cpu, _, accesses) = run(&[0xBB, 0x10, 0x12], [0x00, 0x00, 0x10, 0x7E]);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0xBB, OpcodeFetch),
+                (0x0401, 0x10, OperandRead),
+                (0x0402, 0x12, OperandRead),
+                (0x1220, 0xEA, OperandRead),
+            ]
+        );
+        assert_eq!(
+            (cpu.registers.a, cpu.registers.x, cpu.registers.stack_pointer),
+            (0x6A, 0x6A, 0x6A)
+        );
+
+        // LAS $12F0,Y crossing a page reads the uncorrected address first
+        let (cpu, _, accesses) = run(&[0xBB, 0xF0, 0x12], [0x00, 0x00, 0x20, 0x7E]);
+
+        assert_eq!(
+            accesses,
+            vec![
+                (0x0400, 0xBB, OpcodeFetch),
+                (0x0401, 0xF0, OperandRead),
+                (0x0402, 0x12, OperandRead),
+                (0x1210, 0xEA, DummyRead),
+                (0x1310, 0xEA, OperandRead),
+            ]
+        );
+        assert_eq!(
+            (cpu.registers.a, cpu.registers.x, cpu.registers.stack_pointer),
+            (0x6A, 0x6A, 0x6A)
+        );
+        // No write is made, even to the stack
+        assert!(accesses.iter().all(|&(_, _, kind)| !kind.is_write()));
+    }
+}
+
+#[cfg(test)]
+mod jammed_tests {
+    use super::test_bus::{self, TestBus};
+    use super::{is_jammed, reset, Cpu, CpuVariant};
+    use crate::bus::BusAccessKind;
+
+    fn clock(cpu: &mut Cpu, bus: &mut TestBus, cycles: u32) {
+        for _ in 0..cycles {
+            cpu.clock(bus);
+        }
+    }
+
+    #[test]
+    fn test_kil_jams_until_reset() {
+        let (mut cpu, mut bus) = test_bus::cpu_with_program(CpuVariant::Nmos6502, &[0x02]);
+        assert!(!is_jammed(&cpu));
+
+        clock(&mut cpu, &mut bus, 2);
+        assert!(is_jammed(&cpu));
+
+        // Once jammed the cpu keeps reading $FFFF, and counting cycles, until it's reset
+        clock(&mut cpu, &mut bus, 6);
+        assert!(is_jammed(&cpu));
+        assert_eq!(cpu.cycles, 8);
+        let accesses: Vec<_> = bus
+            .accesses
+            .iter()
+            .map(|access| (access.cycle, access.address, access.kind))
+            .collect();
+        let mut expected = vec![
+            (0, 0x0400, BusAccessKind::OpcodeFetch),
+            (1, 0x0401, BusAccessKind::DummyRead),
+        ];
+        expected.extend((2..8).map(|cycle| (cycle, 0xFFFF, BusAccessKind::DummyRead)));
+        assert_eq!(accesses, expected);
+
+        reset(&mut cpu);
+        assert!(!is_jammed(&cpu));
+        bus.accesses.clear();
+        while bus.accesses.last().map(|access| access.kind) != Some(BusAccessKind::OpcodeFetch) {
+            cpu.clock(&mut bus);
+        }
+        // The 7 cycle reset sequence, then the program is fetched again
+        assert_eq!(bus.accesses.len(), 8);
+        assert_eq!(bus.accesses.last().unwrap().address, 0x0400);
+        assert_eq!(cpu.cycles, 16);
+    }
+}
+
+#[cfg(test)]
+mod interrupt_tests {
+    use super::test_bus::{self, TestBus};
+    use super::{Cpu, CpuVariant, Flag, Register};
+
+    const IRQ_HANDLER: u16 = 0x0700;
+    const NMI_HANDLER: u16 = 0x0780;
+
+    /// The program runs from $0400, both interrupt handlers are NOP, RTI
+    fn cpu_with_program(program: &[u8]) -> (Cpu, TestBus) {
+        let (cpu, mut bus) = test_bus::cpu_with_program(CpuVariant::Nmos6502, program);
+        bus.ram[0xFFFE..=0xFFFF].copy_from_slice(&IRQ_HANDLER.to_le_bytes());
+        bus.ram[0xFFFA..=0xFFFB].copy_from_slice(&NMI_HANDLER.to_le_bytes());
+        for handler in [IRQ_HANDLER, NMI_HANDLER] {
+            bus.ram[handler as usize + 1] = 0x40;
+        }
+
+        (cpu, bus)
+    }
+
+    /// Run the next instruction, along with any interrupt taken after it, returning the new PC
+    fn step(cpu: &mut Cpu, bus: &mut TestBus) -> u16 {
+        test_bus::step(cpu, bus);
+        cpu.register(Register::ProgramCounter)
+    }
+
+    fn clock(cpu: &mut Cpu, bus: &mut TestBus, cycles: u32) {
+        for _ in 0..cycles {
+            cpu.clock(bus);
+        }
+    }
+
+    /// The status register pushed by the last interrupt
+    fn pushed_status(cpu: &Cpu, bus: &TestBus) -> u8 {
+        bus.ram[0x100 + cpu.register(Register::StackPointer) as usize + 1]
+    }
+
+    #[test]
+    fn test_irq_is_level_sensitive_and_masked() {
+        let (mut cpu, mut bus) = cpu_with_program(&[0xEA, 0xEA, 0xEA]);
+        cpu.set_irq_line(true);
+
+        // I is set from reset
+        assert_eq!(step(&mut cpu, &mut bus), 0x0401);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(pushed_status(&cpu, &bus), 0b0010_0000);
+        assert!(cpu.flag(Flag::InterruptDisable));
+
+        // RTI clears I again and the line is still asserted, so the IRQ is taken again immediately
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER + 1);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+
+        cpu.set_irq_line(false);
+        step(&mut cpu, &mut bus);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0402);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0403);
+    }
+
+    #[test]
+    fn test_irq_polled_on_second_to_last_cycle() {
+        let (mut cpu, mut bus) = cpu_with_program(&[0xEA, 0xEA, 0xEA]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+
+        // Asserted during the first cycle of the 2 cycle NOP, it's seen before the last cycle
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(bus.ram[0x1FC], 0x01);
+
+        // Asserted during the last cycle, it's too late and the next instruction runs first
+        let (mut cpu, mut bus) = cpu_with_program(&[0xEA, 0xEA, 0xEA]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        clock(&mut cpu, &mut bus, 1);
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0401);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(bus.ram[0x1FC], 0x02);
+    }
+
+    #[test]
+    fn test_cli_sei_and_plp_take_effect_after_the_next_instruction() {
+        // CLI, NOP
+        let (mut cpu, mut bus) = cpu_with_program(&[0x58, 0xEA]);
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0401);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+
+        // SEI, the IRQ is still taken straight after it with I set in the pushed status
+        let (mut cpu, mut bus) = cpu_with_program(&[0x78, 0xEA]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(pushed_status(&cpu, &bus), 0b0010_0100);
+
+        // PLP clearing I
+        let (mut cpu, mut bus) = cpu_with_program(&[0x28, 0xEA]);
+        bus.ram[0x1FE] = 0x00;
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0401);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+    }
+
+    #[test]
+    fn test_taken_branch_delays_interrupts() {
+        // BNE to the next instruction, taken (Z is clear) without crossing a page
+        let (mut cpu, mut bus) = cpu_with_program(&[0xD0, 0x00, 0xEA]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        clock(&mut cpu, &mut bus, 1);
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0402);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+
+        // A page crossing branch polls again on its last cycle
+        let (mut cpu, mut bus) = cpu_with_program(&[0x4C, 0xF0, 0x04]);
+        bus.ram[0x4F0..0x4F2].copy_from_slice(&[0xD0, 0x20]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        assert_eq!(step(&mut cpu, &mut bus), 0x04F0);
+        clock(&mut cpu, &mut bus, 1);
+        cpu.set_irq_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(bus.ram[0x1FC], 0x12);
+    }
+
+    #[test]
+    fn test_nmi_is_edge_triggered() {
+        let (mut cpu, mut bus) = cpu_with_program(&[0xEA, 0xEA, 0xEA]);
+
+        // I doesn't mask NMI
+        cpu.set_nmi_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER + 1);
+
+        // Holding the line asserted doesn't trigger another
+        assert_eq!(step(&mut cpu, &mut bus), 0x0401);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0402);
+
+        cpu.set_nmi_line(false);
+        assert_eq!(step(&mut cpu, &mut bus), 0x0403);
+        cpu.set_nmi_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        // BRK, with an NMI arriving before the status register is pushed
+        let (mut cpu, mut bus) = cpu_with_program(&[0x00, 0x00]);
+        clock(&mut cpu, &mut bus, 3);
+        cpu.set_nmi_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER);
+        assert_eq!(pushed_status(&cpu, &bus) & 0b0001_0000, 0b0001_0000);
+
+        // The NMI has been serviced so isn't taken again
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER + 1);
+
+        // Too late to hijack, the BRK completes and the NMI follows the first handler instruction
+        let (mut cpu, mut bus) = cpu_with_program(&[0x00, 0x00]);
+        clock(&mut cpu, &mut bus, 5);
+        cpu.set_nmi_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), IRQ_HANDLER);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_irq() {
+        let (mut cpu, mut bus) = cpu_with_program(&[0xEA, 0xEA]);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        cpu.set_irq_line(true);
+        clock(&mut cpu, &mut bus, 4);
+        cpu.set_nmi_line(true);
+        assert_eq!(step(&mut cpu, &mut bus), NMI_HANDLER);
+        assert_eq!(pushed_status(&cpu, &bus) & 0b0001_0000, 0);
+    }
+}
+
+#[cfg(test)]
+mod variant_tests {
+    use super::test_bus::{self, step, TestBus};
+    use super::{Cpu, CpuVariant, Flag, Register};
+    use crate::bus::BusAccessKind;
+    use crate::save_state::{load, save};
+
+    /// The program runs from $0400 with BRK vectored to $0700, which holds a NOP
+    fn cpu_with_program(variant: CpuVariant, program: &[u8]) -> (Cpu, TestBus) {
+        let (cpu, mut bus) = test_bus::cpu_with_program(variant, program);
+        bus.ram[0xFFFE..=0xFFFF].copy_from_slice(&0x0700u16.to_le_bytes());
+
+        (cpu, bus)
+    }
+
+    fn run(cpu: &mut Cpu, bus: &mut TestBus, instructions: usize) {
+        for _ in 0..instructions {
+            step(cpu, bus);
+        }
+    }
+
+    #[test]
+    fn test_6507_has_13_address_lines() {
+        // LDA #$42, STA $2080, LDA $F080
+        let program = [0xA9, 0x42, 0x8D, 0x80, 0x20, 0xAD, 0x80, 0xF0];
+        let mut bus = TestBus::with_program(0, 0x1000, &program);
+        // The reset vector as seen through 13 address lines
+        bus.ram[0x1FFC..=0x1FFD].copy_from_slice(&0xF000u16.to_le_bytes());
+        bus.ram[0x1080] = 0x99;
+
+        let mut cpu = Cpu::new_with_variant(CpuVariant::Mos6507, 0, &mut bus);
+        // The cpu still has a 16 bit program counter, only the address lines are missing
+        assert_eq!(cpu.register(Register::ProgramCounter), 0xF000);
+
+        run(&mut cpu, &mut bus, 3);
+        assert_eq!(bus.ram[0x80], 0x42);
+        assert_eq!(bus.ram[0x2080], 0x00);
+        assert_eq!(cpu.register(Register::A), 0x99);
+    }
+
+    #[test]
+    fn test_6507_has_no_interrupt_pins() {
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Mos6507, &[]);
+        let start = cpu.register(Register::ProgramCounter);
+        cpu.set_flag(Flag::InterruptDisable, false);
+        cpu.set_irq_line(true);
+        cpu.set_nmi_line(true);
+
+        run(&mut cpu, &mut bus, 3);
+        assert_eq!(cpu.register(Register::ProgramCounter), start.wrapping_add(3));
+    }
+
+    #[test]
+    fn test_2a03_has_no_decimal_mode() {
+        // SED, CLC, LDA #$09, ADC #$01
+        let program = [0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01];
+        for (variant, result) in [(CpuVariant::Nmos6502, 0x10), (CpuVariant::Ricoh2A03, 0x0A)] {
+            let (mut cpu, mut bus) = cpu_with_program(variant, &program);
+            run(&mut cpu, &mut bus, 4);
+
+            assert_eq!(cpu.register(Register::A), result, "{:?}", variant);
+            assert!(cpu.flag(Flag::Decimal));
+        }
+    }
+
+    #[test]
+    fn test_jmp_indirect_page_wrap_fixed_on_65c02() {
+        // JMP ($02FF)
+        for (variant, target, cycles) in [
+            (CpuVariant::Nmos6502, 0x0500, 5),
+            (CpuVariant::Cmos65C02, 0x0600, 6),
+        ] {
+            let (mut cpu, mut bus) = cpu_with_program(variant, &[0x6C, 0xFF, 0x02]);
+            bus.ram[0x2FF] = 0x00;
+            bus.ram[0x200] = 0x05;
+            bus.ram[0x300] = 0x06;
+
+            assert_eq!(step(&mut cpu, &mut bus), cycles, "{:?}", variant);
+            assert_eq!(cpu.register(Register::ProgramCounter), target);
+        }
+    }
+
+    #[test]
+    fn test_65c02_opcodes() {
+        let program = [
+            0xA2, 0x12, // LDX #$12
+            0xDA, // PHX
+            0x7A, // PLY
+            0x64, 0x10, // STZ $10
+            0xA9, 0x0F, // LDA #$0F
+            0x04, 0x11, // TSB $11
+            0x14, 0x12, // TRB $12
+            0x1A, // INC A
+            0x07, 0x13, // RMB0 $13
+            0xF7, 0x14, // SMB7 $14
+            0xB2, 0x20, // LDA ($20)
+            0x80, 0x02, // BRA +2
+            0x00, 0x00, // Skipped
+            0x7C, 0x00, 0x05, // JMP ($0500,X)
+        ];
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Cmos65C02, &program);
+        bus.ram[0x10] = 0xFF;
+        bus.ram[0x11] = 0xF0;
+        bus.ram[0x12] = 0xFF;
+        bus.ram[0x13] = 0xFF;
+        bus.ram[0x14] = 0x00;
+        bus.ram[0x20..=0x21].copy_from_slice(&[0x00, 0x03]);
+        bus.ram[0x300] = 0x77;
+        bus.ram[0x512..=0x513].copy_from_slice(&[0x34, 0x06]);
+
+        run(&mut cpu, &mut bus, 6);
+        assert_eq!(cpu.register(Register::Y), 0x12);
+        assert_eq!(bus.ram[0x10], 0x00);
+        assert_eq!(bus.ram[0x11], 0xFF);
+        assert!(cpu.flag(Flag::Zero));
+
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!(bus.ram[0x12], 0xF0);
+        assert!(!cpu.flag(Flag::Zero));
+        assert_eq!(cpu.register(Register::A), 0x10);
+
+        run(&mut cpu, &mut bus, 3);
+        assert_eq!(bus.ram[0x13], 0xFE);
+        assert_eq!(bus.ram[0x14], 0x80);
+        assert_eq!(cpu.register(Register::A), 0x77);
+
+        assert_eq!(step(&mut cpu, &mut bus), 3);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0417);
+        assert_eq!(step(&mut cpu, &mut bus), 6);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0634);
+    }
+
+    #[test]
+    fn test_65c02_branch_on_bit() {
+        // BBR0 $13,+1, NOP, BBS0 $13,-3 (not taken)
+        let program = [0x0F, 0x13, 0x01, 0xEA, 0x8F, 0x13, 0xFD];
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Cmos65C02, &program);
+        bus.ram[0x13] = 0xFE;
+
+        assert_eq!(step(&mut cpu, &mut bus), 6);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0404);
+        assert_eq!(step(&mut cpu, &mut bus), 5);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0407);
+    }
+
+    #[test]
+    fn test_65c02_decimal_mode() {
+        // SED, CLC, LDA #$99, ADC #$01, BRK
+        let program = [0xF8, 0x18, 0xA9, 0x99, 0x69, 0x01, 0x00];
+        for (variant, zero, negative, adc_cycles, decimal_after_brk) in [
+            (CpuVariant::Nmos6502, false, true, 2, true),
+            (CpuVariant::Cmos65C02, true, false, 3, false),
+        ] {
+            let (mut cpu, mut bus) = cpu_with_program(variant, &program);
+            run(&mut cpu, &mut bus, 3);
+
+            // N & Z only reflect the BCD result on the 65C02, NMOS takes them from
+            // the binary sum ($9A) and the half adjusted result ($A0)
+            assert_eq!(step(&mut cpu, &mut bus), adc_cycles, "{:?}", variant);
+            assert_eq!(cpu.register(Register::A), 0x00);
+            assert!(cpu.flag(Flag::Carry));
+            assert_eq!(cpu.flag(Flag::Zero), zero, "{:?}", variant);
+            assert_eq!(cpu.flag(Flag::Negative), negative, "{:?}", variant);
+
+            step(&mut cpu, &mut bus);
+            assert_eq!(cpu.register(Register::ProgramCounter), 0x0700);
+            assert_eq!(cpu.flag(Flag::Decimal), decimal_after_brk, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn test_65c02_single_cycle_nops_and_wai() {
+        // NOP (undefined, 1 cycle), WAI, NOP
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Cmos65C02, &[0x03, 0xCB, 0xEA]);
+
+        assert_eq!(step(&mut cpu, &mut bus), 1);
+        for _ in 0..10 {
+            cpu.clock(&mut bus);
+        }
+        assert!(!cpu.at_instruction_boundary());
+
+        // With I set the IRQ only wakes the cpu, execution carries on after the WAI
+        cpu.set_irq_line(true);
+        cpu.clock(&mut bus);
+        assert!(cpu.at_instruction_boundary());
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0402);
+    }
+
+    #[test]
+    fn test_bus_access_kinds() {
+        use BusAccessKind::*;
+
+        // NOP, INC $1234
+        let program = [0xEA, 0xEE, 0x34, 0x12];
+        // The NMOS cpu writes the unmodified value back, the 65C02 reads it again
+        for (variant, rmw_kind) in [
+            (CpuVariant::Nmos6502, DummyWrite),
+            (CpuVariant::Cmos65C02, DummyRead),
+        ] {
+            let (mut cpu, mut bus) = cpu_with_program(variant, &program);
+            bus.ram[0x1234] = 0x41;
+            run(&mut cpu, &mut bus, 2);
+
+            let accesses: Vec<(u16, u8, BusAccessKind)> = bus
+                .accesses
+                .iter()
+                .map(|access| (access.address, access.value, access.kind))
+                .collect();
+            assert_eq!(
+                accesses,
+                vec![
+                    (0x0400, 0xEA, OpcodeFetch),
+                    (0x0401, 0xEE, DummyRead),
+                    (0x0401, 0xEE, OpcodeFetch),
+                    (0x0402, 0x34, OperandRead),
+                    (0x0403, 0x12, OperandRead),
+                    (0x1234, 0x41, OperandRead),
+                    (0x1234, 0x41, rmw_kind),
+                    (0x1234, 0x42, Write),
+                ],
+                "{:?}",
+                variant
+            );
+            // One access per cycle, so the dummy write lands on the cycle before the real one
+            for (cycle, access) in bus.accesses.iter().enumerate() {
+                assert_eq!(access.cycle, cycle as u32, "{:?}", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_state_rejects_other_variant() {
+        // Part way through the 65C02's ORA ($10), which is a single cycle KIL on the NMOS cpu
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Cmos65C02, &[0x12, 0x10]);
+        for _ in 0..3 {
+            cpu.clock(&mut bus);
+        }
+        let (mut other, _) = cpu_with_program(CpuVariant::Nmos6502, &[]);
+        let original = save(&other);
+
+        // The variant is checked before the state is decoded with the wrong opcode table
+        assert_eq!(
+            load(&mut other, &save(&cpu)),
+            Err("Save state is for a Cmos65C02 cpu, this is a Nmos6502".to_string())
+        );
+        assert_eq!(save(&other), original);
+    }
+}
+
+#[cfg(test)]
+mod execute_instruction_tests {
+    use super::test_bus::{cpu_with_program, TestBus};
+    use super::{Cpu, CpuVariant, Register};
+
+    /// Clock the cpu until it stops where `execute_instruction` would, returning the cycles taken
+    fn clock_instruction(cpu: &mut Cpu, bus: &mut TestBus) -> u32 {
+        let start = cpu.cycles;
+        loop {
+            cpu.clock(bus);
+
+            if cpu.between_instructions() {
+                return cpu.cycles - start;
+            }
+        }
+    }
+
+    #[test]
+    fn test_execute_instruction_cycle_counts() {
+        let program = [
+            0xA2, 0x01, // LDX #$01
+            0xBD, 0x00, 0x20, // LDA $2000,X
+            0xBD, 0xFF, 0x20, // LDA $20FF,X, crosses a page
+            0xD0, 0x00, // BNE +0, not taken as A is 0
+            0xF0, 0x00, // BEQ +0, taken
+            0xF0, 0xF0, // BEQ -16, taken to the previous page
+        ];
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Nmos6502, &program);
+        bus.ram[0x2001] = 0x00;
+        bus.ram[0x2100] = 0x00;
+
+        for cycles in [2, 4, 5, 2, 3, 4] {
+            assert_eq!(cpu.execute_instruction(&mut bus), cycles);
+        }
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x03FE);
+
+        // An interrupt taken after an instruction is run along with it
+        cpu.set_nmi_line(true);
+        assert_eq!(cpu.execute_instruction(&mut bus), 2 + 7);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0xEAEA);
+    }
+
+    #[test]
+    fn test_execute_instruction_only_moves_a_stopped_cpu_on_by_one_cycle() {
+        // KIL on the NMOS cpu, WAI on the 65C02
+        for (variant, opcode) in [(CpuVariant::Nmos6502, 0x02), (CpuVariant::Cmos65C02, 0xCB)] {
+            let (mut cpu, mut bus) = cpu_with_program(variant, &[opcode]);
+            cpu.execute_instruction(&mut bus);
+
+            assert_eq!(cpu.execute_instruction(&mut bus), 1, "{:?}", variant);
+            assert!(!cpu.at_instruction_boundary());
+        }
+    }
+
+    #[test]
+    fn test_execute_instruction_matches_clock() {
+        // xorshift32 with a fixed seed, so any failure can be reproduced
+        let mut seed = 0x2545_F491u32;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        let variants = [
+            CpuVariant::Mos6507,
+            CpuVariant::Nmos6502,
+            CpuVariant::Cmos65C02,
+            CpuVariant::Ricoh2A03,
+        ];
+        for variant in variants {
+            for run in 0..20 {
+                // Random memory & registers, so every opcode & addressing mode gets run
+                let ram: Vec<u8> = (0..0x10000).map(|_| random() as u8).collect();
+                let mut clocked_bus = TestBus {
+                    ram: ram.clone(),
+                    accesses: Vec::new(),
+                    recording: true,
+                };
+                let mut bus = TestBus {
+                    ram,
+                    accesses: Vec::new(),
+                    recording: true,
+                };
+                let mut clocked = Cpu::new_with_variant(variant, 0, &mut clocked_bus);
+                let mut cpu = Cpu::new_with_variant(variant, 0, &mut bus);
+                let registers = [Register::A, Register::X, Register::Y, Register::StackPointer];
+                for register in registers.into_iter().chain([Register::Status]) {
+                    let value = random() as u16;
+                    clocked.set_register(register, value);
+                    cpu.set_register(register, value);
+                }
+
+                for instruction in 0..500 {
+                    // Interrupts are taken at the same points either way
+                    let (irq, nmi) = (random() % 8 == 0, random() % 16 == 0);
+                    for cpu in [&mut clocked, &mut cpu] {
+                        cpu.set_irq_line(irq);
+                        cpu.set_nmi_line(nmi);
+                    }
+
+                    let cycles = cpu.execute_instruction(&mut bus);
+                    let context = format!("{:?} run {} instruction {}", variant, run, instruction);
+                    let expected_cycles = clock_instruction(&mut clocked, &mut clocked_bus);
+                    assert_eq!(cycles, expected_cycles, "{}", context);
+                    assert_eq!(cpu.registers(), clocked.registers(), "{}", context);
+                    assert_eq!(cpu.cycles, clocked.cycles, "{}", context);
+                    assert_eq!(bus.accesses, clocked_bus.accesses, "{}", context);
+
+                    // Restart a jammed cpu rather than spending the rest of the run locked up
+                    if cpu.is_jammed() {
+                        clocked.reset();
+                        cpu.reset();
+                    }
+                }
+                assert!(bus.ram == clocked_bus.ram, "{:?} run {}", variant, run);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod register_tests {
+    use super::test_bus::cpu_with_program;
+    use super::{CpuVariant, Flag, Register, RegisterSnapshot};
+
+    #[test]
+    fn test_register_snapshot() {
+        // LDA #$42, LDX #$07, SEC
+        let program = [0xA9, 0x42, 0xA2, 0x07, 0x38];
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Nmos6502, &program);
+        for _ in 0..3 {
+            cpu.execute_instruction(&mut bus);
+        }
+
+        let registers = cpu.registers();
+        assert_eq!(
+            registers,
+            RegisterSnapshot {
+                a: 0x42,
+                x: 0x07,
+                y: 0x00,
+                stack_pointer: 0xFD,
+                program_counter: 0x0405,
+                status: 0b0000_0101,
+            }
+        );
+        assert!(registers.flag(Flag::Carry));
+        assert!(registers.flag(Flag::InterruptDisable));
+        assert!(!registers.flag(Flag::Zero));
+
+        // Snapshots are copies, changing the cpu afterwards doesn't affect them
+        cpu.set_register(Register::Y, 0x1FF);
+        cpu.set_flag(Flag::Carry, false);
+        assert_eq!(cpu.registers().y, 0xFF);
+        assert!(!cpu.registers().flag(Flag::Carry));
+        assert_eq!(registers.y, 0x00);
+    }
+
+    #[test]
+    fn test_set_pc_abandons_the_current_instruction() {
+        // LDA $1234, with LDX #$09 at $0500
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Nmos6502, &[0xAD, 0x34, 0x12]);
+        bus.ram[0x500..0x502].copy_from_slice(&[0xA2, 0x09]);
+        bus.ram[0x1234] = 0x42;
+
+        cpu.clock(&mut bus);
+        cpu.clock(&mut bus);
+        assert!(!cpu.at_instruction_boundary());
+
+        cpu.set_pc(0x0500);
+        assert!(cpu.at_instruction_boundary());
+        assert_eq!(cpu.execute_instruction(&mut bus), 2);
+        assert_eq!(cpu.register(Register::X), 0x09);
+        assert_eq!(cpu.register(Register::A), 0x00);
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0502);
+    }
+
+    #[test]
+    fn test_set_pc_leaves_a_jammed_cpu_jammed() {
+        // KIL
+        let (mut cpu, mut bus) = cpu_with_program(CpuVariant::Nmos6502, &[0x02]);
+        cpu.execute_instruction(&mut bus);
+        assert!(cpu.is_jammed());
+
+        cpu.set_pc(0x0500);
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.register(Register::ProgramCounter), 0x0500);
+    }
+}
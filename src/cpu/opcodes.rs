@@ -1,6 +1,4 @@
-use crate::Device;
-
-use super::{Cpu, State, CpuState, status_flags::StatusFlags, InterruptState, interrupts::Interrupt};
+use super::{Cpu, State, status_flags::StatusFlags, micro_ops::Step};
 
 use wasm_bindgen::prelude::*;
 
@@ -10,488 +8,315 @@ pub(super) struct Opcode {
     pub(super) opcode: u8,
     pub(super) operation: Operation,
     pub(super) address_mode: AddressingMode,
-    is_illegal: bool,
+    pub(super) is_illegal: bool,
 }
 
 impl Opcode {
-    pub(super) fn execute(&self, cpu: &mut Cpu, device: &Device, operand: Option<u8>, address: Option<u16>) -> State {
-        // All read modify write instructions do a double write, one on this cycle and
-        // one on the actual write cycle with the proper new value
-        if let (InstructionType::ReadModifyWrite, Some(o), Some(a)) =
-            (self.operation.instruction_type(), operand, address)
-        {
-            // Dummy write, first write the original value
-            device.write_byte(a, o);
-        };
+    /// RMB, SMB, BBR & BBS encode the bit they operate on in bits 4-6 of the opcode
+    pub(super) fn bit_number(&self) -> u8 {
+        (self.opcode >> 4) & 0b111
+    }
 
+    ///
+    /// The last cycle of read & implied instructions. Read instructions act on
+    /// the operand, implied ones are passed whatever their dummy read saw and
+    /// ignore it.
+    ///
+    pub(super) fn execute(&self, cpu: &mut Cpu, operand: u8) -> Step {
         match self.operation {
             Operation::ADC => {
-                device.poll_for_interrupts(true);
-                cpu.adc(operand.unwrap());
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::AHX => todo!(),
-            Operation::ALR => todo!(),
-            Operation::ANC => todo!(),
-            Operation::AND => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a &= operand.unwrap();
-                cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
+                cpu.adc(operand);
+                return cpu.finish_arithmetic();
             }
-            Operation::ARR => todo!(),
-            Operation::ASL => {
-                let result = operand.unwrap() << 1;
+            Operation::ALR => {
+                let value = cpu.registers.a & operand;
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 0b1000_0000 != 0);
-                cpu.set_negative_zero_flags(result);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
-                }
+                    .set(StatusFlags::CARRY_FLAG, value & 1 == 1);
+                cpu.registers.a = value >> 1;
+                cpu.set_negative_zero_flags(cpu.registers.a);
             }
-            Operation::AXS => todo!(),
-            Operation::BCC
-            | Operation::BCS
-            | Operation::BEQ
-            | Operation::BMI
-            | Operation::BNE
-            | Operation::BPL
-            | Operation::BVC
-            | Operation::BVS => State::Cpu(CpuState::SetProgramCounter {
-                address: address.unwrap(),
-                was_branch_instruction: true,
-            }),
-            Operation::BIT => {
-                device.poll_for_interrupts(true);
-                let result = cpu.registers.a & operand.unwrap();
-                cpu.registers.status_register.set(StatusFlags::ZERO_FLAG, result == 0);
-                cpu.registers
-                    .status_register
-                    .set(StatusFlags::OVERFLOW_FLAG, operand.unwrap() & 0b0100_0000 != 0);
+            Operation::ANC => {
+                cpu.registers.a &= operand;
+                cpu.set_negative_zero_flags(cpu.registers.a);
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::NEGATIVE_FLAG, operand.unwrap() & 0b1000_0000 != 0);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::BRK => State::Interrupt(InterruptState::PushPCH(Interrupt::IRQ_BRK(0))),
-            Operation::CLC => {
-                device.poll_for_interrupts(true);
-                cpu.registers.status_register.remove(StatusFlags::CARRY_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
+                    .set(StatusFlags::CARRY_FLAG, cpu.registers.a & 0b1000_0000 != 0);
             }
-            Operation::CLD => {
-                device.poll_for_interrupts(true);
-                cpu.registers.status_register.remove(StatusFlags::DECIMAL_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
+            Operation::AND => {
+                cpu.registers.a &= operand;
+                cpu.set_negative_zero_flags(cpu.registers.a);
             }
-            Operation::CLI => {
-                device.poll_for_interrupts(true);
+            Operation::ARR => cpu.arr(operand),
+            Operation::AXS => {
+                let value = cpu.registers.a & cpu.registers.x;
                 cpu.registers
                     .status_register
-                    .remove(StatusFlags::INTERRUPT_DISABLE_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::CLV => {
-                device.poll_for_interrupts(true);
-                cpu.registers.status_register.remove(StatusFlags::OVERFLOW_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::CMP => {
-                device.poll_for_interrupts(true);
-                cpu.compare(operand.unwrap(), cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::CPX => {
-                device.poll_for_interrupts(true);
-                cpu.compare(operand.unwrap(), cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::CPY => {
-                device.poll_for_interrupts(true);
-                cpu.compare(operand.unwrap(), cpu.registers.y);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::DCP => {
-                let result = cpu.decrement(operand.unwrap());
-                cpu.compare(result, cpu.registers.a);
-                State::Cpu(CpuState::WritingResult {
-                    value: result,
-                    address: address.unwrap(),
-                    dummy: true,
-                })
+                    .set(StatusFlags::CARRY_FLAG, value >= operand);
+                cpu.registers.x = value.wrapping_sub(operand);
+                cpu.set_negative_zero_flags(cpu.registers.x);
             }
-            Operation::DEC => {
-                let result = cpu.decrement(operand.unwrap());
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
+            Operation::BIT => {
+                let result = cpu.registers.a & operand;
+                cpu.registers.status_register.set(StatusFlags::ZERO_FLAG, result == 0);
+                // The 65C02's BIT #imm only sets Z, there's no memory for N & V to come from
+                if self.address_mode != AddressingMode::Immediate {
+                    cpu.registers
+                        .status_register
+                        .set(StatusFlags::OVERFLOW_FLAG, operand & 0b0100_0000 != 0);
+                    cpu.registers
+                        .status_register
+                        .set(StatusFlags::NEGATIVE_FLAG, operand & 0b1000_0000 != 0);
                 }
             }
-            Operation::DEX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.x = cpu.decrement(cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::DEY => {
-                device.poll_for_interrupts(true);
-                cpu.registers.y = cpu.decrement(cpu.registers.y);
-                State::Cpu(CpuState::FetchOpcode)
-            }
+            Operation::CLC => cpu.registers.status_register.remove(StatusFlags::CARRY_FLAG),
+            Operation::CLD => cpu.registers.status_register.remove(StatusFlags::DECIMAL_FLAG),
+            Operation::CLI => cpu
+                .registers
+                .status_register
+                .remove(StatusFlags::INTERRUPT_DISABLE_FLAG),
+            Operation::CLV => cpu.registers.status_register.remove(StatusFlags::OVERFLOW_FLAG),
+            Operation::CMP => cpu.compare(operand, cpu.registers.a),
+            Operation::CPX => cpu.compare(operand, cpu.registers.x),
+            Operation::CPY => cpu.compare(operand, cpu.registers.y),
+            Operation::DEX => cpu.registers.x = cpu.decrement(cpu.registers.x),
+            Operation::DEY => cpu.registers.y = cpu.decrement(cpu.registers.y),
             Operation::EOR => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a ^= operand.unwrap();
+                cpu.registers.a ^= operand;
                 cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::INC => {
-                let result = cpu.increment(operand.unwrap());
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
-                }
-            }
-            Operation::INX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.x = cpu.increment(cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::INY => {
-                device.poll_for_interrupts(true);
-                cpu.registers.y = cpu.increment(cpu.registers.y);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::ISB => {
-                let result = cpu.increment(operand.unwrap());
-                cpu.adc(!result);
-
-                State::Cpu(CpuState::WritingResult {
-                    value: result,
-                    address: address.unwrap(),
-                    dummy: true,
-                })
-            }
-            Operation::JMP => {
-                device.poll_for_interrupts(true);
-                cpu.registers.program_counter = address.unwrap();
-                State::Cpu(CpuState::FetchOpcode)
             }
-            Operation::JSR => State::Cpu(CpuState::WritePCHToStack {
-                address: address.unwrap(),
-            }),
-            Operation::KIL => {
-                // Illegal opcode - KIL
-                panic!();
+            Operation::INX => cpu.registers.x = cpu.increment(cpu.registers.x),
+            Operation::INY => cpu.registers.y = cpu.increment(cpu.registers.y),
+            // STP halts the cpu until it's reset, just as KIL does on NMOS parts
+            Operation::KIL | Operation::STP => return Step::Goto(State::Jammed),
+            Operation::LAS => {
+                let value = operand & cpu.registers.stack_pointer;
+                cpu.registers.a = value;
+                cpu.registers.x = value;
+                cpu.registers.stack_pointer = value;
+                cpu.set_negative_zero_flags(value);
             }
-            Operation::LAS => todo!(),
             Operation::LAX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a = operand.unwrap();
-                cpu.registers.x = operand.unwrap();
-                cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
+                // The immediate form is unstable, it mixes in whatever A & the magic constant give
+                let value = match self.address_mode {
+                    AddressingMode::Immediate => {
+                        (cpu.registers.a | cpu.magic_constant) & operand
+                    }
+                    _ => operand,
+                };
+                cpu.registers.a = value;
+                cpu.registers.x = value;
+                cpu.set_negative_zero_flags(value);
             }
             Operation::LDA => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a = operand.unwrap();
+                cpu.registers.a = operand;
                 cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
             }
             Operation::LDX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.x = operand.unwrap();
+                cpu.registers.x = operand;
                 cpu.set_negative_zero_flags(cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
             }
             Operation::LDY => {
-                device.poll_for_interrupts(true);
-                cpu.registers.y = operand.unwrap();
+                cpu.registers.y = operand;
                 cpu.set_negative_zero_flags(cpu.registers.y);
-                State::Cpu(CpuState::FetchOpcode)
             }
-            Operation::LSR => {
-                let result = operand.unwrap() >> 1;
-                cpu.registers
-                    .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 1 == 1);
-                cpu.set_negative_zero_flags(result);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
-                }
+            Operation::NOP => {}
+            Operation::ORA => {
+                cpu.registers.a |= operand;
+                cpu.set_negative_zero_flags(cpu.registers.a);
             }
-            Operation::NOP => {
-                device.poll_for_interrupts(true);
-                State::Cpu(CpuState::FetchOpcode)
+            Operation::SBC => {
+                cpu.sbc(operand);
+                return cpu.finish_arithmetic();
             }
-            Operation::ORA => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a |= operand.unwrap();
+            Operation::SEC => cpu.registers.status_register.insert(StatusFlags::CARRY_FLAG),
+            Operation::SED => cpu.registers.status_register.insert(StatusFlags::DECIMAL_FLAG),
+            Operation::SEI => cpu
+                .registers
+                .status_register
+                .insert(StatusFlags::INTERRUPT_DISABLE_FLAG),
+            Operation::TAX => {
+                cpu.registers.x = cpu.registers.a;
+                cpu.set_negative_zero_flags(cpu.registers.x);
+            }
+            Operation::TAY => {
+                cpu.registers.y = cpu.registers.a;
+                cpu.set_negative_zero_flags(cpu.registers.y);
+            }
+            Operation::TSX => {
+                cpu.registers.x = cpu.registers.stack_pointer;
+                cpu.set_negative_zero_flags(cpu.registers.x);
+            }
+            Operation::TXA => {
+                cpu.registers.a = cpu.registers.x;
                 cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
             }
-            Operation::PHA => {
-                device.poll_for_interrupts(true);
-                State::Cpu(CpuState::PushRegisterOnStack { value: cpu.registers.a })
+            Operation::TXS => cpu.registers.stack_pointer = cpu.registers.x,
+            Operation::TYA => {
+                cpu.registers.a = cpu.registers.y;
+                cpu.set_negative_zero_flags(cpu.registers.a);
             }
-            Operation::PHP => {
-                device.poll_for_interrupts(true);
-                // Note that we mask bits 4, 5 here as this is called from an instruction
-                State::Cpu(CpuState::PushRegisterOnStack {
-                    value: cpu.registers.status_register.bits() | 0b0011_0000,
-                })
+            Operation::WAI => return Step::Goto(State::Waiting),
+            Operation::XAA => {
+                cpu.registers.a =
+                    (cpu.registers.a | cpu.magic_constant) & cpu.registers.x & operand;
+                cpu.set_negative_zero_flags(cpu.registers.a);
             }
-            Operation::PLA => State::Cpu(CpuState::PreIncrementStackPointer {
-                operation: self.operation,
-            }),
-            Operation::PLP => State::Cpu(CpuState::PreIncrementStackPointer {
-                operation: self.operation,
-            }),
-            Operation::RLA => {
-                let mut result = operand.unwrap() << 1;
-                if cpu.registers.status_register.contains(StatusFlags::CARRY_FLAG) {
-                    result |= 1;
-                }
+            _ => panic!("{:?} isn't a read or implied instruction", self.operation),
+        }
+
+        Step::Done
+    }
+
+    /// The result of a read modify write instruction, also used by the accumulator forms
+    pub(super) fn modify(&self, cpu: &mut Cpu, value: u8) -> u8 {
+        let carry = cpu.registers.status_register.contains(StatusFlags::CARRY_FLAG);
+
+        match self.operation {
+            Operation::ASL | Operation::SLO => {
+                let result = value << 1;
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 0b1000_0000 != 0);
-                cpu.registers.a &= result;
-                cpu.set_negative_zero_flags(cpu.registers.a);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
+                    .set(StatusFlags::CARRY_FLAG, value & 0b1000_0000 != 0);
+                cpu.set_negative_zero_flags(result);
+                if self.operation == Operation::SLO {
+                    cpu.registers.a |= result;
+                    cpu.set_negative_zero_flags(cpu.registers.a);
                 }
+                result
             }
-            Operation::ROL => {
-                let mut result = operand.unwrap() << 1;
-                if cpu.registers.status_register.contains(StatusFlags::CARRY_FLAG) {
-                    result |= 1;
-                }
+            Operation::DCP => {
+                let result = cpu.decrement(value);
+                cpu.compare(result, cpu.registers.a);
+                result
+            }
+            Operation::DEC => cpu.decrement(value),
+            Operation::INC => cpu.increment(value),
+            Operation::ISB => {
+                let result = cpu.increment(value);
+                cpu.sbc(result);
+                result
+            }
+            Operation::LSR | Operation::SRE => {
+                let result = value >> 1;
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 0b1000_0000 != 0);
+                    .set(StatusFlags::CARRY_FLAG, value & 1 == 1);
                 cpu.set_negative_zero_flags(result);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
+                if self.operation == Operation::SRE {
+                    cpu.registers.a ^= result;
+                    cpu.set_negative_zero_flags(cpu.registers.a);
                 }
+                result
             }
-            Operation::ROR => {
-                let mut result = operand.unwrap() >> 1;
-                if cpu.registers.status_register.contains(StatusFlags::CARRY_FLAG) {
-                    result |= 0b1000_0000;
-                }
+            Operation::RMB => value & !(1 << self.bit_number()),
+            Operation::ROL | Operation::RLA => {
+                let result = (value << 1) | carry as u8;
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 1 == 1);
+                    .set(StatusFlags::CARRY_FLAG, value & 0b1000_0000 != 0);
                 cpu.set_negative_zero_flags(result);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
+                if self.operation == Operation::RLA {
+                    cpu.registers.a &= result;
+                    cpu.set_negative_zero_flags(cpu.registers.a);
                 }
+                result
             }
-            Operation::RRA => {
-                let mut result = operand.unwrap() >> 1;
-                if cpu.registers.status_register.contains(StatusFlags::CARRY_FLAG) {
-                    result |= 0b1000_0000;
-                }
+            Operation::ROR | Operation::RRA => {
+                let result = (value >> 1) | ((carry as u8) << 7);
                 cpu.registers
                     .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 1 == 1);
-                cpu.adc(result);
-
-                match self.address_mode {
-                    AddressingMode::Accumulator => {
-                        device.poll_for_interrupts(true);
-                        cpu.registers.a = result;
-                        State::Cpu(CpuState::FetchOpcode)
-                    }
-                    _ => State::Cpu(CpuState::WritingResult {
-                        address: address.unwrap(),
-                        value: result,
-                        dummy: true,
-                    }),
+                    .set(StatusFlags::CARRY_FLAG, value & 1 == 1);
+                cpu.set_negative_zero_flags(result);
+                if self.operation == Operation::RRA {
+                    cpu.adc(result);
                 }
+                result
             }
-            Operation::RTI => State::Cpu(CpuState::PreIncrementStackPointer {
-                operation: self.operation,
-            }),
-            Operation::RTS => State::Cpu(CpuState::PreIncrementStackPointer {
-                operation: self.operation,
-            }),
-            Operation::SAX => State::Cpu(CpuState::WritingResult {
-                value: cpu.registers.a & cpu.registers.x,
-                address: address.unwrap(),
-                dummy: false,
-            }),
-            Operation::SBC => {
-                device.poll_for_interrupts(true);
-                cpu.adc(!operand.unwrap());
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::SEC => {
-                device.poll_for_interrupts(true);
-                cpu.registers.status_register.insert(StatusFlags::CARRY_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::SED => {
-                device.poll_for_interrupts(true);
-                cpu.registers.status_register.insert(StatusFlags::DECIMAL_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::SEI => {
-                device.poll_for_interrupts(true);
+            Operation::SMB => value | (1 << self.bit_number()),
+            Operation::TRB | Operation::TSB => {
                 cpu.registers
                     .status_register
-                    .insert(StatusFlags::INTERRUPT_DISABLE_FLAG);
-                State::Cpu(CpuState::FetchOpcode)
+                    .set(StatusFlags::ZERO_FLAG, value & cpu.registers.a == 0);
+                match self.operation {
+                    Operation::TRB => value & !cpu.registers.a,
+                    _ => value | cpu.registers.a,
+                }
             }
-            Operation::SHX => todo!(),
-            Operation::SHY => todo!(),
-            Operation::SLO => {
-                let result = operand.unwrap() << 1;
-                cpu.registers
-                    .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 0b1000_0000 != 0);
-                cpu.registers.a |= result;
-                cpu.set_negative_zero_flags(cpu.registers.a);
+            _ => panic!("{:?} isn't a read modify write instruction", self.operation),
+        }
+    }
 
-                State::Cpu(CpuState::WritingResult {
-                    value: result,
-                    address: address.unwrap(),
-                    dummy: true,
-                })
-            }
-            Operation::SRE => {
-                let result = operand.unwrap() >> 1;
-                cpu.registers
-                    .status_register
-                    .set(StatusFlags::CARRY_FLAG, operand.unwrap() & 1 == 1);
-                cpu.registers.a ^= result;
-                cpu.set_negative_zero_flags(cpu.registers.a);
+    /// The address & value a store instruction writes, given the address it was aimed at
+    pub(super) fn store(&self, cpu: &mut Cpu, address: u16) -> (u16, u8) {
+        let index = match self.address_mode {
+            AddressingMode::AbsoluteXIndexed => cpu.registers.x,
+            _ => cpu.registers.y,
+        };
 
-                State::Cpu(CpuState::WritingResult {
-                    address: address.unwrap(),
-                    value: result,
-                    dummy: true,
-                })
-            }
-            Operation::STA => State::Cpu(CpuState::WritingResult {
-                value: cpu.registers.a,
-                address: address.unwrap(),
-                dummy: false,
-            }),
-            Operation::STX => State::Cpu(CpuState::WritingResult {
-                value: cpu.registers.x,
-                address: address.unwrap(),
-                dummy: false,
-            }),
-            Operation::STY => State::Cpu(CpuState::WritingResult {
-                value: cpu.registers.y,
-                address: address.unwrap(),
-                dummy: false,
-            }),
-            Operation::TAS => todo!(),
-            Operation::TAX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.x = cpu.registers.a;
-                cpu.set_negative_zero_flags(cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
-            }
-            Operation::TAY => {
-                device.poll_for_interrupts(true);
-                cpu.registers.y = cpu.registers.a;
-                cpu.set_negative_zero_flags(cpu.registers.y);
-                State::Cpu(CpuState::FetchOpcode)
+        match self.operation {
+            Operation::AHX => cpu.unstable_store(cpu.registers.a & cpu.registers.x, address, index),
+            Operation::SAX => (address, cpu.registers.a & cpu.registers.x),
+            Operation::SHX => cpu.unstable_store(cpu.registers.x, address, index),
+            Operation::SHY => cpu.unstable_store(cpu.registers.y, address, index),
+            Operation::STA => (address, cpu.registers.a),
+            Operation::STX => (address, cpu.registers.x),
+            Operation::STY => (address, cpu.registers.y),
+            Operation::STZ => (address, 0),
+            Operation::TAS => {
+                cpu.registers.stack_pointer = cpu.registers.a & cpu.registers.x;
+                cpu.unstable_store(cpu.registers.stack_pointer, address, index)
             }
-            Operation::TSX => {
-                device.poll_for_interrupts(true);
-                cpu.registers.x = cpu.registers.stack_pointer;
-                cpu.set_negative_zero_flags(cpu.registers.x);
-                State::Cpu(CpuState::FetchOpcode)
+            _ => panic!("{:?} isn't a store instruction", self.operation),
+        }
+    }
+
+    pub(super) fn branch_taken(&self, cpu: &Cpu) -> bool {
+        let status = &cpu.registers.status_register;
+
+        match self.operation {
+            Operation::BCC => !status.contains(StatusFlags::CARRY_FLAG),
+            Operation::BCS => status.contains(StatusFlags::CARRY_FLAG),
+            Operation::BEQ => status.contains(StatusFlags::ZERO_FLAG),
+            Operation::BMI => status.contains(StatusFlags::NEGATIVE_FLAG),
+            Operation::BNE => !status.contains(StatusFlags::ZERO_FLAG),
+            Operation::BPL => !status.contains(StatusFlags::NEGATIVE_FLAG),
+            Operation::BVC => !status.contains(StatusFlags::OVERFLOW_FLAG),
+            Operation::BVS => status.contains(StatusFlags::OVERFLOW_FLAG),
+            Operation::BRA => true,
+            _ => panic!("{:?} isn't a branch instruction", self.operation),
+        }
+    }
+
+    pub(super) fn push_value(&self, cpu: &Cpu) -> u8 {
+        match self.operation {
+            Operation::PHA => cpu.registers.a,
+            // B & bit 5 are always set when P is pushed by an instruction
+            Operation::PHP => cpu.registers.status_register.bits() | 0b0011_0000,
+            Operation::PHX => cpu.registers.x,
+            Operation::PHY => cpu.registers.y,
+            _ => panic!("{:?} isn't a push instruction", self.operation),
+        }
+    }
+
+    pub(super) fn pull(&self, cpu: &mut Cpu, value: u8) {
+        match self.operation {
+            Operation::PLA => {
+                cpu.registers.a = value;
+                cpu.set_negative_zero_flags(value);
             }
-            Operation::TXA => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a = cpu.registers.x;
-                cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
+            Operation::PLP => {
+                cpu.registers.status_register = StatusFlags::from_bits_truncate(value & 0b1100_1111)
             }
-            Operation::TXS => {
-                device.poll_for_interrupts(true);
-                cpu.registers.stack_pointer = cpu.registers.x;
-                State::Cpu(CpuState::FetchOpcode)
+            Operation::PLX => {
+                cpu.registers.x = value;
+                cpu.set_negative_zero_flags(value);
             }
-            Operation::TYA => {
-                device.poll_for_interrupts(true);
-                cpu.registers.a = cpu.registers.y;
-                cpu.set_negative_zero_flags(cpu.registers.a);
-                State::Cpu(CpuState::FetchOpcode)
+            Operation::PLY => {
+                cpu.registers.y = value;
+                cpu.set_negative_zero_flags(value);
             }
-            Operation::XAA => todo!(),
+            _ => panic!("{:?} isn't a pull instruction", self.operation),
         }
     }
 }
@@ -520,6 +345,8 @@ pub(super) enum AddressingMode {
     Absolute,
     AbsoluteXIndexed,
     AbsoluteYIndexed,
+    // JMP ($HHLL,X), 65C02 only
+    AbsoluteIndexedIndirect,
     Immediate,
     Implied,
     Indirect,
@@ -529,6 +356,10 @@ pub(super) enum AddressingMode {
     ZeroPage,
     ZeroPageXIndexed,
     ZeroPageYIndexed,
+    // ($LL), 65C02 only
+    ZeroPageIndirect,
+    // BBR & BBS test a bit at a zero page address then branch, 65C02 only
+    ZeroPageRelative,
 }
 
 impl AddressingMode {
@@ -538,6 +369,7 @@ impl AddressingMode {
             AddressingMode::Absolute => InstructionLength::Three,
             AddressingMode::AbsoluteXIndexed => InstructionLength::Three,
             AddressingMode::AbsoluteYIndexed => InstructionLength::Three,
+            AddressingMode::AbsoluteIndexedIndirect => InstructionLength::Three,
             AddressingMode::Immediate => InstructionLength::Two,
             AddressingMode::Implied => InstructionLength::One,
             AddressingMode::Indirect => InstructionLength::Three,
@@ -547,6 +379,8 @@ impl AddressingMode {
             AddressingMode::ZeroPage => InstructionLength::Two,
             AddressingMode::ZeroPageXIndexed => InstructionLength::Two,
             AddressingMode::ZeroPageYIndexed => InstructionLength::Two,
+            AddressingMode::ZeroPageIndirect => InstructionLength::Two,
+            AddressingMode::ZeroPageRelative => InstructionLength::Three,
         }
     }
 }
@@ -562,6 +396,8 @@ pub(super) enum Operation {
     ARR,
     ASL,
     AXS,
+    BBR,
+    BBS,
     BCC,
     BCS,
     BEQ,
@@ -569,6 +405,7 @@ pub(super) enum Operation {
     BMI,
     BNE,
     BPL,
+    BRA,
     BRK,
     BVC,
     BVS,
@@ -601,9 +438,14 @@ pub(super) enum Operation {
     ORA,
     PHA,
     PHP,
+    PHX,
+    PHY,
     PLA,
     PLP,
+    PLX,
+    PLY,
     RLA,
+    RMB,
     ROL,
     ROR,
     RRA,
@@ -617,27 +459,39 @@ pub(super) enum Operation {
     SHX,
     SHY,
     SLO,
+    SMB,
     SRE,
     STA,
+    STP,
     STX,
     STY,
+    STZ,
     TAS,
     TAX,
     TAY,
+    TRB,
+    TSB,
     TSX,
     TXA,
     TXS,
     TYA,
+    WAI,
     XAA,
 }
 
 impl Operation {
-    pub(super) fn instruction_type(&self) -> InstructionType {
+    pub(super) const fn instruction_type(&self) -> InstructionType {
         match self {
             Operation::JMP | Operation::JSR => InstructionType::Jump,
-            Operation::STA | Operation::STX | Operation::STY | Operation::SAX | Operation::SHX | Operation::SHY => {
-                InstructionType::Write
-            }
+            Operation::STA
+            | Operation::STX
+            | Operation::STY
+            | Operation::SAX
+            | Operation::SHX
+            | Operation::SHY
+            | Operation::AHX
+            | Operation::TAS
+            | Operation::STZ => InstructionType::Write,
             Operation::ASL
             | Operation::LSR
             | Operation::ROL
@@ -649,7 +503,11 @@ impl Operation {
             | Operation::RLA
             | Operation::RRA
             | Operation::ISB
-            | Operation::DCP => InstructionType::ReadModifyWrite,
+            | Operation::DCP
+            | Operation::TSB
+            | Operation::TRB
+            | Operation::RMB
+            | Operation::SMB => InstructionType::ReadModifyWrite,
             Operation::LDA
             | Operation::LDX
             | Operation::LDY
@@ -663,6 +521,12 @@ impl Operation {
             | Operation::CPY
             | Operation::BIT
             | Operation::LAX
+            | Operation::LAS
+            | Operation::ANC
+            | Operation::ALR
+            | Operation::ARR
+            | Operation::AXS
+            | Operation::XAA
             | Operation::NOP => InstructionType::Read,
             Operation::BCC
             | Operation::BCS
@@ -671,14 +535,21 @@ impl Operation {
             | Operation::BPL
             | Operation::BMI
             | Operation::BVC
-            | Operation::BVS => InstructionType::Branch,
+            | Operation::BVS
+            | Operation::BRA
+            | Operation::BBR
+            | Operation::BBS => InstructionType::Branch,
             Operation::BRK
             | Operation::RTI
             | Operation::RTS
             | Operation::PHA
             | Operation::PHP
             | Operation::PLA
-            | Operation::PLP => InstructionType::Stack,
+            | Operation::PLP
+            | Operation::PHX
+            | Operation::PHY
+            | Operation::PLX
+            | Operation::PLY => InstructionType::Stack,
             Operation::CLC
             | Operation::CLD
             | Operation::CLI
@@ -695,8 +566,10 @@ impl Operation {
             | Operation::TSX
             | Operation::TXA
             | Operation::TXS
-            | Operation::TYA => InstructionType::NoMemoryAccess,
-            _ => todo!("Not yet defined instruction type for {:?}", self),
+            | Operation::TYA
+            | Operation::KIL
+            | Operation::WAI
+            | Operation::STP => InstructionType::NoMemoryAccess,
         }
     }
 }
@@ -2255,3 +2128,1633 @@ pub(super) const OPCODE_TABLE: [Opcode; 0x100] = [
         is_illegal: true,
     },
 ];
+
+///
+/// The W65C02S. Every undefined opcode is a NOP, the one byte ones take a
+/// single cycle and are marked illegal along with the other undefined NOPs.
+///
+pub(super) const OPCODE_TABLE_65C02: [Opcode; 0x100] = [
+    // 0x00-0x0F
+    Opcode {
+        opcode: 0x00,
+        operation: Operation::BRK,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x01,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x02,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x03,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x04,
+        operation: Operation::TSB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x05,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x06,
+        operation: Operation::ASL,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x07,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x08,
+        operation: Operation::PHP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x09,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x0A,
+        operation: Operation::ASL,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x0B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x0C,
+        operation: Operation::TSB,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x0D,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x0E,
+        operation: Operation::ASL,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x0F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x10-0x1F
+    Opcode {
+        opcode: 0x10,
+        operation: Operation::BPL,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x11,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x12,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x13,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x14,
+        operation: Operation::TRB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x15,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x16,
+        operation: Operation::ASL,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x17,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x18,
+        operation: Operation::CLC,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x19,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x1A,
+        operation: Operation::INC,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x1B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x1C,
+        operation: Operation::TRB,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x1D,
+        operation: Operation::ORA,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x1E,
+        operation: Operation::ASL,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x1F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x20-0x2F
+    Opcode {
+        opcode: 0x20,
+        operation: Operation::JSR,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x21,
+        operation: Operation::AND,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x22,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x23,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x24,
+        operation: Operation::BIT,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x25,
+        operation: Operation::AND,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x26,
+        operation: Operation::ROL,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x27,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x28,
+        operation: Operation::PLP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x29,
+        operation: Operation::AND,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x2A,
+        operation: Operation::ROL,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x2B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x2C,
+        operation: Operation::BIT,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x2D,
+        operation: Operation::AND,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x2E,
+        operation: Operation::ROL,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x2F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x30-0x3F
+    Opcode {
+        opcode: 0x30,
+        operation: Operation::BMI,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x31,
+        operation: Operation::AND,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x32,
+        operation: Operation::AND,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x33,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x34,
+        operation: Operation::BIT,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x35,
+        operation: Operation::AND,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x36,
+        operation: Operation::ROL,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x37,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x38,
+        operation: Operation::SEC,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x39,
+        operation: Operation::AND,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x3A,
+        operation: Operation::DEC,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x3B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x3C,
+        operation: Operation::BIT,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x3D,
+        operation: Operation::AND,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x3E,
+        operation: Operation::ROL,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x3F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x40-0x4F
+    Opcode {
+        opcode: 0x40,
+        operation: Operation::RTI,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x41,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x42,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x43,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x44,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x45,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x46,
+        operation: Operation::LSR,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x47,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x48,
+        operation: Operation::PHA,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x49,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x4A,
+        operation: Operation::LSR,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x4B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x4C,
+        operation: Operation::JMP,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x4D,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x4E,
+        operation: Operation::LSR,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x4F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x50-0x5F
+    Opcode {
+        opcode: 0x50,
+        operation: Operation::BVC,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x51,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x52,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x53,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x54,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x55,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x56,
+        operation: Operation::LSR,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x57,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x58,
+        operation: Operation::CLI,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x59,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x5A,
+        operation: Operation::PHY,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x5B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x5C,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x5D,
+        operation: Operation::EOR,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x5E,
+        operation: Operation::LSR,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x5F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x60-0x6F
+    Opcode {
+        opcode: 0x60,
+        operation: Operation::RTS,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x61,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x62,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x63,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x64,
+        operation: Operation::STZ,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x65,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x66,
+        operation: Operation::ROR,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x67,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x68,
+        operation: Operation::PLA,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x69,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x6A,
+        operation: Operation::ROR,
+        address_mode: AddressingMode::Accumulator,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x6B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x6C,
+        operation: Operation::JMP,
+        address_mode: AddressingMode::Indirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x6D,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x6E,
+        operation: Operation::ROR,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x6F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x70-0x7F
+    Opcode {
+        opcode: 0x70,
+        operation: Operation::BVS,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x71,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x72,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x73,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x74,
+        operation: Operation::STZ,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x75,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x76,
+        operation: Operation::ROR,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x77,
+        operation: Operation::RMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x78,
+        operation: Operation::SEI,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x79,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x7A,
+        operation: Operation::PLY,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x7B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x7C,
+        operation: Operation::JMP,
+        address_mode: AddressingMode::AbsoluteIndexedIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x7D,
+        operation: Operation::ADC,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x7E,
+        operation: Operation::ROR,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x7F,
+        operation: Operation::BBR,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x80-0x8F
+    Opcode {
+        opcode: 0x80,
+        operation: Operation::BRA,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x81,
+        operation: Operation::STA,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x82,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x83,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x84,
+        operation: Operation::STY,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x85,
+        operation: Operation::STA,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x86,
+        operation: Operation::STX,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x87,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x88,
+        operation: Operation::DEY,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x89,
+        operation: Operation::BIT,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x8A,
+        operation: Operation::TXA,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x8B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x8C,
+        operation: Operation::STY,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x8D,
+        operation: Operation::STA,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x8E,
+        operation: Operation::STX,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x8F,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0x90-0x9F
+    Opcode {
+        opcode: 0x90,
+        operation: Operation::BCC,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x91,
+        operation: Operation::STA,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x92,
+        operation: Operation::STA,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x93,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x94,
+        operation: Operation::STY,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x95,
+        operation: Operation::STA,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x96,
+        operation: Operation::STX,
+        address_mode: AddressingMode::ZeroPageYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x97,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x98,
+        operation: Operation::TYA,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x99,
+        operation: Operation::STA,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x9A,
+        operation: Operation::TXS,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x9B,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0x9C,
+        operation: Operation::STZ,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x9D,
+        operation: Operation::STA,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x9E,
+        operation: Operation::STZ,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0x9F,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xA0-0xAF
+    Opcode {
+        opcode: 0xA0,
+        operation: Operation::LDY,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA1,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA2,
+        operation: Operation::LDX,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xA4,
+        operation: Operation::LDY,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA5,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA6,
+        operation: Operation::LDX,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA8,
+        operation: Operation::TAY,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xA9,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xAA,
+        operation: Operation::TAX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xAB,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xAC,
+        operation: Operation::LDY,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xAD,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xAE,
+        operation: Operation::LDX,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xAF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xB0-0xBF
+    Opcode {
+        opcode: 0xB0,
+        operation: Operation::BCS,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB1,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB2,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xB4,
+        operation: Operation::LDY,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB5,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB6,
+        operation: Operation::LDX,
+        address_mode: AddressingMode::ZeroPageYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB8,
+        operation: Operation::CLV,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xB9,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xBA,
+        operation: Operation::TSX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xBB,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xBC,
+        operation: Operation::LDY,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xBD,
+        operation: Operation::LDA,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xBE,
+        operation: Operation::LDX,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xBF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xC0-0xCF
+    Opcode {
+        opcode: 0xC0,
+        operation: Operation::CPY,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC1,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC2,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xC3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xC4,
+        operation: Operation::CPY,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC5,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC6,
+        operation: Operation::DEC,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC8,
+        operation: Operation::INY,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xC9,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCA,
+        operation: Operation::DEX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCB,
+        operation: Operation::WAI,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCC,
+        operation: Operation::CPY,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCD,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCE,
+        operation: Operation::DEC,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xCF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xD0-0xDF
+    Opcode {
+        opcode: 0xD0,
+        operation: Operation::BNE,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD1,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD2,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xD4,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xD5,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD6,
+        operation: Operation::DEC,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD8,
+        operation: Operation::CLD,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xD9,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xDA,
+        operation: Operation::PHX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xDB,
+        operation: Operation::STP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xDC,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xDD,
+        operation: Operation::CMP,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xDE,
+        operation: Operation::DEC,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xDF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xE0-0xEF
+    Opcode {
+        opcode: 0xE0,
+        operation: Operation::CPX,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE1,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::IndirectXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE2,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xE3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xE4,
+        operation: Operation::CPX,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE5,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE6,
+        operation: Operation::INC,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE8,
+        operation: Operation::INX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xE9,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::Immediate,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xEA,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xEB,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xEC,
+        operation: Operation::CPX,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xED,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xEE,
+        operation: Operation::INC,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xEF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+    // 0xF0-0xFF
+    Opcode {
+        opcode: 0xF0,
+        operation: Operation::BEQ,
+        address_mode: AddressingMode::Relative,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF1,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::IndirectYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF2,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::ZeroPageIndirect,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF3,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xF4,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xF5,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF6,
+        operation: Operation::INC,
+        address_mode: AddressingMode::ZeroPageXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF7,
+        operation: Operation::SMB,
+        address_mode: AddressingMode::ZeroPage,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF8,
+        operation: Operation::SED,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xF9,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::AbsoluteYIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xFA,
+        operation: Operation::PLX,
+        address_mode: AddressingMode::Implied,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xFB,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Implied,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xFC,
+        operation: Operation::NOP,
+        address_mode: AddressingMode::Absolute,
+        is_illegal: true,
+    },
+    Opcode {
+        opcode: 0xFD,
+        operation: Operation::SBC,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xFE,
+        operation: Operation::INC,
+        address_mode: AddressingMode::AbsoluteXIndexed,
+        is_illegal: false,
+    },
+    Opcode {
+        opcode: 0xFF,
+        operation: Operation::BBS,
+        address_mode: AddressingMode::ZeroPageRelative,
+        is_illegal: false,
+    },
+];
+
+#[cfg(test)]
+mod opcode_table_tests {
+    use super::{AddressingMode, InstructionType, OPCODE_TABLE, OPCODE_TABLE_65C02};
+
+    #[test]
+    fn test_opcode_table_is_indexed_by_opcode() {
+        for table in [&OPCODE_TABLE, &OPCODE_TABLE_65C02] {
+            for (ix, opcode) in table.iter().enumerate() {
+                assert_eq!(opcode.opcode as usize, ix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_opcode_has_consistent_instruction_type() {
+        for opcode in OPCODE_TABLE.iter().chain(OPCODE_TABLE_65C02.iter()) {
+            let mode = opcode.address_mode;
+
+            match opcode.operation.instruction_type() {
+                InstructionType::Read => assert!(
+                    !matches!(mode, AddressingMode::Accumulator | AddressingMode::Relative),
+                    "{:?}",
+                    opcode
+                ),
+                InstructionType::ReadModifyWrite => assert!(
+                    !matches!(
+                        mode,
+                        AddressingMode::Immediate
+                            | AddressingMode::Implied
+                            | AddressingMode::Relative
+                    ),
+                    "{:?}",
+                    opcode
+                ),
+                InstructionType::Write => assert!(
+                    !matches!(
+                        mode,
+                        AddressingMode::Immediate
+                            | AddressingMode::Implied
+                            | AddressingMode::Accumulator
+                            | AddressingMode::Relative
+                    ),
+                    "{:?}",
+                    opcode
+                ),
+                InstructionType::Branch => assert!(
+                    matches!(
+                        mode,
+                        AddressingMode::Relative | AddressingMode::ZeroPageRelative
+                    ),
+                    "{:?}",
+                    opcode
+                ),
+                InstructionType::Jump => assert!(
+                    matches!(
+                        mode,
+                        AddressingMode::Absolute
+                            | AddressingMode::Indirect
+                            | AddressingMode::AbsoluteIndexedIndirect
+                    ),
+                    "{:?}",
+                    opcode
+                ),
+                InstructionType::Stack | InstructionType::NoMemoryAccess => {
+                    assert_eq!(mode, AddressingMode::Implied, "{:?}", opcode)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//!
+//! Runs Tom Harte's ProcessorTests (now SingleStepTests/65x02) for the NMOS
+//! 6502, which list the exact bus activity on every cycle of 10,000 random
+//! executions of each opcode.
+//!
+//! The tests aren't distributed with this repository so they're ignored by
+//! default. Download the JSON files from
+//! https://github.com/SingleStepTests/65x02 (the `6502/v1` directory) into
+//! `tests/data/processor_tests`, or the directory named by the
+//! `PROCESSOR_TESTS_DIR` environment variable, and run them with
+//! `cargo test -- --ignored`. They fail if the files aren't there.
+//!
+//! Every opcode is run, but for the 12 KIL opcodes only the registers & RAM
+//! are compared. Once jammed the cpu just reads $FFFF every cycle, the tests
+//! list the floating address bus of a real part which isn't modelled.
+//!
+
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use super::test_bus::TestBus;
+use super::{Cpu, Register};
+
+/// Bits 4 & 5 of P only exist when it's pushed to the stack
+const STATUS_MASK: u16 = 0b1100_1111;
+
+/// The opcodes which jam the cpu, their bus activity isn't compared
+const JAM_OPCODES: [u8; 12] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// The registers & RAM before or after a test, as listed in the JSON
+struct MachineState {
+    registers: [(Register, u16); 6],
+    ram: Vec<(u16, u8)>,
+}
+
+fn number(value: &Value, what: &str) -> Result<u64, String> {
+    value
+        .as_u64()
+        .ok_or_else(|| format!("Expected a number for {} but found {}", what, value))
+}
+
+fn parse_state(state: &Value) -> Result<MachineState, String> {
+    let register = |name: &str| number(&state[name], name).map(|value| value as u16);
+
+    let ram = state["ram"]
+        .as_array()
+        .ok_or("Expected a ram array")?
+        .iter()
+        .map(|entry| {
+            Ok((
+                number(&entry[0], "ram address")? as u16,
+                number(&entry[1], "ram value")? as u8,
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(MachineState {
+        registers: [
+            (Register::ProgramCounter, register("pc")?),
+            (Register::StackPointer, register("s")?),
+            (Register::A, register("a")?),
+            (Register::X, register("x")?),
+            (Register::Y, register("y")?),
+            (Register::Status, register("p")?),
+        ],
+        ram,
+    })
+}
+
+fn parse_cycles(cycles: &Value) -> Result<Vec<(u16, u8, Access)>, String> {
+    cycles
+        .as_array()
+        .ok_or("Expected a cycles array")?
+        .iter()
+        .map(|cycle| {
+            let access = match cycle[2].as_str() {
+                Some("read") => Access::Read,
+                Some("write") => Access::Write,
+                _ => return Err(format!("Unexpected cycle type {}", cycle[2])),
+            };
+            Ok((
+                number(&cycle[0], "cycle address")? as u16,
+                number(&cycle[1], "cycle value")? as u8,
+                access,
+            ))
+        })
+        .collect()
+}
+
+///
+/// Run a single test case, one clock per listed cycle, and compare the bus
+/// activity and final state with what the test expects.
+///
+fn run_test(test: &Value) -> Result<(), String> {
+    let name = test["name"].as_str().unwrap_or("unnamed");
+    let initial = parse_state(&test["initial"])?;
+    let expected = parse_state(&test["final"])?;
+    let expected_cycles = parse_cycles(&test["cycles"])?;
+
+    // The tests only initialise the addresses they use
+    let mut bus = TestBus {
+        ram: vec![0; 0x10000],
+        accesses: Vec::new(),
+        recording: true,
+    };
+    for &(address, value) in &initial.ram {
+        bus.ram[address as usize] = value;
+    }
+    let mut cpu = Cpu::new(0, &mut bus);
+    for (register, value) in initial.registers {
+        cpu.set_register(register, value);
+    }
+    let opcode = bus.ram[cpu.register(Register::ProgramCounter) as usize];
+    let jams = JAM_OPCODES.contains(&opcode);
+    bus.accesses.clear();
+
+    // Every cycle must make exactly the one expected access
+    for (cycle, expected_access) in expected_cycles.iter().enumerate() {
+        cpu.clock(&mut bus);
+
+        let accesses: Vec<_> = bus
+            .accesses
+            .iter()
+            .map(|access| {
+                let kind = match access.kind.is_write() {
+                    true => Access::Write,
+                    false => Access::Read,
+                };
+                (access.address, access.value, kind)
+            })
+            .collect();
+        if !jams && accesses != [*expected_access] {
+            return Err(format!(
+                "{}: cycle {} accessed {:02X?}, expected {:02X?}",
+                name, cycle, accesses, expected_access
+            ));
+        }
+        bus.accesses.clear();
+    }
+    if jams && !cpu.is_jammed() {
+        return Err(format!("{}: didn't jam the cpu", name));
+    }
+    if !jams && !cpu.at_instruction_boundary() {
+        return Err(format!(
+            "{}: still executing after {} cycles",
+            name,
+            expected_cycles.len()
+        ));
+    }
+    for (register, value) in expected.registers {
+        let mask = match register {
+            Register::Status => STATUS_MASK,
+            _ => 0xFFFF,
+        };
+        if cpu.register(register) & mask != value & mask {
+            return Err(format!(
+                "{}: {:?} was {:02X}, expected {:02X}",
+                name,
+                register,
+                cpu.register(register),
+                value
+            ));
+        }
+    }
+    for (address, value) in expected.ram {
+        if bus.ram[address as usize] != value {
+            return Err(format!(
+                "{}: ${:04X} was {:02X}, expected {:02X}",
+                name, address, bus.ram[address as usize], value
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run every test in a file, returning the first failure
+fn run_tests(json: &str) -> Result<usize, String> {
+    let tests: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let tests = tests.as_array().ok_or("Expected an array of tests")?;
+
+    for test in tests {
+        run_test(test)?;
+    }
+
+    Ok(tests.len())
+}
+
+#[test]
+#[ignore = "needs the JSON files in tests/data/processor_tests, see the module docs"]
+fn test_processor_tests() {
+    let directory = match std::env::var_os("PROCESSOR_TESTS_DIR") {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/processor_tests"),
+    };
+    assert!(directory.is_dir(), "{} not found", directory.display());
+
+    let mut failures = Vec::new();
+    for opcode in 0..=0xFF {
+        let path = directory.join(format!("{:02x}.json", opcode));
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+            .and_then(|json| run_tests(&json));
+
+        if let Err(error) = result {
+            failures.push(format!("{:02X} - {}", opcode, error));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+/// Check the runner itself with a hand written case, so it's exercised without the downloaded tests
+#[test]
+fn test_runner_with_known_bus_activity() {
+    let json = r#"[
+        {
+            "name": "a9 lda immediate",
+            "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 128]] },
+            "final": { "pc": 514, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164, "ram": [[512, 169], [513, 128]] },
+            "cycles": [[512, 169, "read"], [513, 128, "read"]]
+        }
+    ]"#;
+    assert_eq!(run_tests(json), Ok(1));
+
+    let wrong_access = json.replace(r#"[513, 128, "read"]"#, r#"[513, 128, "write"]"#);
+    let error = run_tests(&wrong_access).unwrap_err();
+    assert!(error.starts_with("a9 lda immediate: cycle 1"), "{}", error);
+
+    let wrong_register = json.replace(r#""a": 128"#, r#""a": 127"#);
+    let error = run_tests(&wrong_register).unwrap_err();
+    assert!(error.starts_with("a9 lda immediate: A was 80"), "{}", error);
+
+    assert!(run_tests("[{").is_err());
+
+    // Only the registers & RAM are compared for KIL
+    let jam = r#"[
+        {
+            "name": "02 kil",
+            "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 2]] },
+            "final": { "pc": 513, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 2]] },
+            "cycles": [[512, 2, "read"], [513, 0, "read"], [65535, 0, "write"], [65534, 0, "read"]]
+        }
+    ]"#;
+    assert_eq!(run_tests(jam), Ok(1));
+
+    let wrong_pc = jam.replace(r#""pc": 513"#, r#""pc": 514"#);
+    let error = run_tests(&wrong_pc).unwrap_err();
+    assert!(error.starts_with("02 kil: ProgramCounter"), "{}", error);
+}
@@ -1,4 +1,4 @@
-use super::status_flags::StatusFlags;
+use super::status_flags::{Flag, StatusFlags};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -28,3 +28,52 @@ impl Registers {
         }
     }
 }
+
+///
+/// A copy of every register at a single point in time, so that a debugger
+/// can show the whole cpu at once. The status register is as `P` would be
+/// pushed by PHP, less the break and unused bits.
+///
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub stack_pointer: u8,
+    pub program_counter: u16,
+    pub status: u8,
+}
+
+#[wasm_bindgen]
+impl RegisterSnapshot {
+    /// Whether a flag is set in the snapshot's status register
+    pub fn flag(&self, flag: Flag) -> bool {
+        self.status & flag.status_flag().bits() != 0
+    }
+}
+
+impl From<&Registers> for RegisterSnapshot {
+    fn from(registers: &Registers) -> Self {
+        RegisterSnapshot {
+            a: registers.a,
+            x: registers.x,
+            y: registers.y,
+            stack_pointer: registers.stack_pointer,
+            program_counter: registers.program_counter,
+            status: registers.status_register.bits(),
+        }
+    }
+}
+
+/// The registers which a debugger can inspect and modify
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    StackPointer,
+    ProgramCounter,
+    Status,
+}
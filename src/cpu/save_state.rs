@@ -0,0 +1,257 @@
+use super::interrupts::Interrupt;
+use super::opcodes::Opcode;
+use super::status_flags::StatusFlags;
+use super::{Cpu, CpuState, CpuVariant, InterruptState, State};
+use crate::save_state::{invalid_value, SaveState, StateReader, StateWriter};
+
+///
+/// The cpu state machine holds references into the opcode table, so each
+/// variant is written as a tag followed by its fields with opcodes stored as
+/// their byte value. An instruction part way through also needs the latches
+/// which carry its address & data between cycles.
+///
+/// The variant comes first so that a state saved from a different cpu
+/// variant is rejected before any opcode is looked up in the wrong table.
+///
+impl SaveState for Cpu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.variant as u8);
+        write_state(writer, &self.state);
+        writer.write_u8(self.registers.a);
+        writer.write_u8(self.registers.x);
+        writer.write_u8(self.registers.y);
+        writer.write_u8(self.registers.stack_pointer);
+        writer.write_u16(self.registers.program_counter);
+        writer.write_u8(self.registers.status_register.bits());
+        writer.write_u32(self.cycles);
+        writer.write_u8(self.cpu_cycle_counter);
+        writer.write_bool(self.polled_interrupt.is_some());
+        if let Some(interrupt) = self.polled_interrupt {
+            write_interrupt(writer, interrupt);
+        }
+        let lines = &self.interrupt_lines;
+        writer.write_bool(lines.irq);
+        writer.write_bool(lines.nmi);
+        writer.write_bool(lines.nmi_previous);
+        writer.write_bool(lines.nmi_pending);
+        writer.write_bool(lines.sampled.is_some());
+        if let Some(interrupt) = lines.sampled {
+            write_interrupt(writer, interrupt);
+        }
+        writer.write_u8(self.magic_constant);
+        writer.write_u16(self.latches.address);
+        writer.write_u8(self.latches.pointer);
+        writer.write_u8(self.latches.data);
+        writer.write_bool(self.latches.page_crossed);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let variant = reader.read_u8()?;
+        match CpuVariant::from_u8(variant) {
+            Some(variant) if variant == self.variant => {}
+            Some(variant) => {
+                return Err(format!(
+                    "Save state is for a {:?} cpu, this is a {:?}",
+                    variant, self.variant
+                ))
+            }
+            None => return Err(invalid_value("cpu variant", variant)),
+        }
+
+        self.state = read_state(reader, self.variant)?;
+        self.registers.a = reader.read_u8()?;
+        self.registers.x = reader.read_u8()?;
+        self.registers.y = reader.read_u8()?;
+        self.registers.stack_pointer = reader.read_u8()?;
+        self.registers.program_counter = reader.read_u16()?;
+        self.registers.status_register = StatusFlags::from_bits_truncate(reader.read_u8()?);
+        self.cycles = reader.read_u32()?;
+        self.cpu_cycle_counter = reader.read_u8()?;
+        self.polled_interrupt = match reader.read_bool()? {
+            true => Some(read_interrupt(reader)?),
+            false => None,
+        };
+        self.interrupt_lines.irq = reader.read_bool()?;
+        self.interrupt_lines.nmi = reader.read_bool()?;
+        self.interrupt_lines.nmi_previous = reader.read_bool()?;
+        self.interrupt_lines.nmi_pending = reader.read_bool()?;
+        self.interrupt_lines.sampled = match reader.read_bool()? {
+            true => Some(read_interrupt(reader)?),
+            false => None,
+        };
+        self.magic_constant = reader.read_u8()?;
+        self.latches.address = reader.read_u16()?;
+        self.latches.pointer = reader.read_u8()?;
+        self.latches.data = reader.read_u8()?;
+        self.latches.page_crossed = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+fn write_interrupt(writer: &mut StateWriter, interrupt: Interrupt) {
+    let (tag, cycle) = match interrupt {
+        Interrupt::NMI(cycle) => (0, cycle),
+        Interrupt::IRQ(cycle) => (1, cycle),
+        Interrupt::IRQ_BRK(cycle) => (2, cycle),
+        Interrupt::RESET(cycle) => (3, cycle),
+    };
+    writer.write_u8(tag);
+    writer.write_u32(cycle);
+}
+
+fn read_interrupt(reader: &mut StateReader) -> Result<Interrupt, String> {
+    let tag = reader.read_u8()?;
+    let cycle = reader.read_u32()?;
+
+    match tag {
+        0 => Ok(Interrupt::NMI(cycle)),
+        1 => Ok(Interrupt::IRQ(cycle)),
+        2 => Ok(Interrupt::IRQ_BRK(cycle)),
+        3 => Ok(Interrupt::RESET(cycle)),
+        _ => Err(invalid_value("interrupt", tag)),
+    }
+}
+
+fn read_opcode(reader: &mut StateReader, variant: CpuVariant) -> Result<&'static Opcode, String> {
+    Ok(&variant.opcode_table()[reader.read_u8()? as usize])
+}
+
+fn write_state(writer: &mut StateWriter, state: &State) {
+    match *state {
+        State::Interrupt(interrupt_state) => {
+            writer.write_u8(0);
+            let (tag, interrupt) = match interrupt_state {
+                InterruptState::InternalOps1(i) => (0, i),
+                InterruptState::InternalOps2(i) => (1, i),
+                InterruptState::PushPCH(i) => (2, i),
+                InterruptState::PushPCL(i) => (3, i),
+                InterruptState::PushStatusRegister(i) => (4, i),
+                InterruptState::PullIRQVecLow(i) => (5, i),
+                InterruptState::PullIRQVecHigh(i) => (6, i),
+            };
+            writer.write_u8(tag);
+            write_interrupt(writer, interrupt);
+        }
+        State::Cpu(cpu_state) => {
+            writer.write_u8(1);
+            write_cpu_state(writer, &cpu_state);
+        }
+        State::Jammed => writer.write_u8(2),
+        State::Waiting => writer.write_u8(3),
+    }
+}
+
+fn read_state(reader: &mut StateReader, variant: CpuVariant) -> Result<State, String> {
+    match reader.read_u8()? {
+        0 => {
+            let tag = reader.read_u8()?;
+            let interrupt = read_interrupt(reader)?;
+            let interrupt_state = match tag {
+                0 => InterruptState::InternalOps1(interrupt),
+                1 => InterruptState::InternalOps2(interrupt),
+                2 => InterruptState::PushPCH(interrupt),
+                3 => InterruptState::PushPCL(interrupt),
+                4 => InterruptState::PushStatusRegister(interrupt),
+                5 => InterruptState::PullIRQVecLow(interrupt),
+                6 => InterruptState::PullIRQVecHigh(interrupt),
+                _ => return Err(invalid_value("interrupt state", tag)),
+            };
+            Ok(State::Interrupt(interrupt_state))
+        }
+        1 => Ok(State::Cpu(read_cpu_state(reader, variant)?)),
+        2 => Ok(State::Jammed),
+        3 => Ok(State::Waiting),
+        tag => Err(invalid_value("cpu state", tag)),
+    }
+}
+
+fn write_cpu_state(writer: &mut StateWriter, state: &CpuState) {
+    match *state {
+        CpuState::FetchOpcode => writer.write_u8(0),
+        CpuState::Executing { opcode, step } => {
+            writer.write_u8(1);
+            writer.write_u8(opcode.opcode);
+            writer.write_u8(step);
+        }
+        CpuState::DecimalAdjust => writer.write_u8(2),
+    }
+}
+
+fn read_cpu_state(reader: &mut StateReader, variant: CpuVariant) -> Result<CpuState, String> {
+    Ok(match reader.read_u8()? {
+        0 => CpuState::FetchOpcode,
+        1 => {
+            let opcode = read_opcode(reader, variant)?;
+            let step = reader.read_u8()?;
+            if step as usize >= variant.micro_op_table()[opcode.opcode as usize].len() {
+                return Err(invalid_value("micro-op step", step));
+            }
+
+            CpuState::Executing { opcode, step }
+        }
+        2 => CpuState::DecimalAdjust,
+        tag => return Err(invalid_value("cpu state", tag)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::test_bus::TestBus;
+    use crate::cpu::Cpu;
+    use crate::save_state::{load, save};
+
+    /// 64K of RAM holding a program which loops through a variety of addressing modes
+    fn test_bus() -> TestBus {
+        // LDX #$05, loop: LDA ($10),Y, STA $0200,X, INC $20, JSR sub, DEX, BNE loop, JMP $0400
+        // sub: PHA, PLA, RTS
+        let program = [
+            0xA2, 0x05, 0xB1, 0x10, 0x9D, 0x00, 0x02, 0xE6, 0x20, 0x20, 0x20, 0x04, 0xCA, 0xD0,
+            0xF4, 0x4C, 0x00, 0x04,
+        ];
+        let mut bus = TestBus::with_program(0, 0x0400, &program);
+        bus.ram[0x420..0x423].copy_from_slice(&[0x48, 0x68, 0x60]);
+        bus.ram[0x10] = 0xF0;
+        bus.ram[0x11] = 0x02;
+
+        bus
+    }
+
+    #[test]
+    fn test_restore_mid_instruction() {
+        // Saving on every cycle catches each of the states an instruction passes through
+        for split in 0..80 {
+            let mut bus = test_bus();
+            let mut cpu = Cpu::new(0, &mut bus);
+            for _ in 0..split {
+                cpu.clock(&mut bus);
+            }
+
+            let mut restored_bus = test_bus();
+            restored_bus.ram.copy_from_slice(&bus.ram);
+            let mut restored = Cpu::new(0, &mut test_bus());
+            load(&mut restored, &save(&cpu)).unwrap();
+
+            for _ in 0..50 {
+                cpu.clock(&mut bus);
+                restored.clock(&mut restored_bus);
+            }
+            assert_eq!(save(&cpu), save(&restored), "split at cycle {}", split);
+            assert!(bus.ram == restored_bus.ram, "split at cycle {}", split);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_cpu_state() {
+        let mut bus = test_bus();
+        let mut cpu = Cpu::new(0, &mut bus);
+        let original = save(&cpu);
+        let mut state = original.clone();
+        // The state tag follows the header and the variant
+        state[7] = 0xFF;
+
+        assert!(load(&mut cpu, &state).is_err());
+        assert!(load(&mut cpu, &original[..original.len() - 1]).is_err());
+        assert_eq!(save(&cpu), original);
+    }
+}
@@ -13,6 +13,31 @@ bitflags! {
   }
 }
 
+/// The flags in the status register which a debugger can inspect and modify
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+    Carry,
+    Zero,
+    InterruptDisable,
+    Decimal,
+    Overflow,
+    Negative,
+}
+
+impl Flag {
+    pub(super) fn status_flag(&self) -> StatusFlags {
+        match self {
+            Flag::Carry => StatusFlags::CARRY_FLAG,
+            Flag::Zero => StatusFlags::ZERO_FLAG,
+            Flag::InterruptDisable => StatusFlags::INTERRUPT_DISABLE_FLAG,
+            Flag::Decimal => StatusFlags::DECIMAL_FLAG,
+            Flag::Overflow => StatusFlags::OVERFLOW_FLAG,
+            Flag::Negative => StatusFlags::NEGATIVE_FLAG,
+        }
+    }
+}
+
 #[cfg(test)]
 mod status_flag_tests {
     use super::StatusFlags;
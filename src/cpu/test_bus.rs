@@ -0,0 +1,70 @@
+//!
+//! A cpu wired straight to 64K of RAM, shared by the cpu's tests and the
+//! benchmark harness.
+//!
+
+#[cfg(test)]
+use super::{Cpu, CpuVariant};
+use crate::bus::{Bus, BusAccess};
+
+pub(super) struct TestBus {
+    pub(super) ram: Vec<u8>,
+    /// Every access the cpu reported, while `recording` is set
+    pub(super) accesses: Vec<BusAccess>,
+    pub(super) recording: bool,
+}
+
+impl TestBus {
+    /// RAM filled with `fill`, holding `program` at `address` with the reset vector pointing to it
+    pub(super) fn with_program(fill: u8, address: u16, program: &[u8]) -> TestBus {
+        let mut ram = vec![fill; 0x10000];
+        ram[address as usize..address as usize + program.len()].copy_from_slice(program);
+        ram[0xFFFC..=0xFFFD].copy_from_slice(&address.to_le_bytes());
+
+        TestBus {
+            ram,
+            accesses: Vec::new(),
+            recording: true,
+        }
+    }
+}
+
+impl Bus for TestBus {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.ram[address as usize] = value;
+    }
+
+    fn observe(&mut self, access: BusAccess) {
+        if self.recording {
+            self.accesses.push(access);
+        }
+    }
+}
+
+/// The program runs from $0400 with the rest of memory, including the other vectors, filled by NOPs
+#[cfg(test)]
+pub(super) fn cpu_with_program(variant: CpuVariant, program: &[u8]) -> (Cpu, TestBus) {
+    let mut bus = TestBus::with_program(0xEA, 0x0400, program);
+    let cpu = Cpu::new_with_variant(variant, 0, &mut bus);
+
+    (cpu, bus)
+}
+
+///
+/// Run the next instruction a cycle at a time, along with any interrupt
+/// taken after it, and return the cycles taken.
+///
+#[cfg(test)]
+pub(super) fn step(cpu: &mut Cpu, bus: &mut TestBus) -> u32 {
+    let start = cpu.cycles;
+    cpu.clock(bus);
+    while !cpu.at_instruction_boundary() {
+        cpu.clock(bus);
+    }
+
+    cpu.cycles - start
+}
@@ -0,0 +1,94 @@
+use wasm_bindgen::prelude::*;
+
+use super::micro_ops::{MicroOp, MICRO_OP_TABLE, MICRO_OP_TABLE_65C02};
+use super::opcodes::{Opcode, OPCODE_TABLE, OPCODE_TABLE_65C02};
+use crate::bus::{Bus, BusAccess};
+
+///
+/// The members of the 6502 family which this core can emulate. They share
+/// the same state machine, the differences are which opcodes exist, how
+/// many address lines are bonded out and a handful of bug fixes.
+///
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuVariant {
+    /// The Atari 2600's cpu, a 6502 with only 13 address lines and no IRQ or NMI pins
+    Mos6507 = 0,
+    /// The original NMOS 6502, including the undocumented opcodes
+    Nmos6502 = 1,
+    /// The CMOS 65C02 (WDC W65C02S) with its extra opcodes and bug fixes
+    Cmos65C02 = 2,
+    /// The NES cpu, an NMOS 6502 with the decimal mode circuitry disconnected
+    Ricoh2A03 = 3,
+}
+
+impl CpuVariant {
+    pub(super) fn from_u8(value: u8) -> Option<CpuVariant> {
+        match value {
+            0 => Some(CpuVariant::Mos6507),
+            1 => Some(CpuVariant::Nmos6502),
+            2 => Some(CpuVariant::Cmos65C02),
+            3 => Some(CpuVariant::Ricoh2A03),
+            _ => None,
+        }
+    }
+
+    pub(super) fn opcode_table(&self) -> &'static [Opcode; 0x100] {
+        match self {
+            CpuVariant::Cmos65C02 => &OPCODE_TABLE_65C02,
+            _ => &OPCODE_TABLE,
+        }
+    }
+
+    /// The cycles following each opcode's fetch, these differ where the 65C02 fixed NMOS quirks
+    pub(super) fn micro_op_table(&self) -> &'static [&'static [MicroOp]; 0x100] {
+        match self {
+            CpuVariant::Cmos65C02 => &MICRO_OP_TABLE_65C02,
+            _ => &MICRO_OP_TABLE,
+        }
+    }
+
+    /// The address lines which leave the chip, the 6507 only has A0-A12
+    pub(super) fn address_mask(&self) -> u16 {
+        match self {
+            CpuVariant::Mos6507 => 0x1FFF,
+            _ => 0xFFFF,
+        }
+    }
+
+    pub(super) fn has_interrupt_pins(&self) -> bool {
+        !matches!(self, CpuVariant::Mos6507)
+    }
+
+    pub(super) fn has_decimal_mode(&self) -> bool {
+        !matches!(self, CpuVariant::Ricoh2A03)
+    }
+
+    /// Whether this is the CMOS core, which fixes most of the NMOS quirks
+    pub(super) fn is_cmos(&self) -> bool {
+        matches!(self, CpuVariant::Cmos65C02)
+    }
+}
+
+/// Drops the address lines which a variant doesn't have before they reach the bus
+pub(super) struct AddressLines<'a, B: Bus> {
+    pub(super) bus: &'a mut B,
+    pub(super) mask: u16,
+}
+
+impl<B: Bus> Bus for AddressLines<'_, B> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.bus.read_byte(address & self.mask)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.bus.write_byte(address & self.mask, value);
+    }
+
+    fn observe(&mut self, access: BusAccess) {
+        self.bus.observe(BusAccess {
+            address: access.address & self.mask,
+            ..access
+        });
+    }
+}
@@ -0,0 +1,334 @@
+use wasm_bindgen::prelude::*;
+
+use crate::tia::COLOR_CLOCKS_PER_SCANLINE;
+
+/// Scanlines a paddle at full resistance takes to charge its INPTx capacitor
+const PADDLE_FULL_SCALE_SCANLINES: u32 = 200;
+
+/// Keypad inputs are only held up by a resistor so read high almost immediately
+const KEYPAD_CHARGE_CLOCKS: u32 = 0;
+
+/// Driving controllers output this 2 bit gray code as they rotate
+const DRIVING_GRAY_CODE: [u8; 4] = [0b00, 0b01, 0b11, 0b10];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Controller {
+    Joystick {
+        up: bool,
+        down: bool,
+        left: bool,
+        right: bool,
+        fire: bool,
+    },
+    // A pair of paddles share each port, positions are 0 (charges fastest) to 1
+    Paddles {
+        positions: [f32; 2],
+        fire: [bool; 2],
+    },
+    // Bit n is set if key n is pressed, keys are numbered left to right and
+    // top to bottom (1, 2, 3, 4, 5, 6, 7, 8, 9, *, 0, #)
+    Keypad {
+        keys: u16,
+    },
+    Driving {
+        position: u8,
+        fire: bool,
+    },
+}
+
+impl Controller {
+    fn joystick() -> Self {
+        Controller::Joystick {
+            up: false,
+            down: false,
+            left: false,
+            right: false,
+            fire: false,
+        }
+    }
+}
+
+///
+/// The state of everything plugged into the console along with the console
+/// switches. The frontend builds one of these from its own input handling and
+/// passes it to the system once per frame.
+///
+/// Each controller port uses whichever type of controller was last set on it.
+///
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputState {
+    controllers: [Controller; 2],
+    reset: bool,
+    select: bool,
+    color: bool,
+    // Difficulty switches, true is A (pro) and false is B (amateur)
+    left_difficulty_a: bool,
+    right_difficulty_a: bool,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputState {
+    /// Bit position of the high nibble of SWCHA used by each port
+    fn port_shift(port: usize) -> u8 {
+        match port {
+            0 => 4,
+            _ => 0,
+        }
+    }
+
+    fn keypad_column_pressed(keys: u16, port: usize, column: usize, port_a_outputs: u8) -> bool {
+        (0..4).any(|row| {
+            let row_selected = port_a_outputs & (1 << (Self::port_shift(port) + row as u8)) == 0;
+            row_selected && keys & (1 << (row * 3 + column)) != 0
+        })
+    }
+
+    /// Levels driven onto SWCHA by the controllers, active low
+    pub(crate) fn swcha(&self) -> u8 {
+        self.controllers
+            .iter()
+            .enumerate()
+            .fold(0, |swcha, (port, controller)| {
+                let nibble = match *controller {
+                    Controller::Joystick {
+                        up,
+                        down,
+                        left,
+                        right,
+                        ..
+                    } => !((right as u8) << 3 | (left as u8) << 2 | (down as u8) << 1 | up as u8),
+                    Controller::Paddles { fire, .. } => {
+                        !((fire[0] as u8) << 3 | (fire[1] as u8) << 2)
+                    }
+                    Controller::Keypad { .. } => 0b1111,
+                    Controller::Driving { position, .. } => {
+                        0b1100 | DRIVING_GRAY_CODE[(position & 0b11) as usize]
+                    }
+                };
+
+                swcha | ((nibble & 0b1111) << Self::port_shift(port))
+            })
+    }
+
+    /// Levels driven onto SWCHB by the console switches
+    pub(crate) fn swchb(&self) -> u8 {
+        (!self.reset as u8)
+            | (!self.select as u8) << 1
+            | (self.color as u8) << 3
+            | (self.left_difficulty_a as u8) << 6
+            | (self.right_difficulty_a as u8) << 7
+    }
+
+    ///
+    /// Colour clocks each of the INPT0-3 capacitors takes to charge after
+    /// being dumped, None if nothing ever charges it. Keypads are read by the
+    /// game selecting rows with SWCHA so this depends on the port A outputs.
+    ///
+    pub(crate) fn pot_charge_times(&self, port_a_outputs: u8) -> [Option<u32>; 4] {
+        let mut charge_times = [None; 4];
+
+        for (port, controller) in self.controllers.iter().enumerate() {
+            for ix in 0..2 {
+                charge_times[port * 2 + ix] = match *controller {
+                    Controller::Paddles { positions, .. } => {
+                        let full_scale =
+                            PADDLE_FULL_SCALE_SCANLINES * COLOR_CLOCKS_PER_SCANLINE as u32;
+                        Some((positions[ix].clamp(0.0, 1.0) * full_scale as f32) as u32)
+                    }
+                    Controller::Keypad { keys } => {
+                        match Self::keypad_column_pressed(keys, port, ix, port_a_outputs) {
+                            true => None,
+                            false => Some(KEYPAD_CHARGE_CLOCKS),
+                        }
+                    }
+                    _ => None,
+                };
+            }
+        }
+
+        charge_times
+    }
+
+    /// Levels of the INPT4 & INPT5 fire button inputs, active low
+    pub(crate) fn fire_levels(&self, port_a_outputs: u8) -> [bool; 2] {
+        let mut levels = [true; 2];
+
+        for (port, controller) in self.controllers.iter().enumerate() {
+            levels[port] = match *controller {
+                Controller::Joystick { fire, .. } | Controller::Driving { fire, .. } => !fire,
+                Controller::Keypad { keys } => {
+                    !Self::keypad_column_pressed(keys, port, 2, port_a_outputs)
+                }
+                Controller::Paddles { .. } => true,
+            };
+        }
+
+        levels
+    }
+}
+
+#[wasm_bindgen]
+impl InputState {
+    /// Two joysticks with nothing pressed, colour mode and both difficulty switches on B
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> InputState {
+        InputState {
+            controllers: [Controller::joystick(), Controller::joystick()],
+            reset: false,
+            select: false,
+            color: true,
+            left_difficulty_a: false,
+            right_difficulty_a: false,
+        }
+    }
+
+    pub fn set_joystick(
+        &mut self,
+        port: usize,
+        up: bool,
+        down: bool,
+        left: bool,
+        right: bool,
+        fire: bool,
+    ) {
+        self.controllers[port & 1] = Controller::Joystick {
+            up,
+            down,
+            left,
+            right,
+            fire,
+        };
+    }
+
+    ///
+    /// Set one of the four paddles, 0 & 1 are plugged into the left port and
+    /// 2 & 3 into the right. Position runs from 0 to 1.
+    ///
+    pub fn set_paddle(&mut self, paddle: usize, position: f32, fire: bool) {
+        let port = (paddle >> 1) & 1;
+        let (mut positions, mut fires) = match self.controllers[port] {
+            Controller::Paddles { positions, fire } => (positions, fire),
+            _ => ([0.0; 2], [false; 2]),
+        };
+        positions[paddle & 1] = position;
+        fires[paddle & 1] = fire;
+
+        self.controllers[port] = Controller::Paddles {
+            positions,
+            fire: fires,
+        };
+    }
+
+    /// Set the pressed keys of a keypad, bit n is key n in the order 1-9, *, 0, #
+    pub fn set_keypad(&mut self, port: usize, keys: u16) {
+        self.controllers[port & 1] = Controller::Keypad { keys };
+    }
+
+    /// Set a driving controller, position counts steps of rotation and may wrap
+    pub fn set_driving(&mut self, port: usize, position: u8, fire: bool) {
+        self.controllers[port & 1] = Controller::Driving { position, fire };
+    }
+
+    pub fn set_console_switches(
+        &mut self,
+        reset: bool,
+        select: bool,
+        color: bool,
+        left_difficulty_a: bool,
+        right_difficulty_a: bool,
+    ) {
+        self.reset = reset;
+        self.select = select;
+        self.color = color;
+        self.left_difficulty_a = left_difficulty_a;
+        self.right_difficulty_a = right_difficulty_a;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputState;
+
+    /// Nothing driven on port A by the RIOT
+    const NO_OUTPUTS: u8 = 0xFF;
+
+    #[test]
+    fn test_default_state() {
+        let input = InputState::new();
+        assert_eq!(input.swcha(), 0xFF);
+        assert_eq!(input.swchb(), 0b0000_1011);
+        assert_eq!(input.fire_levels(NO_OUTPUTS), [true, true]);
+        assert_eq!(input.pot_charge_times(NO_OUTPUTS), [None; 4]);
+    }
+
+    #[test]
+    fn test_joysticks() {
+        let mut input = InputState::new();
+        input.set_joystick(0, true, false, false, true, true);
+        assert_eq!(input.swcha(), 0b0110_1111);
+        assert_eq!(input.fire_levels(NO_OUTPUTS), [false, true]);
+
+        input.set_joystick(1, false, true, true, false, false);
+        assert_eq!(input.swcha(), 0b0110_1001);
+    }
+
+    #[test]
+    fn test_paddles() {
+        let mut input = InputState::new();
+        input.set_paddle(0, 0.0, false);
+        input.set_paddle(1, 1.0, true);
+        input.set_paddle(3, 0.5, true);
+
+        assert_eq!(input.swcha(), 0b1011_1011);
+        assert_eq!(
+            input.pot_charge_times(NO_OUTPUTS),
+            [Some(0), Some(200 * 228), Some(0), Some(100 * 228)]
+        );
+        // Paddle buttons aren't on INPT4/5
+        assert_eq!(input.fire_levels(NO_OUTPUTS), [true, true]);
+    }
+
+    #[test]
+    fn test_keypad_reads_selected_rows() {
+        let mut input = InputState::new();
+        // Keys 5 (row 1, column 1) and # (row 3, column 2)
+        input.set_keypad(0, 1 << 4 | 1 << 11);
+
+        // Row 1 selected
+        let outputs = 0b1101_1111;
+        assert_eq!(input.pot_charge_times(outputs)[..2], [Some(0), None]);
+        assert_eq!(input.fire_levels(outputs), [true, true]);
+
+        // Row 3 selected
+        let outputs = 0b0111_1111;
+        assert_eq!(input.pot_charge_times(outputs)[..2], [Some(0), Some(0)]);
+        assert_eq!(input.fire_levels(outputs), [false, true]);
+    }
+
+    #[test]
+    fn test_driving_controller_gray_code() {
+        let mut input = InputState::new();
+        let codes: Vec<u8> = (0..5)
+            .map(|position| {
+                input.set_driving(1, position, false);
+                input.swcha() & 0b11
+            })
+            .collect();
+        assert_eq!(codes, vec![0b00, 0b01, 0b11, 0b10, 0b00]);
+    }
+
+    #[test]
+    fn test_console_switches() {
+        let mut input = InputState::new();
+        input.set_console_switches(true, false, false, true, false);
+        assert_eq!(input.swchb(), 0b0100_0010);
+        input.set_console_switches(false, true, true, false, true);
+        assert_eq!(input.swchb(), 0b1000_1001);
+    }
+}
@@ -3,11 +3,28 @@ extern crate bitflags;
 extern crate console_error_panic_hook;
 extern crate log;
 
+mod atari2600;
+mod bus;
+mod cartridge;
 mod cpu;
+mod input;
+mod rewind;
+mod riot;
+mod save_state;
+mod tia;
+mod trace;
 mod utils;
 
 use wasm_bindgen::prelude::*;
 
+pub use atari2600::Atari2600;
+pub use bus::{BusAccess, BusAccessKind};
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use cpu::BenchCpu;
+pub use cpu::{Cpu, Flag, Register, RegisterSnapshot};
+pub use input::InputState;
+
 /// This ClockCycle type alias is used to be clear about which type of cycle we're referring to.
 /// It always refers to the devices system clock which is then subdivided up between dependent 
 /// components.
@@ -23,9 +40,5 @@ extern "C" {
     #[wasm_bindgen(structural, method)]
     pub fn write_byte(this: &Device, address: u16, value: u8);
 
-    // TODO - This doesn't currently do anything useful with the result
-    #[wasm_bindgen(structural, method)]
-    pub fn poll_for_interrupts(this: &Device, clear_lines: bool);
-
     fn alert(s: &str);
 }
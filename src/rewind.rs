@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+
+/// Take a snapshot every this many frames unless configured otherwise
+pub(crate) const DEFAULT_INTERVAL_FRAMES: u32 = 4;
+
+/// Memory the snapshots may use unless configured otherwise
+pub(crate) const DEFAULT_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+///
+/// A ring buffer of save states taken every few frames so that the system
+/// can be stepped backwards.
+///
+/// Consecutive save states are almost identical, so only the newest is kept
+/// in full and every older one is stored as the XOR of it and the snapshot
+/// after it, run length encoded so that unchanged bytes take no space. The
+/// oldest snapshots are discarded whenever the memory budget is exceeded.
+///
+pub(crate) struct RewindBuffer {
+    interval: u32,
+    memory_budget: usize,
+    // Frames run since the system was created (less any that were rewound)
+    frame: u32,
+    latest: Option<Snapshot>,
+    // Oldest first, each is a delta from the snapshot which follows it
+    history: VecDeque<Snapshot>,
+    history_bytes: usize,
+}
+
+struct Snapshot {
+    frame: u32,
+    data: Vec<u8>,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(interval: u32, memory_budget: usize) -> Self {
+        RewindBuffer {
+            interval: interval.max(1),
+            memory_budget,
+            frame: 0,
+            latest: None,
+            history: VecDeque::new(),
+            history_bytes: 0,
+        }
+    }
+
+    /// Change how often snapshots are taken and how much memory they use, this discards them all
+    pub(crate) fn configure(&mut self, interval: u32, memory_budget: usize) {
+        *self = RewindBuffer::new(interval, memory_budget);
+    }
+
+    /// Whether a snapshot should be taken now, i.e. none has been taken for this frame yet
+    pub(crate) fn wants_snapshot(&self) -> bool {
+        self.memory_budget > 0
+            && self.frame.is_multiple_of(self.interval)
+            && !matches!(&self.latest, Some(latest) if latest.frame == self.frame)
+    }
+
+    /// Called by the system at the end of each frame
+    pub(crate) fn frame_completed(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Store a save state taken at the current frame
+    pub(crate) fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = self.latest.take() {
+            let delta = encode_delta(&state, &latest.data);
+            self.history_bytes += delta.len();
+            self.history.push_back(Snapshot {
+                frame: latest.frame,
+                data: delta,
+            });
+        }
+        self.latest = Some(Snapshot {
+            frame: self.frame,
+            data: state,
+        });
+
+        let latest_bytes = self.latest.as_ref().map_or(0, |latest| latest.data.len());
+        while self.history_bytes + latest_bytes > self.memory_budget {
+            match self.history.pop_front() {
+                Some(oldest) => self.history_bytes -= oldest.data.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// How many frames back the oldest snapshot is
+    pub(crate) fn frames_available(&self) -> u32 {
+        let oldest = self.history.front().or(self.latest.as_ref());
+        oldest.map_or(0, |oldest| self.frame.wrapping_sub(oldest.frame))
+    }
+
+    ///
+    /// Find the newest snapshot which is at least `frames` back, or the
+    /// oldest if none are that old. Every snapshot after it is discarded and
+    /// it becomes the current frame. Returns the number of frames actually
+    /// rewound along with the save state to restore.
+    ///
+    pub(crate) fn rewind(&mut self, frames: u32) -> Option<(u32, &[u8])> {
+        let target = self.frame.saturating_sub(frames);
+        let mut latest = self.latest.take()?;
+
+        while latest.frame > target {
+            match self.history.pop_back() {
+                Some(previous) => {
+                    self.history_bytes -= previous.data.len();
+                    latest = Snapshot {
+                        frame: previous.frame,
+                        data: apply_delta(&latest.data, &previous.data),
+                    };
+                }
+                None => break,
+            }
+        }
+
+        let rewound = self.frame.wrapping_sub(latest.frame);
+        self.frame = latest.frame;
+        let latest = self.latest.insert(latest);
+
+        Some((rewound, &latest.data))
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input[*position];
+        *position += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+///
+/// Encode `to` relative to `from` as the length of `to` followed by pairs of
+/// (unchanged byte count, changed byte count, XORed changed bytes).
+///
+fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, to.len());
+
+    let xor = |ix: usize| to[ix] ^ from.get(ix).copied().unwrap_or(0);
+    let mut ix = 0;
+    while ix < to.len() {
+        let unchanged_start = ix;
+        while ix < to.len() && xor(ix) == 0 {
+            ix += 1;
+        }
+        let changed_start = ix;
+        while ix < to.len() && xor(ix) != 0 {
+            ix += 1;
+        }
+
+        write_varint(&mut delta, changed_start - unchanged_start);
+        write_varint(&mut delta, ix - changed_start);
+        delta.extend((changed_start..ix).map(xor));
+    }
+
+    delta
+}
+
+fn apply_delta(from: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut position = 0;
+    let length = read_varint(delta, &mut position);
+    let mut to: Vec<u8> = (0..length)
+        .map(|ix| from.get(ix).copied().unwrap_or(0))
+        .collect();
+
+    let mut ix = 0;
+    while position < delta.len() {
+        ix += read_varint(delta, &mut position);
+        let changed = read_varint(delta, &mut position);
+        for byte in to[ix..ix + changed].iter_mut() {
+            *byte ^= delta[position];
+            position += 1;
+        }
+        ix += changed;
+    }
+
+    to
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, encode_delta, RewindBuffer};
+
+    #[test]
+    fn test_delta_round_trip() {
+        let from: Vec<u8> = (0..1000).map(|ix| (ix % 251) as u8).collect();
+        let mut to = from.clone();
+        to[3] = 0xFF;
+        to[500..700].fill(0);
+
+        let delta = encode_delta(&from, &to);
+        assert!(delta.len() < 250);
+        assert_eq!(apply_delta(&from, &delta), to);
+
+        // Snapshots of different lengths still round trip
+        assert_eq!(
+            apply_delta(&from, &encode_delta(&from, &to[..10])),
+            &to[..10]
+        );
+        assert_eq!(
+            apply_delta(&to[..10], &encode_delta(&to[..10], &from)),
+            from
+        );
+    }
+
+    fn buffer_with_frames(interval: u32, memory_budget: usize, frames: u32) -> RewindBuffer {
+        let mut buffer = RewindBuffer::new(interval, memory_budget);
+        for frame in 0..frames {
+            if buffer.wants_snapshot() {
+                buffer.push(vec![frame as u8; 100]);
+            }
+            buffer.frame_completed();
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_rewind_to_snapshot() {
+        let mut buffer = buffer_with_frames(4, 10_000, 20);
+        assert_eq!(buffer.frames_available(), 20);
+
+        // Frame 20 back 5 is frame 15, the snapshot before that was frame 12
+        let (rewound, state) = buffer.rewind(5).unwrap();
+        assert_eq!(rewound, 8);
+        assert_eq!(state, &[12; 100][..]);
+
+        // Rewinding further than the history goes stops at the oldest snapshot
+        let (rewound, state) = buffer.rewind(100).unwrap();
+        assert_eq!(rewound, 12);
+        assert_eq!(state, &[0; 100][..]);
+        assert_eq!(buffer.frames_available(), 0);
+    }
+
+    #[test]
+    fn test_memory_budget_drops_oldest() {
+        let mut buffer = buffer_with_frames(1, 500, 50);
+        assert!(buffer.frames_available() < 50);
+
+        let frames = buffer.frames_available();
+        let (rewound, state) = buffer.rewind(1000).unwrap();
+        assert_eq!(rewound, frames);
+        assert_eq!(state, &[(50 - frames) as u8; 100][..]);
+    }
+
+    #[test]
+    fn test_zero_budget_disables_snapshots() {
+        let mut buffer = buffer_with_frames(1, 0, 10);
+        assert!(buffer.rewind(1).is_none());
+    }
+}
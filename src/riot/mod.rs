@@ -0,0 +1,408 @@
+use log::info;
+use wasm_bindgen::prelude::*;
+
+use crate::save_state::{invalid_value, SaveState, StateReader, StateWriter};
+
+const RAM_SIZE: usize = 128;
+
+/// Bit set in TIMINT when the interval timer has underflowed
+const TIMER_FLAG: u8 = 0b1000_0000;
+/// Bit set in TIMINT when the configured edge has been seen on PA7
+const PA7_FLAG: u8 = 0b0100_0000;
+
+/// Nothing pressed on either joystick, the port is active low
+const DEFAULT_SWCHA_INPUT: u8 = 0xFF;
+/// Colour mode, reset & select not pressed and both difficulty switches on B
+const DEFAULT_SWCHB_INPUT: u8 = 0b0000_1011;
+
+///
+/// The 6532 RIOT (RAM, I/O, Timer) provides the 128 bytes of system RAM,
+/// two 8 bit I/O ports (joysticks on port A, console switches on port B)
+/// and an interval timer which games use to count out VBLANK and overscan.
+///
+/// It is clocked once per cpu cycle.
+///
+#[wasm_bindgen]
+pub struct Riot {
+    ram: [u8; RAM_SIZE],
+
+    // Output registers and data direction registers (1 = output) for each port
+    ora: u8,
+    ddra: u8,
+    orb: u8,
+    ddrb: u8,
+    // Levels driven onto the port pins by the connected peripherals
+    swcha_input: u8,
+    swchb_input: u8,
+
+    timer: u8,
+    // Number of cycles between each decrement, 1, 8, 64 or 1024
+    timer_interval: u16,
+    cycles_until_decrement: u16,
+    timer_interrupt_enabled: bool,
+
+    // PA7 edge detection, positive edge when true, negative otherwise
+    pa7_positive_edge: bool,
+    pa7_interrupt_enabled: bool,
+
+    interrupt_flags: u8,
+}
+
+impl Default for Riot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Riot {
+    /// Set the levels the joysticks drive onto port A
+    pub(crate) fn set_swcha_input(&mut self, value: u8) {
+        let old_swcha = self.swcha();
+        self.swcha_input = value;
+        self.detect_pa7_edge(old_swcha);
+    }
+
+    /// Set the levels the console switches drive onto port B
+    pub(crate) fn set_swchb_input(&mut self, value: u8) {
+        self.swchb_input = value;
+    }
+
+    /// Levels on the port A pins configured as outputs, input pins are pulled high
+    pub(crate) fn port_a_outputs(&self) -> u8 {
+        (self.ora & self.ddra) | !self.ddra
+    }
+
+    fn swcha(&self) -> u8 {
+        (self.ora & self.ddra) | (self.swcha_input & !self.ddra)
+    }
+
+    /// Flag the configured edge on PA7, whether it came from a joystick or the port's own output
+    fn detect_pa7_edge(&mut self, old_swcha: u8) {
+        let old_pa7 = old_swcha & 0b1000_0000 != 0;
+        let new_pa7 = self.swcha() & 0b1000_0000 != 0;
+
+        if old_pa7 != new_pa7 && new_pa7 == self.pa7_positive_edge {
+            self.interrupt_flags |= PA7_FLAG;
+        }
+    }
+
+    fn swchb(&self) -> u8 {
+        (self.orb & self.ddrb) | (self.swchb_input & !self.ddrb)
+    }
+
+    fn write_timer(&mut self, address: u16, value: u8) {
+        self.timer = value;
+        self.timer_interval = match address & 0b11 {
+            0b00 => 1,  // TIM1T
+            0b01 => 8,  // TIM8T
+            0b10 => 64, // TIM64T
+            _ => 1024,  // T1024T
+        };
+        // The first decrement happens on the cycle the timer is written
+        self.cycles_until_decrement = 1;
+        self.timer_interrupt_enabled = address & 0b1000 != 0;
+        self.interrupt_flags &= !TIMER_FLAG;
+    }
+}
+
+/// The port input levels aren't saved, they're reapplied by the system after loading
+impl SaveState for Riot {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.ram);
+        writer.write_u8(self.ora);
+        writer.write_u8(self.ddra);
+        writer.write_u8(self.orb);
+        writer.write_u8(self.ddrb);
+        writer.write_u8(self.timer);
+        writer.write_u16(self.timer_interval);
+        writer.write_u16(self.cycles_until_decrement);
+        writer.write_bool(self.timer_interrupt_enabled);
+        writer.write_bool(self.pa7_positive_edge);
+        writer.write_bool(self.pa7_interrupt_enabled);
+        writer.write_u8(self.interrupt_flags);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        reader.read_bytes_into(&mut self.ram)?;
+        self.ora = reader.read_u8()?;
+        self.ddra = reader.read_u8()?;
+        self.orb = reader.read_u8()?;
+        self.ddrb = reader.read_u8()?;
+        self.timer = reader.read_u8()?;
+        self.timer_interval = match reader.read_u16()? {
+            interval @ (1 | 8 | 64 | 1024) => interval,
+            interval => return Err(invalid_value("timer interval", interval)),
+        };
+        self.cycles_until_decrement = match reader.read_u16()? {
+            cycles if (1..=self.timer_interval).contains(&cycles) => cycles,
+            cycles => return Err(invalid_value("timer count", cycles)),
+        };
+        self.timer_interrupt_enabled = reader.read_bool()?;
+        self.pa7_positive_edge = reader.read_bool()?;
+        self.pa7_interrupt_enabled = reader.read_bool()?;
+        self.interrupt_flags = reader.read_u8()?;
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Riot {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Riot {
+        Riot {
+            ram: [0; RAM_SIZE],
+            ora: 0,
+            ddra: 0,
+            orb: 0,
+            ddrb: 0,
+            swcha_input: DEFAULT_SWCHA_INPUT,
+            swchb_input: DEFAULT_SWCHB_INPUT,
+            // The timer starts at a random value on real hardware
+            timer: 0xFF,
+            timer_interval: 1024,
+            cycles_until_decrement: 1024,
+            timer_interrupt_enabled: false,
+            pa7_positive_edge: false,
+            pa7_interrupt_enabled: false,
+            interrupt_flags: 0,
+        }
+    }
+
+    /// Move the RIOT on by a single cpu cycle
+    pub fn clock(&mut self) {
+        self.cycles_until_decrement -= 1;
+        if self.cycles_until_decrement > 0 {
+            return;
+        }
+
+        if self.timer == 0 {
+            // On underflow the timer keeps counting down once per cycle so
+            // that the cpu can tell how long ago it expired
+            self.interrupt_flags |= TIMER_FLAG;
+            self.timer_interval = 1;
+        }
+        self.timer = self.timer.wrapping_sub(1);
+        self.cycles_until_decrement = self.timer_interval;
+    }
+
+    ///
+    /// Read from the RIOT, A9 selects between RAM and I/O and A2 between the
+    /// ports and the timer. The address should already be known to be
+    /// targeting the RIOT (A12 low, A7 high).
+    ///
+    pub fn read_byte(&mut self, address: u16) -> u8 {
+        if address & 0x200 == 0 {
+            return self.ram[address as usize & 0x7F];
+        }
+
+        match (address & 0b100 != 0, address & 0b11) {
+            (false, 0b00) => self.swcha(), // SWCHA
+            (false, 0b01) => self.ddra,    // SWACNT
+            (false, 0b10) => self.swchb(), // SWCHB
+            (false, _) => self.ddrb,       // SWBCNT
+            (true, register) if register & 0b01 == 0 => {
+                // INTIM
+                self.timer_interrupt_enabled = address & 0b1000 != 0;
+                self.interrupt_flags &= !TIMER_FLAG;
+                self.timer
+            }
+            (true, _) => {
+                // TIMINT - reading clears the PA7 flag but leaves the timer flag
+                let flags = self.interrupt_flags;
+                self.interrupt_flags &= !PA7_FLAG;
+                flags
+            }
+        }
+    }
+
+    /// Read without clearing any of the interrupt flags, used by the debugger
+    pub(crate) fn peek_byte(&self, address: u16) -> u8 {
+        if address & 0x200 == 0 {
+            return self.ram[address as usize & 0x7F];
+        }
+
+        match (address & 0b100 != 0, address & 0b11) {
+            (false, 0b00) => self.swcha(),
+            (false, 0b01) => self.ddra,
+            (false, 0b10) => self.swchb(),
+            (false, _) => self.ddrb,
+            (true, register) if register & 0b01 == 0 => self.timer,
+            (true, _) => self.interrupt_flags,
+        }
+    }
+
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        if address & 0x200 == 0 {
+            self.ram[address as usize & 0x7F] = value;
+            return;
+        }
+
+        match (address & 0b100 != 0, address & 0b1_0000 != 0) {
+            (false, _) => {
+                let old_swcha = self.swcha();
+                match address & 0b11 {
+                    0b00 => self.ora = value,  // SWCHA
+                    0b01 => self.ddra = value, // SWACNT
+                    0b10 => self.orb = value,  // SWCHB
+                    _ => self.ddrb = value,    // SWBCNT
+                }
+                self.detect_pa7_edge(old_swcha);
+            }
+            (true, true) => self.write_timer(address, value),
+            (true, false) => {
+                // Edge detect control, the value written is ignored
+                self.pa7_positive_edge = address & 0b01 != 0;
+                self.pa7_interrupt_enabled = address & 0b10 != 0;
+                info!(
+                    "PA7 edge detection set to {} edge",
+                    if self.pa7_positive_edge {
+                        "positive"
+                    } else {
+                        "negative"
+                    }
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Riot;
+
+    const SWCHA: u16 = 0x280;
+    const SWACNT: u16 = 0x281;
+    const SWCHB: u16 = 0x282;
+    const SWBCNT: u16 = 0x283;
+    const INTIM: u16 = 0x284;
+    const TIMINT: u16 = 0x285;
+    const TIM1T: u16 = 0x294;
+    const TIM8T: u16 = 0x295;
+    const TIM64T: u16 = 0x296;
+    const T1024T: u16 = 0x297;
+
+    fn clock(riot: &mut Riot, cycles: u32) {
+        for _ in 0..cycles {
+            riot.clock();
+        }
+    }
+
+    #[test]
+    fn test_ram_is_mirrored() {
+        let mut riot = Riot::new();
+        riot.write_byte(0x80, 0x12);
+        riot.write_byte(0xFF, 0x34);
+        assert_eq!(riot.read_byte(0x180), 0x12);
+        assert_eq!(riot.read_byte(0x1FF), 0x34);
+    }
+
+    #[test]
+    fn test_timer_intervals() {
+        for (register, interval) in [(TIM1T, 1), (TIM8T, 8), (TIM64T, 64), (T1024T, 1024)] {
+            let mut riot = Riot::new();
+            riot.write_byte(register, 10);
+            riot.clock();
+            assert_eq!(riot.read_byte(INTIM), 9);
+
+            clock(&mut riot, interval - 1);
+            assert_eq!(riot.read_byte(INTIM), 9);
+            riot.clock();
+            assert_eq!(riot.read_byte(INTIM), 8);
+        }
+    }
+
+    #[test]
+    fn test_timer_underflow() {
+        let mut riot = Riot::new();
+        riot.write_byte(TIM64T, 2);
+        clock(&mut riot, 1 + 64 + 63);
+        assert_eq!(riot.read_byte(INTIM), 0);
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0);
+
+        // After passing 0 the timer decrements every cycle
+        riot.clock();
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0x80);
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0x80);
+        assert_eq!(riot.read_byte(INTIM), 0xFF);
+        riot.clock();
+        riot.clock();
+        assert_eq!(riot.read_byte(INTIM), 0xFD);
+
+        // Reading INTIM clears the flag
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0);
+    }
+
+    #[test]
+    fn test_writing_timer_clears_flag() {
+        let mut riot = Riot::new();
+        riot.write_byte(TIM1T, 0);
+        clock(&mut riot, 2);
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0x80);
+
+        riot.write_byte(T1024T | 0b1000, 5);
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0);
+        clock(&mut riot, 1 + 1024 * 5 + 1);
+        assert_eq!(riot.read_byte(TIMINT) & 0x80, 0x80);
+    }
+
+    #[test]
+    fn test_port_direction_registers() {
+        let mut riot = Riot::new();
+        riot.set_swcha_input(0b1010_1010);
+        assert_eq!(riot.read_byte(SWCHA), 0b1010_1010);
+
+        // Only the pins configured as outputs read back the output register
+        riot.write_byte(SWACNT, 0x0F);
+        riot.write_byte(SWCHA, 0b0101_0101);
+        assert_eq!(riot.read_byte(SWACNT), 0x0F);
+        assert_eq!(riot.read_byte(SWCHA), 0b1010_0101);
+
+        riot.set_swchb_input(0b0000_1011);
+        riot.write_byte(SWBCNT, 0b0000_0100);
+        riot.write_byte(SWCHB, 0xFF);
+        assert_eq!(riot.read_byte(SWBCNT), 0b0000_0100);
+        assert_eq!(riot.read_byte(SWCHB), 0b0000_1111);
+    }
+
+    #[test]
+    fn test_pa7_edge_detection() {
+        let mut riot = Riot::new();
+        // Negative edge by default
+        riot.set_swcha_input(0x7F);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0x40);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0);
+
+        riot.set_swcha_input(0xFF);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0);
+
+        // Positive edge with interrupt enabled
+        riot.write_byte(0x287, 0);
+        riot.set_swcha_input(0x7F);
+        assert_eq!(riot.peek_byte(TIMINT) & 0x40, 0);
+        riot.set_swcha_input(0xFF);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0x40);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0);
+    }
+
+    #[test]
+    fn test_pa7_edge_driven_by_the_port_output() {
+        let mut riot = Riot::new();
+        riot.write_byte(SWCHA, 0x00);
+
+        // Making PA7 an output drives the low output register onto the pin
+        riot.write_byte(SWACNT, 0x80);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0x40);
+
+        // Positive edge, driven by writing the output register
+        riot.write_byte(0x285, 0);
+        riot.write_byte(SWCHA, 0x80);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0x40);
+        riot.write_byte(SWCHA, 0x7F);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0);
+
+        // Handing the pin back to the joystick, which is pulling it high
+        riot.write_byte(SWACNT, 0x00);
+        assert_eq!(riot.read_byte(TIMINT) & 0x40, 0x40);
+    }
+}
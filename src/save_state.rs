@@ -0,0 +1,241 @@
+/// Every save state starts with these bytes so that random data is rejected early
+const MAGIC: [u8; 4] = *b"A26S";
+
+///
+/// Bump this whenever the layout of any component's state changes, states
+/// written by any other version are rejected rather than misinterpreted.
+///
+const VERSION: u16 = 5;
+
+///
+/// Implemented by every component which makes up part of the machine state.
+/// Values are written in a fixed order with no field names, so `load_state`
+/// must read back exactly what `save_state` wrote.
+///
+pub(crate) trait SaveState {
+    fn save_state(&self, writer: &mut StateWriter);
+
+    ///
+    /// Restore the component from a save state. On error the component may
+    /// have been partially updated, callers are expected to restore a known
+    /// good state.
+    ///
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String>;
+}
+
+/// Little endian binary writer, the header is written on creation
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        let mut writer = StateWriter { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(&MAGIC);
+        writer.write_u16(VERSION);
+
+        writer
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub(crate) fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write a block of bytes prefixed with its length
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads back a state written by `StateWriter`, every read is bounds checked
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// Check the header and position the reader at the start of the state
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        let mut reader = StateReader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not a save state".to_string());
+        }
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(format!(
+                "Save state version {} is not supported, expected version {}",
+                version, VERSION
+            ));
+        }
+
+        Ok(reader)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        match self.bytes.get(self.position..self.position + length) {
+            Some(bytes) => {
+                self.position += length;
+                Ok(bytes)
+            }
+            None => Err("Save state is truncated".to_string()),
+        }
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, String> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(invalid_value("boolean", value)),
+        }
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read an index which must be less than `length`, e.g. a bank number
+    pub(crate) fn read_index(&mut self, length: usize, what: &str) -> Result<usize, String> {
+        match self.read_u32()? as usize {
+            index if index < length => Ok(index),
+            index => Err(invalid_value(what, index)),
+        }
+    }
+
+    /// Read a block of bytes written by `write_bytes`, it must exactly fill `destination`
+    pub(crate) fn read_bytes_into(&mut self, destination: &mut [u8]) -> Result<(), String> {
+        let length = self.read_u32()? as usize;
+        if length != destination.len() {
+            return Err(format!(
+                "Save state has a block of {} bytes where {} were expected",
+                length,
+                destination.len()
+            ));
+        }
+        destination.copy_from_slice(self.take(length)?);
+
+        Ok(())
+    }
+
+    /// Check that the whole state has been consumed
+    pub(crate) fn finish(self) -> Result<(), String> {
+        match self.bytes.len() - self.position {
+            0 => Ok(()),
+            remaining => Err(format!(
+                "Save state has {} unexpected trailing bytes",
+                remaining
+            )),
+        }
+    }
+}
+
+/// Save a whole component, including the header
+pub(crate) fn save<T: SaveState>(component: &T) -> Vec<u8> {
+    let mut writer = StateWriter::new();
+    component.save_state(&mut writer);
+
+    writer.into_bytes()
+}
+
+///
+/// Restore a whole component from a state written by `save`. If the state
+/// can't be loaded then the component is left exactly as it was.
+///
+pub(crate) fn load<T: SaveState>(component: &mut T, bytes: &[u8]) -> Result<(), String> {
+    let mut reader = StateReader::new(bytes)?;
+    let backup = save(component);
+
+    let result = component
+        .load_state(&mut reader)
+        .and_then(|_| reader.finish());
+    if result.is_err() {
+        let mut reader = StateReader::new(&backup).unwrap();
+        component
+            .load_state(&mut reader)
+            .expect("A freshly saved state must always load");
+    }
+
+    result
+}
+
+/// Error for a value in a save state which can't be restored
+pub(crate) fn invalid_value<T: std::fmt::Display>(what: &str, value: T) -> String {
+    format!("Save state contains an invalid {} ({})", what, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateReader, StateWriter, VERSION};
+
+    #[test]
+    fn test_values_round_trip() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_u32(0x789A_BCDE);
+        writer.write_bytes(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(reader.read_u8(), Ok(0x12));
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_u16(), Ok(0x3456));
+        assert_eq!(reader.read_u32(), Ok(0x789A_BCDE));
+        let mut block = [0; 3];
+        assert_eq!(reader.read_bytes_into(&mut block), Ok(()));
+        assert_eq!(block, [1, 2, 3]);
+        assert_eq!(reader.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_bad_headers() {
+        assert!(StateReader::new(&[]).is_err());
+        assert!(StateReader::new(b"NOPE\x01\x00").is_err());
+
+        let mut bytes = StateWriter::new().into_bytes();
+        bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = StateReader::new(&bytes).err().unwrap();
+        assert!(error.contains("version"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_truncated_and_trailing_data() {
+        let mut writer = StateWriter::new();
+        writer.write_u16(1);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(reader.read_u16().is_err());
+
+        let reader = StateReader::new(&bytes).unwrap();
+        assert!(reader.finish().is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// The TIA clocks its audio circuits twice per scanline, giving ~31.4kHz on NTSC
+pub(super) const AUDIO_CLOCK_RATE: f64 = 3_579_545.0 / 114.0;
+
+pub(super) const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// Keep at most this many seconds of samples if nobody is taking them
+const MAX_BUFFERED_SECONDS: usize = 1;
+
+const fn poly_table<const N: usize>(bits: u32, feedback_bit: u32) -> [bool; N] {
+    // Maximal length linear feedback shift register, the output is bit 0
+    let mut table = [false; N];
+    let mut register: u32 = (1 << bits) - 1;
+    let mut ix = 0;
+    while ix < N {
+        table[ix] = register & 1 != 0;
+        let feedback = (register ^ (register >> feedback_bit)) & 1;
+        register = (register >> 1) | (feedback << (bits - 1));
+        ix += 1;
+    }
+    table
+}
+
+/// x^4 + x^3 + 1
+const POLY4: [bool; 15] = poly_table(4, 1);
+/// x^5 + x^3 + 1
+const POLY5: [bool; 31] = poly_table(5, 2);
+/// x^9 + x^5 + 1
+const POLY9: [bool; 511] = poly_table(9, 4);
+
+/// The divide by 31 circuit toggles the output twice per period, giving a 13:18 duty cycle
+const fn div31_table() -> [bool; 31] {
+    let mut table = [false; 31];
+    table[0] = true;
+    table[13] = true;
+    table
+}
+
+const DIV31: [bool; 31] = div31_table();
+
+///
+/// A single TIA audio channel. Each channel divides the 31.4kHz audio clock
+/// by AUDF+1 (and by a further 3 for AUDC modes 12-15) and uses the result to
+/// step whichever combination of polynomial counters and dividers AUDC
+/// selects. The output bit is then scaled by the 4 bit AUDV volume.
+///
+#[derive(Debug, Clone, Copy)]
+pub(super) struct AudioChannel {
+    pub(super) audc: u8,
+    pub(super) audf: u8,
+    pub(super) audv: u8,
+    divider: u16,
+    poly4: usize,
+    poly5: usize,
+    poly9: usize,
+    output: bool,
+}
+
+impl AudioChannel {
+    pub(super) fn new() -> Self {
+        AudioChannel {
+            audc: 0,
+            audf: 0,
+            audv: 0,
+            divider: 0,
+            poly4: 0,
+            poly5: 0,
+            poly9: 0,
+            output: false,
+        }
+    }
+
+    fn frequency_divider(&self) -> u16 {
+        let divider = (self.audf & 0b1_1111) as u16 + 1;
+        match self.audc & 0b1100 {
+            0b1100 => divider * 3,
+            _ => divider,
+        }
+    }
+
+    /// Step the channel by one audio clock and return its current volume (0-15)
+    pub(super) fn clock(&mut self) -> u8 {
+        self.divider += 1;
+        if self.divider >= self.frequency_divider() {
+            self.divider = 0;
+            self.step();
+        }
+
+        match self.output {
+            true => self.audv & 0b1111,
+            false => 0,
+        }
+    }
+
+    fn step(&mut self) {
+        self.poly5 = (self.poly5 + 1) % POLY5.len();
+        let poly5 = POLY5[self.poly5];
+        let div31 = DIV31[self.poly5];
+
+        match self.audc & 0b1111 {
+            // Constant output, games use these to play samples by writing AUDV
+            0x0 | 0xB => self.output = true,
+            0x1 => self.step_poly4(),
+            0x2 if div31 => self.step_poly4(),
+            0x3 if poly5 => self.step_poly4(),
+            // Pure tones
+            0x4 | 0x5 | 0xC | 0xD => self.output = !self.output,
+            0x6 | 0xA | 0xE if div31 => self.output = !self.output,
+            0x7 | 0xF if poly5 => self.output = !self.output,
+            0x8 => {
+                self.poly9 = (self.poly9 + 1) % POLY9.len();
+                self.output = POLY9[self.poly9];
+            }
+            0x9 => self.output = poly5,
+            _ => {}
+        }
+    }
+
+    fn step_poly4(&mut self) {
+        self.poly4 = (self.poly4 + 1) % POLY4.len();
+        self.output = POLY4[self.poly4];
+    }
+}
+
+impl SaveState for AudioChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.audc);
+        writer.write_u8(self.audf);
+        writer.write_u8(self.audv);
+        writer.write_u16(self.divider);
+        writer.write_u32(self.poly4 as u32);
+        writer.write_u32(self.poly5 as u32);
+        writer.write_u32(self.poly9 as u32);
+        writer.write_bool(self.output);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.audc = reader.read_u8()?;
+        self.audf = reader.read_u8()?;
+        self.audv = reader.read_u8()?;
+        self.divider = reader.read_u16()?;
+        self.poly4 = reader.read_index(POLY4.len(), "poly4 position")?;
+        self.poly5 = reader.read_index(POLY5.len(), "poly5 position")?;
+        self.poly9 = reader.read_index(POLY9.len(), "poly9 position")?;
+        self.output = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+///
+/// Converts the ~31.4kHz TIA output to the host sample rate by linearly
+/// interpolating between consecutive TIA samples.
+///
+#[derive(Debug, Clone)]
+pub(super) struct Resampler {
+    output_rate: u32,
+    // Position of the next output sample between the previous and next input samples
+    position: f64,
+    previous: f32,
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub(super) fn new(output_rate: u32) -> Self {
+        Resampler {
+            output_rate,
+            position: 0.0,
+            previous: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub(super) fn set_output_rate(&mut self, output_rate: u32) {
+        self.output_rate = output_rate.max(1);
+        self.position = 0.0;
+        self.samples.clear();
+    }
+
+    pub(super) fn push(&mut self, sample: f32) {
+        let step = AUDIO_CLOCK_RATE / self.output_rate as f64;
+
+        while self.position < 1.0 {
+            let position = self.position as f32;
+            self.samples
+                .push(self.previous + (sample - self.previous) * position);
+            self.position += step;
+        }
+        self.position -= 1.0;
+        self.previous = sample;
+
+        let max_samples = self.output_rate as usize * MAX_BUFFERED_SECONDS;
+        if self.samples.len() > max_samples {
+            let excess = self.samples.len() - max_samples;
+            self.samples.drain(..excess);
+        }
+    }
+
+    pub(super) fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioChannel, Resampler, AUDIO_CLOCK_RATE, POLY4, POLY5, POLY9};
+
+    #[test]
+    fn test_poly_counters_are_maximal_length() {
+        // A maximal length n bit LFSR outputs 2^(n-1) ones per period
+        assert_eq!(POLY4.iter().filter(|&&bit| bit).count(), 8);
+        assert_eq!(POLY5.iter().filter(|&&bit| bit).count(), 16);
+        assert_eq!(POLY9.iter().filter(|&&bit| bit).count(), 256);
+    }
+
+    #[test]
+    fn test_pure_tone_divides_by_audf() {
+        let mut channel = AudioChannel::new();
+        channel.audc = 0x4;
+        channel.audf = 2;
+        channel.audv = 0xF;
+
+        let output: Vec<u8> = (0..12).map(|_| channel.clock()).collect();
+        assert_eq!(output, vec![0, 0, 15, 15, 15, 0, 0, 0, 15, 15, 15, 0]);
+    }
+
+    #[test]
+    fn test_div3_modes_are_slower() {
+        let mut channel = AudioChannel::new();
+        channel.audc = 0xC;
+        channel.audv = 0x8;
+
+        let output: Vec<u8> = (0..6).map(|_| channel.clock()).collect();
+        assert_eq!(output, vec![0, 0, 8, 8, 8, 0]);
+    }
+
+    #[test]
+    fn test_constant_output_follows_volume() {
+        let mut channel = AudioChannel::new();
+        channel.audv = 0x5;
+        assert_eq!(channel.clock(), 5);
+        channel.audv = 0x9;
+        assert_eq!(channel.clock(), 9);
+    }
+
+    #[test]
+    fn test_div31_tone_period() {
+        let mut channel = AudioChannel::new();
+        channel.audc = 0x6;
+        channel.audv = 0x1;
+
+        let output: Vec<u8> = (0..62).map(|_| channel.clock()).collect();
+        let high = output.iter().filter(|&&volume| volume == 1).count();
+        assert_eq!(high, 2 * 18);
+        assert_eq!(output[..31], output[31..]);
+    }
+
+    #[test]
+    fn test_resampler_produces_host_rate() {
+        let mut resampler = Resampler::new(48_000);
+        for _ in 0..AUDIO_CLOCK_RATE as usize {
+            resampler.push(0.5);
+        }
+        let samples = resampler.take();
+
+        assert!((samples.len() as i32 - 48_000).abs() <= 2);
+        assert!(samples[2..].iter().all(|&sample| sample == 0.5));
+        assert!(resampler.take().is_empty());
+    }
+}
@@ -0,0 +1,944 @@
+mod audio;
+mod objects;
+mod palette;
+
+use audio::{AudioChannel, Resampler, DEFAULT_SAMPLE_RATE};
+use log::info;
+use objects::{Ball, Missile, Player, VISIBLE_PIXELS};
+use palette::NTSC_PALETTE;
+use wasm_bindgen::prelude::*;
+
+use crate::save_state::{invalid_value, SaveState, StateReader, StateWriter};
+
+/// Colour clocks on each scanline, the first 68 of them are horizontal blank
+pub(crate) const COLOR_CLOCKS_PER_SCANLINE: u8 = 228;
+const HBLANK_COLOR_CLOCKS: u8 = 68;
+/// The audio circuits are clocked at these two points on each scanline
+const AUDIO_CLOCKS: [u8; 2] = [0, COLOR_CLOCKS_PER_SCANLINE / 2];
+/// Highest combined volume of both audio channels
+const MAX_AUDIO_VOLUME: f32 = 30.0;
+/// An HMOVE extends the horizontal blank by 8 clocks (the black "HMOVE comb")
+const EXTENDED_HBLANK_COLOR_CLOCKS: u8 = HBLANK_COLOR_CLOCKS + 8;
+
+pub(crate) const FRAME_WIDTH: usize = VISIBLE_PIXELS as usize;
+/// Enough scanlines for a PAL frame. Scanlines are counted from the start of VSYNC.
+pub(crate) const FRAME_HEIGHT: usize = 312;
+const BYTES_PER_PIXEL: usize = 4;
+
+// Bit masks identifying which objects are drawing on the current pixel
+const P0_BIT: usize = 0b00_0001;
+const P1_BIT: usize = 0b00_0010;
+const M0_BIT: usize = 0b00_0100;
+const M1_BIT: usize = 0b00_1000;
+const BL_BIT: usize = 0b01_0000;
+const PF_BIT: usize = 0b10_0000;
+
+///
+/// Collision latches are stored so that bit 2n is bit 7 of collision
+/// register n and bit 2n+1 is bit 6 of the same register.
+///
+const COLLISION_PAIRS: [(usize, usize); 15] = [
+    (M0_BIT, P1_BIT), // CXM0P
+    (M0_BIT, P0_BIT),
+    (M1_BIT, P0_BIT), // CXM1P
+    (M1_BIT, P1_BIT),
+    (P0_BIT, PF_BIT), // CXP0FB
+    (P0_BIT, BL_BIT),
+    (P1_BIT, PF_BIT), // CXP1FB
+    (P1_BIT, BL_BIT),
+    (M0_BIT, PF_BIT), // CXM0FB
+    (M0_BIT, BL_BIT),
+    (M1_BIT, PF_BIT), // CXM1FB
+    (M1_BIT, BL_BIT),
+    (BL_BIT, PF_BIT), // CXBLPF (bit 6 is unused)
+    (P0_BIT, P1_BIT), // CXPPMM
+    (M0_BIT, M1_BIT),
+];
+
+const fn build_collision_table() -> [u16; 64] {
+    let mut table = [0u16; 64];
+    let mut objects = 0;
+    while objects < 64 {
+        let mut pair = 0;
+        while pair < COLLISION_PAIRS.len() {
+            let (a, b) = COLLISION_PAIRS[pair];
+            // CXBLPF only has a bit 7 so everything after it moves along by one
+            let bit = if pair < 13 { pair } else { pair + 1 };
+            if objects & a != 0 && objects & b != 0 {
+                table[objects] |= 1 << bit;
+            }
+            pair += 1;
+        }
+        objects += 1;
+    }
+    table
+}
+
+/// Collision latches set by each combination of objects drawing on the same pixel
+const COLLISION_TABLE: [u16; 64] = build_collision_table();
+
+///
+/// The TIA generates the video signal one colour clock at a time. Each
+/// scanline is 228 colour clocks (76 cpu cycles), the first 68 of which are
+/// horizontal blank and the remaining 160 draw a pixel each.
+///
+#[wasm_bindgen]
+pub struct Tia {
+    color_clock: u8,
+    scanline: usize,
+    frame_number: u32,
+
+    vsync: bool,
+    vblank: bool,
+    // Set by WSYNC, the cpu is halted until the start of the next scanline
+    wsync: bool,
+    extended_hblank: bool,
+
+    players: [Player; 2],
+    missiles: [Missile; 2],
+    ball: Ball,
+
+    // Bit n is set if the nth 4 pixel block of the left half of the playfield is drawn
+    playfield: u32,
+    pf0: u8,
+    pf1: u8,
+    pf2: u8,
+    ctrlpf: u8,
+
+    colup0: u8,
+    colup1: u8,
+    colupf: u8,
+    colubk: u8,
+
+    movement_in_progress: bool,
+    movement_clock: u8,
+
+    collisions: u16,
+
+    // VBLANK bit 7 grounds the paddle capacitors, they charge from the moment it's cleared
+    pots_dumped: bool,
+    pot_charge_clocks: u32,
+    // Colour clocks each INPT0-3 capacitor takes to charge, None if it never will
+    pot_charge_times: [Option<u32>; 4],
+    // VBLANK bit 6 latches INPT4 & INPT5 low once they've been seen low
+    input_latches_enabled: bool,
+    fire_levels: [bool; 2],
+    fire_latched: [bool; 2],
+
+    audio_channels: [AudioChannel; 2],
+    resampler: Resampler,
+
+    // RGBA pixels, FRAME_WIDTH x FRAME_HEIGHT
+    frame_buffer: Box<[u8]>,
+}
+
+impl Default for Tia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tia {
+    fn hblank_end(&self) -> u8 {
+        match self.extended_hblank {
+            true => EXTENDED_HBLANK_COLOR_CLOCKS,
+            false => HBLANK_COLOR_CLOCKS,
+        }
+    }
+
+    fn in_hblank(&self) -> bool {
+        self.color_clock < self.hblank_end()
+    }
+
+    fn update_playfield(&mut self) {
+        // PF0 bits 4-7 then PF1 bits 7-0 then PF2 bits 0-7, left to right
+        self.playfield = (self.pf0 >> 4) as u32
+            | ((self.pf1.reverse_bits() as u32) << 4)
+            | ((self.pf2 as u32) << 12);
+    }
+
+    fn playfield_pixel(&self, x: u8) -> bool {
+        let block = x / 4;
+        let block = match (block < 20, self.ctrlpf & 0b1 != 0) {
+            (true, _) => block,
+            (false, true) => 39 - block,
+            (false, false) => block - 20,
+        };
+
+        self.playfield & (1 << block) != 0
+    }
+
+    fn tick_movement(&mut self) {
+        let hblank = self.in_hblank();
+        let clock = self.movement_clock;
+
+        let mut still_moving = false;
+        for player in self.players.iter_mut() {
+            still_moving |= player.position.motion_tick(clock, hblank);
+        }
+        for missile in self.missiles.iter_mut() {
+            still_moving |= missile.position.motion_tick(clock, hblank);
+        }
+        still_moving |= self.ball.position.motion_tick(clock, hblank);
+
+        self.movement_clock += 1;
+        self.movement_in_progress = still_moving && self.movement_clock < 16;
+    }
+
+    fn tick_objects(&mut self) {
+        for player in self.players.iter_mut() {
+            player.position.tick();
+        }
+        for missile in self.missiles.iter_mut() {
+            missile.position.tick();
+        }
+        self.ball.position.tick();
+    }
+
+    fn lock_missiles_to_players(&mut self) {
+        for ix in 0..2 {
+            if self.missiles[ix].locked_to_player {
+                let player = &self.players[ix];
+                let offset = player.centre_offset();
+                self.missiles[ix].position.value = match player.position.value >= offset {
+                    true => player.position.value - offset,
+                    false => player.position.value + (VISIBLE_PIXELS - offset),
+                };
+            }
+        }
+    }
+
+    fn render_pixel(&mut self, x: u8) {
+        let color = if self.vblank {
+            None
+        } else {
+            let p0 = self.players[0].pixel();
+            let p1 = self.players[1].pixel();
+            let m0 = self.missiles[0].pixel();
+            let m1 = self.missiles[1].pixel();
+            let bl = self.ball.pixel();
+            let pf = self.playfield_pixel(x);
+
+            let objects = (p0 as usize * P0_BIT)
+                | (p1 as usize * P1_BIT)
+                | (m0 as usize * M0_BIT)
+                | (m1 as usize * M1_BIT)
+                | (bl as usize * BL_BIT)
+                | (pf as usize * PF_BIT);
+            self.collisions |= COLLISION_TABLE[objects];
+
+            let priority = self.ctrlpf & 0b100 != 0;
+            // Score mode colours each half of the playfield like the player on that side
+            let pf_color = match (self.ctrlpf & 0b10 != 0 && !priority, x < 80) {
+                (true, true) => self.colup0,
+                (true, false) => self.colup1,
+                (false, _) => self.colupf,
+            };
+
+            let color = if priority && (pf || bl) {
+                if pf {
+                    pf_color
+                } else {
+                    self.colupf
+                }
+            } else if p0 || m0 {
+                self.colup0
+            } else if p1 || m1 {
+                self.colup1
+            } else if pf {
+                pf_color
+            } else if bl {
+                self.colupf
+            } else {
+                self.colubk
+            };
+
+            Some(color)
+        };
+
+        self.write_pixel(x, color);
+    }
+
+    fn write_pixel(&mut self, x: u8, color: Option<u8>) {
+        if self.scanline >= FRAME_HEIGHT {
+            return;
+        }
+
+        let rgb = match color {
+            Some(color) => NTSC_PALETTE[(color >> 1) as usize],
+            None => [0, 0, 0],
+        };
+        let ix = (self.scanline * FRAME_WIDTH + x as usize) * BYTES_PER_PIXEL;
+        self.frame_buffer[ix..ix + BYTES_PER_PIXEL]
+            .copy_from_slice(&[rgb[0], rgb[1], rgb[2], 0xFF]);
+    }
+
+    fn start_scanline(&mut self) {
+        self.color_clock = 0;
+        self.wsync = false;
+        self.extended_hblank = false;
+        self.scanline += 1;
+
+        // A rom which never strobes VSYNC still needs to produce frames
+        if self.scanline == FRAME_HEIGHT {
+            self.start_frame();
+        }
+    }
+
+    fn start_frame(&mut self) {
+        self.scanline = 0;
+        self.frame_number = self.frame_number.wrapping_add(1);
+    }
+
+    fn clock_audio(&mut self) {
+        let volume = self.audio_channels[0].clock() + self.audio_channels[1].clock();
+        self.resampler.push(volume as f32 / MAX_AUDIO_VOLUME);
+    }
+
+    /// Set the rate at which audio samples are produced for the host
+    pub(crate) fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler.set_output_rate(sample_rate);
+    }
+
+    /// Take all of the audio samples (in the range 0-1) produced since the last call
+    pub(crate) fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.resampler.take()
+    }
+
+    ///
+    /// Set the levels of the input pins as driven by the controllers, see
+    /// `InputState` for how these are derived.
+    ///
+    pub(crate) fn set_input_levels(
+        &mut self,
+        pot_charge_times: [Option<u32>; 4],
+        fire_levels: [bool; 2],
+    ) {
+        self.pot_charge_times = pot_charge_times;
+        self.fire_levels = fire_levels;
+        self.update_fire_latches();
+    }
+
+    fn update_fire_latches(&mut self) {
+        if self.input_latches_enabled {
+            for (latched, level) in self.fire_latched.iter_mut().zip(self.fire_levels) {
+                *latched |= !level;
+            }
+        }
+    }
+
+    fn read_pot(&self, pot: usize) -> u8 {
+        match (self.pots_dumped, self.pot_charge_times[pot]) {
+            (false, Some(charge_time)) if self.pot_charge_clocks >= charge_time => 0x80,
+            _ => 0x00,
+        }
+    }
+
+    fn read_fire_button(&self, button: usize) -> u8 {
+        match self.fire_levels[button] && !self.fire_latched[button] {
+            true => 0x80,
+            false => 0x00,
+        }
+    }
+
+    fn read_collision_register(&self, register: u8) -> u8 {
+        let latches = self.collisions >> (register * 2);
+        (((latches & 0b01) as u8) << 7) | (((latches & 0b10) as u8) << 5)
+    }
+}
+
+///
+/// The controller input levels aren't saved as they're reapplied by the
+/// system after loading, nor is the audio resampler which belongs to the host.
+///
+impl SaveState for Tia {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.color_clock);
+        writer.write_u32(self.scanline as u32);
+        writer.write_u32(self.frame_number);
+        writer.write_bool(self.vsync);
+        writer.write_bool(self.vblank);
+        writer.write_bool(self.wsync);
+        writer.write_bool(self.extended_hblank);
+        for player in self.players.iter() {
+            player.save_state(writer);
+        }
+        for missile in self.missiles.iter() {
+            missile.save_state(writer);
+        }
+        self.ball.save_state(writer);
+        writer.write_u8(self.pf0);
+        writer.write_u8(self.pf1);
+        writer.write_u8(self.pf2);
+        writer.write_u8(self.ctrlpf);
+        writer.write_u8(self.colup0);
+        writer.write_u8(self.colup1);
+        writer.write_u8(self.colupf);
+        writer.write_u8(self.colubk);
+        writer.write_bool(self.movement_in_progress);
+        writer.write_u8(self.movement_clock);
+        writer.write_u16(self.collisions);
+        writer.write_bool(self.pots_dumped);
+        writer.write_u32(self.pot_charge_clocks);
+        writer.write_bool(self.input_latches_enabled);
+        for latched in self.fire_latched {
+            writer.write_bool(latched);
+        }
+        for channel in self.audio_channels.iter() {
+            channel.save_state(writer);
+        }
+        writer.write_bytes(&self.frame_buffer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.color_clock = match reader.read_u8()? {
+            color_clock if color_clock < COLOR_CLOCKS_PER_SCANLINE => color_clock,
+            color_clock => return Err(invalid_value("colour clock", color_clock)),
+        };
+        self.scanline = reader.read_index(FRAME_HEIGHT, "scanline")?;
+        self.frame_number = reader.read_u32()?;
+        self.vsync = reader.read_bool()?;
+        self.vblank = reader.read_bool()?;
+        self.wsync = reader.read_bool()?;
+        self.extended_hblank = reader.read_bool()?;
+        for player in self.players.iter_mut() {
+            player.load_state(reader)?;
+        }
+        for missile in self.missiles.iter_mut() {
+            missile.load_state(reader)?;
+        }
+        self.ball.load_state(reader)?;
+        self.pf0 = reader.read_u8()?;
+        self.pf1 = reader.read_u8()?;
+        self.pf2 = reader.read_u8()?;
+        self.update_playfield();
+        self.ctrlpf = reader.read_u8()?;
+        self.colup0 = reader.read_u8()?;
+        self.colup1 = reader.read_u8()?;
+        self.colupf = reader.read_u8()?;
+        self.colubk = reader.read_u8()?;
+        self.movement_in_progress = reader.read_bool()?;
+        self.movement_clock = reader.read_u8()?;
+        self.collisions = reader.read_u16()?;
+        self.pots_dumped = reader.read_bool()?;
+        self.pot_charge_clocks = reader.read_u32()?;
+        self.input_latches_enabled = reader.read_bool()?;
+        for latched in self.fire_latched.iter_mut() {
+            *latched = reader.read_bool()?;
+        }
+        for channel in self.audio_channels.iter_mut() {
+            channel.load_state(reader)?;
+        }
+        reader.read_bytes_into(&mut self.frame_buffer)?;
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Tia {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Tia {
+        Tia {
+            color_clock: 0,
+            scanline: 0,
+            frame_number: 0,
+            vsync: false,
+            vblank: false,
+            wsync: false,
+            extended_hblank: false,
+            players: [Player::new(), Player::new()],
+            missiles: [Missile::new(), Missile::new()],
+            ball: Ball::new(),
+            playfield: 0,
+            pf0: 0,
+            pf1: 0,
+            pf2: 0,
+            ctrlpf: 0,
+            colup0: 0,
+            colup1: 0,
+            colupf: 0,
+            colubk: 0,
+            movement_in_progress: false,
+            movement_clock: 0,
+            collisions: 0,
+            pots_dumped: false,
+            pot_charge_clocks: 0,
+            pot_charge_times: [None; 4],
+            input_latches_enabled: false,
+            fire_levels: [true; 2],
+            fire_latched: [false; 2],
+            audio_channels: [AudioChannel::new(), AudioChannel::new()],
+            resampler: Resampler::new(DEFAULT_SAMPLE_RATE),
+            frame_buffer: vec![0; FRAME_WIDTH * FRAME_HEIGHT * BYTES_PER_PIXEL].into_boxed_slice(),
+        }
+    }
+
+    /// Move the TIA on by a single colour clock, drawing a pixel if the beam is visible
+    pub fn clock(&mut self) {
+        if self.movement_in_progress && self.color_clock & 0b11 == 0 {
+            self.tick_movement();
+        }
+
+        if self.color_clock >= self.hblank_end() {
+            self.render_pixel(self.color_clock - HBLANK_COLOR_CLOCKS);
+            self.tick_objects();
+        } else if self.color_clock >= HBLANK_COLOR_CLOCKS {
+            // The left edge of a line with an HMOVE is blanked
+            self.write_pixel(self.color_clock - HBLANK_COLOR_CLOCKS, None);
+        }
+
+        self.lock_missiles_to_players();
+
+        self.pot_charge_clocks = match self.pots_dumped {
+            true => 0,
+            false => self.pot_charge_clocks.saturating_add(1),
+        };
+
+        if AUDIO_CLOCKS.contains(&self.color_clock) {
+            self.clock_audio();
+        }
+
+        self.color_clock += 1;
+        if self.color_clock == COLOR_CLOCKS_PER_SCANLINE {
+            self.start_scanline();
+        }
+    }
+
+    /// Whether the TIA is holding the RDY line low (after WSYNC) so the cpu must not run
+    pub fn cpu_halted(&self) -> bool {
+        self.wsync
+    }
+
+    /// Scanlines since the start of VSYNC
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    /// Colour clocks since the start of the scanline, the first 68 are horizontal blank
+    pub fn color_clock(&self) -> u8 {
+        self.color_clock
+    }
+
+    /// Incremented every time VSYNC starts a new frame
+    pub fn frame_number(&self) -> u32 {
+        self.frame_number
+    }
+
+    /// Pointer to the RGBA frame buffer in wasm memory, FRAME_WIDTH * FRAME_HEIGHT pixels
+    pub fn frame_buffer_ptr(&self) -> *const u8 {
+        self.frame_buffer.as_ptr()
+    }
+
+    pub(crate) fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    pub fn frame_width(&self) -> usize {
+        FRAME_WIDTH
+    }
+
+    pub fn frame_height(&self) -> usize {
+        FRAME_HEIGHT
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+        // Only the bottom 4 bits of the address bus are decoded for reads
+        match address & 0x0F {
+            register @ 0x00..=0x07 => self.read_collision_register(register as u8),
+            register @ 0x08..=0x0B => self.read_pot(register as usize - 0x08), // INPT0-3
+            register @ 0x0C..=0x0D => self.read_fire_button(register as usize - 0x0C), // INPT4-5
+            _ => 0x00,
+        }
+    }
+
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        match address & 0x3F {
+            0x00 => {
+                // VSYNC
+                let vsync = value & 0b10 != 0;
+                if vsync && !self.vsync {
+                    self.start_frame();
+                }
+                self.vsync = vsync;
+            }
+            0x01 => {
+                // VBLANK
+                self.vblank = value & 0b10 != 0;
+                self.pots_dumped = value & 0b1000_0000 != 0;
+                self.input_latches_enabled = value & 0b0100_0000 != 0;
+                if !self.input_latches_enabled {
+                    self.fire_latched = [false; 2];
+                }
+                self.update_fire_latches();
+            }
+            0x02 => self.wsync = true, // WSYNC
+            0x03 => {
+                // RSYNC - resets the horizontal counter, ending the line a few clocks later
+                self.color_clock = COLOR_CLOCKS_PER_SCANLINE - 3;
+            }
+            0x04 => {
+                // NUSIZ0
+                self.players[0].nusiz = value;
+                self.missiles[0].nusiz = value;
+            }
+            0x05 => {
+                // NUSIZ1
+                self.players[1].nusiz = value;
+                self.missiles[1].nusiz = value;
+            }
+            0x06 => self.colup0 = value, // COLUP0
+            0x07 => self.colup1 = value, // COLUP1
+            0x08 => self.colupf = value, // COLUPF
+            0x09 => self.colubk = value, // COLUBK
+            0x0A => {
+                // CTRLPF
+                self.ctrlpf = value;
+                self.ball.size = 1 << ((value >> 4) & 0b11);
+            }
+            0x0B => self.players[0].reflect = value & 0b1000 != 0, // REFP0
+            0x0C => self.players[1].reflect = value & 0b1000 != 0, // REFP1
+            0x0D => {
+                // PF0
+                self.pf0 = value;
+                self.update_playfield();
+            }
+            0x0E => {
+                // PF1
+                self.pf1 = value;
+                self.update_playfield();
+            }
+            0x0F => {
+                // PF2
+                self.pf2 = value;
+                self.update_playfield();
+            }
+            0x10 => {
+                // RESP0
+                let hblank = self.in_hblank();
+                self.players[0].reset_position(hblank);
+            }
+            0x11 => {
+                // RESP1
+                let hblank = self.in_hblank();
+                self.players[1].reset_position(hblank);
+            }
+            0x12 => {
+                // RESM0
+                let hblank = self.in_hblank();
+                self.missiles[0].reset_position(hblank);
+            }
+            0x13 => {
+                // RESM1
+                let hblank = self.in_hblank();
+                self.missiles[1].reset_position(hblank);
+            }
+            0x14 => {
+                // RESBL
+                let hblank = self.in_hblank();
+                self.ball.reset_position(hblank);
+            }
+            0x15 => self.audio_channels[0].audc = value, // AUDC0
+            0x16 => self.audio_channels[1].audc = value, // AUDC1
+            0x17 => self.audio_channels[0].audf = value, // AUDF0
+            0x18 => self.audio_channels[1].audf = value, // AUDF1
+            0x19 => self.audio_channels[0].audv = value, // AUDV0
+            0x1A => self.audio_channels[1].audv = value, // AUDV1
+            0x1B => {
+                // GRP0
+                self.players[0].graphics = value;
+                self.players[1].old_graphics = self.players[1].graphics;
+            }
+            0x1C => {
+                // GRP1
+                self.players[1].graphics = value;
+                self.players[0].old_graphics = self.players[0].graphics;
+                self.ball.old_enabled = self.ball.enabled;
+            }
+            0x1D => self.missiles[0].enabled = value & 0b10 != 0, // ENAM0
+            0x1E => self.missiles[1].enabled = value & 0b10 != 0, // ENAM1
+            0x1F => self.ball.enabled = value & 0b10 != 0,        // ENABL
+            0x20 => self.players[0].position.set_motion(value),   // HMP0
+            0x21 => self.players[1].position.set_motion(value),   // HMP1
+            0x22 => self.missiles[0].position.set_motion(value),  // HMM0
+            0x23 => self.missiles[1].position.set_motion(value),  // HMM1
+            0x24 => self.ball.position.set_motion(value),         // HMBL
+            0x25 => self.players[0].vertical_delay = value & 0b1 != 0, // VDELP0
+            0x26 => self.players[1].vertical_delay = value & 0b1 != 0, // VDELP1
+            0x27 => self.ball.vertical_delay = value & 0b1 != 0,  // VDELBL
+            0x28 => self.missiles[0].locked_to_player = value & 0b10 != 0, // RESMP0
+            0x29 => self.missiles[1].locked_to_player = value & 0b10 != 0, // RESMP1
+            0x2A => {
+                // HMOVE
+                for player in self.players.iter_mut() {
+                    player.position.start_motion();
+                }
+                for missile in self.missiles.iter_mut() {
+                    missile.position.start_motion();
+                }
+                self.ball.position.start_motion();
+                self.movement_clock = 0;
+                self.movement_in_progress = true;
+                self.extended_hblank = true;
+            }
+            0x2B => {
+                // HMCLR
+                for player in self.players.iter_mut() {
+                    player.position.set_motion(0);
+                }
+                for missile in self.missiles.iter_mut() {
+                    missile.position.set_motion(0);
+                }
+                self.ball.position.set_motion(0);
+            }
+            0x2C => self.collisions = 0, // CXCLR
+            _ => info!("Write to undefined TIA address {:02X}", address & 0x3F),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tia, BYTES_PER_PIXEL, COLOR_CLOCKS_PER_SCANLINE, FRAME_WIDTH, NTSC_PALETTE};
+
+    const WHITE: u8 = 0x0E;
+    const RED: u8 = 0x44;
+    const BLUE: u8 = 0x84;
+
+    fn run_scanline(tia: &mut Tia) {
+        for _ in 0..COLOR_CLOCKS_PER_SCANLINE {
+            tia.clock();
+        }
+    }
+
+    fn run_color_clocks(tia: &mut Tia, clocks: u8) {
+        for _ in 0..clocks {
+            tia.clock();
+        }
+    }
+
+    fn pixel(tia: &Tia, scanline: usize, x: usize) -> [u8; 3] {
+        let ix = (scanline * FRAME_WIDTH + x) * BYTES_PER_PIXEL;
+        [
+            tia.frame_buffer[ix],
+            tia.frame_buffer[ix + 1],
+            tia.frame_buffer[ix + 2],
+        ]
+    }
+
+    fn rgb(color: u8) -> [u8; 3] {
+        NTSC_PALETTE[(color >> 1) as usize]
+    }
+
+    /// Returns the x coordinates drawn in the given colour on the given scanline
+    fn pixels_in_color(tia: &Tia, scanline: usize, color: u8) -> Vec<usize> {
+        (0..FRAME_WIDTH)
+            .filter(|&x| pixel(tia, scanline, x) == rgb(color))
+            .collect()
+    }
+
+    #[test]
+    fn test_playfield_repeated_and_reflected() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x08, WHITE); // COLUPF
+        tia.write_byte(0x0D, 0b0001_0000); // PF0 - leftmost block
+        tia.write_byte(0x0F, 0b1000_0000); // PF2 - rightmost block of the left half
+
+        run_scanline(&mut tia);
+        assert_eq!(
+            pixels_in_color(&tia, 0, WHITE),
+            vec![0, 1, 2, 3, 76, 77, 78, 79, 80, 81, 82, 83, 156, 157, 158, 159]
+        );
+
+        tia.write_byte(0x0A, 0b1); // CTRLPF - reflect
+        run_scanline(&mut tia);
+        assert_eq!(
+            pixels_in_color(&tia, 1, WHITE),
+            vec![0, 1, 2, 3, 76, 77, 78, 79, 80, 81, 82, 83, 156, 157, 158, 159]
+        );
+
+        tia.write_byte(0x0D, 0b0010_0000); // PF0 - second block only
+        tia.write_byte(0x0F, 0);
+        run_scanline(&mut tia);
+        assert_eq!(
+            pixels_in_color(&tia, 2, WHITE),
+            vec![4, 5, 6, 7, 152, 153, 154, 155]
+        );
+    }
+
+    #[test]
+    fn test_wsync_halts_cpu_until_next_scanline() {
+        let mut tia = Tia::new();
+        run_color_clocks(&mut tia, 100);
+        tia.write_byte(0x02, 0);
+        assert!(tia.cpu_halted());
+
+        run_color_clocks(&mut tia, COLOR_CLOCKS_PER_SCANLINE - 101);
+        assert!(tia.cpu_halted());
+        tia.clock();
+        assert!(!tia.cpu_halted());
+        assert_eq!(tia.color_clock, 0);
+    }
+
+    #[test]
+    fn test_player_position_and_copies() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x06, RED); // COLUP0
+        tia.write_byte(0x1B, 0b1000_0001); // GRP0
+        tia.write_byte(0x10, 0); // RESP0 during HBLANK
+
+        run_scanline(&mut tia);
+        assert_eq!(pixels_in_color(&tia, 0, RED), vec![3, 10]);
+
+        tia.write_byte(0x04, 0b011); // NUSIZ0 - three copies close
+        run_scanline(&mut tia);
+        assert_eq!(pixels_in_color(&tia, 1, RED), vec![3, 10, 19, 26, 35, 42]);
+
+        tia.write_byte(0x04, 0b101); // NUSIZ0 - double width
+        tia.write_byte(0x0B, 0b1000); // REFP0
+        tia.write_byte(0x1B, 0b0000_0001); // GRP0
+        run_scanline(&mut tia);
+        assert_eq!(pixels_in_color(&tia, 2, RED), vec![4, 5]);
+    }
+
+    #[test]
+    fn test_resp_during_visible_scanline() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x06, RED); // COLUP0
+        tia.write_byte(0x1B, 0b1000_0000); // GRP0
+
+        // Strobe RESP0 as pixel 50 is about to be drawn
+        run_color_clocks(&mut tia, 68 + 50);
+        tia.write_byte(0x10, 0);
+        run_color_clocks(&mut tia, COLOR_CLOCKS_PER_SCANLINE - 68 - 50);
+        run_scanline(&mut tia);
+
+        assert_eq!(pixels_in_color(&tia, 1, RED), vec![55]);
+    }
+
+    #[test]
+    fn test_hmove_moves_objects() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x06, RED); // COLUP0
+        tia.write_byte(0x08, BLUE); // COLUPF
+        tia.write_byte(0x1B, 0b1000_0000); // GRP0
+        tia.write_byte(0x1F, 0b10); // ENABL
+        run_color_clocks(&mut tia, 68 + 40);
+        tia.write_byte(0x10, 0); // RESP0
+        tia.write_byte(0x14, 0); // RESBL
+        run_color_clocks(&mut tia, COLOR_CLOCKS_PER_SCANLINE - 68 - 40);
+
+        // Player 0 moves left by 3, the ball right by 8
+        tia.write_byte(0x20, 0x30); // HMP0
+        tia.write_byte(0x24, 0x80); // HMBL
+        run_color_clocks(&mut tia, 9);
+        tia.write_byte(0x2A, 0); // HMOVE
+        run_color_clocks(&mut tia, COLOR_CLOCKS_PER_SCANLINE - 9);
+
+        assert_eq!(pixels_in_color(&tia, 1, RED), vec![42]);
+        assert_eq!(pixels_in_color(&tia, 1, BLUE), vec![52]);
+        // The HMOVE comb blanks the first 8 pixels
+        assert_eq!(pixel(&tia, 1, 7), [0, 0, 0]);
+
+        // HMCLR stops any further movement on subsequent HMOVEs
+        tia.write_byte(0x2B, 0); // HMCLR
+        run_color_clocks(&mut tia, 9);
+        tia.write_byte(0x2A, 0); // HMOVE
+        run_color_clocks(&mut tia, COLOR_CLOCKS_PER_SCANLINE - 9);
+        assert_eq!(pixels_in_color(&tia, 2, RED), vec![42]);
+        assert_eq!(pixels_in_color(&tia, 2, BLUE), vec![52]);
+    }
+
+    #[test]
+    fn test_vertical_delay() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x06, RED); // COLUP0
+        tia.write_byte(0x25, 0b1); // VDELP0
+        tia.write_byte(0x10, 0); // RESP0
+        tia.write_byte(0x1B, 0xFF); // GRP0
+
+        run_scanline(&mut tia);
+        assert!(pixels_in_color(&tia, 0, RED).is_empty());
+
+        // Writing GRP1 latches the new GRP0 value into the old register
+        tia.write_byte(0x1C, 0x00);
+        run_scanline(&mut tia);
+        assert_eq!(pixels_in_color(&tia, 1, RED).len(), 8);
+    }
+
+    #[test]
+    fn test_collisions_latch_until_cleared() {
+        let mut tia = Tia::new();
+        tia.write_byte(0x0D, 0xF0); // PF0
+        tia.write_byte(0x1B, 0xFF); // GRP0
+        tia.write_byte(0x10, 0); // RESP0
+        tia.write_byte(0x14, 0); // RESBL
+        tia.write_byte(0x1F, 0b10); // ENABL
+        tia.write_byte(0x0A, 0x30); // CTRLPF - 8 pixel wide ball
+
+        run_scanline(&mut tia);
+        assert_eq!(tia.read_byte(0x02), 0b1100_0000); // CXP0FB
+        assert_eq!(tia.read_byte(0x06), 0b1000_0000); // CXBLPF
+        assert_eq!(tia.read_byte(0x07), 0); // CXPPMM
+
+        tia.write_byte(0x2C, 0); // CXCLR
+        assert_eq!(tia.read_byte(0x02), 0);
+        assert_eq!(tia.read_byte(0x06), 0);
+    }
+
+    #[test]
+    fn test_vsync_starts_new_frame() {
+        let mut tia = Tia::new();
+        run_scanline(&mut tia);
+        run_scanline(&mut tia);
+        assert_eq!(tia.scanline, 2);
+
+        tia.write_byte(0x00, 0b10);
+        assert_eq!(tia.scanline, 0);
+        assert_eq!(tia.frame_number(), 1);
+
+        // Holding VSYNC doesn't keep starting new frames
+        run_scanline(&mut tia);
+        tia.write_byte(0x00, 0b10);
+        assert_eq!(tia.frame_number(), 1);
+    }
+
+    #[test]
+    fn test_paddle_capacitors_charge_after_dump() {
+        let mut tia = Tia::new();
+        tia.set_input_levels([Some(100), None, Some(0), None], [true, true]);
+        tia.write_byte(0x01, 0x80); // VBLANK - dump pots
+        run_color_clocks(&mut tia, 200);
+        assert_eq!(tia.read_byte(0x08), 0x00);
+        assert_eq!(tia.read_byte(0x0A), 0x00);
+
+        tia.write_byte(0x01, 0x00);
+        run_color_clocks(&mut tia, 99);
+        assert_eq!(tia.read_byte(0x08), 0x00);
+        assert_eq!(tia.read_byte(0x0A), 0x80);
+        tia.clock();
+        assert_eq!(tia.read_byte(0x08), 0x80);
+        // Nothing ever charges INPT1
+        run_color_clocks(&mut tia, 200);
+        assert_eq!(tia.read_byte(0x09), 0x00);
+    }
+
+    #[test]
+    fn test_fire_button_latches() {
+        let mut tia = Tia::new();
+        assert_eq!(tia.read_byte(0x0C), 0x80);
+        tia.set_input_levels([None; 4], [false, true]);
+        assert_eq!(tia.read_byte(0x0C), 0x00);
+        tia.set_input_levels([None; 4], [true, true]);
+        assert_eq!(tia.read_byte(0x0C), 0x80);
+
+        tia.write_byte(0x01, 0x40); // VBLANK - enable latches
+        tia.set_input_levels([None; 4], [true, false]);
+        tia.set_input_levels([None; 4], [true, true]);
+        assert_eq!(tia.read_byte(0x0C), 0x80);
+        assert_eq!(tia.read_byte(0x0D), 0x00);
+
+        tia.write_byte(0x01, 0x00);
+        assert_eq!(tia.read_byte(0x0D), 0x80);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::save_state::{invalid_value, SaveState, StateReader, StateWriter};
+
+/// Number of visible pixels on a scanline, the object position counters wrap here
+pub(super) const VISIBLE_PIXELS: u8 = 160;
+
+/// Counter values loaded by RESPx. Players appear 5 pixels after the strobe,
+/// or at pixel 3 if the strobe happens during HBLANK.
+const PLAYER_RESET_COUNTER: u8 = VISIBLE_PIXELS - 5;
+const PLAYER_RESET_COUNTER_HBLANK: u8 = VISIBLE_PIXELS - 3;
+
+/// Counter values loaded by RESMx & RESBL. Missiles and the ball appear 4
+/// pixels after the strobe, or at pixel 2 if the strobe happens during HBLANK.
+const MISSILE_RESET_COUNTER: u8 = VISIBLE_PIXELS - 4;
+const MISSILE_RESET_COUNTER_HBLANK: u8 = VISIBLE_PIXELS - 2;
+
+///
+/// Every movable object has its own position counter which is clocked once
+/// per visible colour clock, an object draws when its counter is close to 0.
+///
+/// HMOVE works by sending extra clocks to the counter during HBLANK (when it
+/// wouldn't otherwise be clocked). Each object receives (HMxx >> 4) ^ 8 extra
+/// clocks, one every 4 colour clocks, which combined with the 8 clock HBLANK
+/// extension moves the object by the signed value in HMxx.
+///
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PositionCounter {
+    pub(super) value: u8,
+    motion_clocks: u8,
+    moving: bool,
+}
+
+impl PositionCounter {
+    fn new() -> Self {
+        PositionCounter {
+            value: 0,
+            motion_clocks: 0x08,
+            moving: false,
+        }
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.value += 1;
+        if self.value == VISIBLE_PIXELS {
+            self.value = 0;
+        }
+    }
+
+    pub(super) fn set_motion(&mut self, hmxx: u8) {
+        self.motion_clocks = (hmxx >> 4) ^ 0x08;
+    }
+
+    pub(super) fn start_motion(&mut self) {
+        self.moving = true;
+    }
+
+    ///
+    /// Called every 4 colour clocks while an HMOVE is in progress, returns
+    /// whether this object is still waiting for more extra clocks.
+    ///
+    pub(super) fn motion_tick(&mut self, movement_clock: u8, hblank: bool) -> bool {
+        if movement_clock == self.motion_clocks {
+            self.moving = false;
+        }
+
+        // Outside of HBLANK the counter is already being clocked so the extra
+        // pulse is swallowed
+        if self.moving && hblank {
+            self.tick();
+        }
+
+        self.moving
+    }
+
+    /// How many pixels the counter is past the given start position
+    fn offset_from(&self, start: u8) -> u8 {
+        match self.value >= start {
+            true => self.value - start,
+            false => self.value + (VISIBLE_PIXELS - start),
+        }
+    }
+}
+
+/// Start offsets of each copy of a player or missile for the bottom 3 bits of NUSIZx
+fn copy_offsets(nusiz: u8) -> &'static [u8] {
+    match nusiz & 0b111 {
+        0b001 => &[0, 16],
+        0b010 => &[0, 32],
+        0b011 => &[0, 16, 32],
+        0b100 => &[0, 64],
+        0b110 => &[0, 32, 64],
+        _ => &[0],
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Player {
+    pub(super) position: PositionCounter,
+    pub(super) nusiz: u8,
+    pub(super) reflect: bool,
+    pub(super) vertical_delay: bool,
+    pub(super) graphics: u8,
+    // The graphics register is copied here whenever the other player's GRPx is written
+    pub(super) old_graphics: u8,
+}
+
+impl Player {
+    pub(super) fn new() -> Self {
+        Player {
+            position: PositionCounter::new(),
+            nusiz: 0,
+            reflect: false,
+            vertical_delay: false,
+            graphics: 0,
+            old_graphics: 0,
+        }
+    }
+
+    pub(super) fn reset_position(&mut self, hblank: bool) {
+        self.position.value = match hblank {
+            true => PLAYER_RESET_COUNTER_HBLANK,
+            false => PLAYER_RESET_COUNTER,
+        };
+    }
+
+    /// Width in pixels of each bit of the graphics register
+    fn scale(&self) -> u8 {
+        match self.nusiz & 0b111 {
+            0b101 => 2,
+            0b111 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Offset into the player that the missile is placed at by RESMPx
+    pub(super) fn centre_offset(&self) -> u8 {
+        match self.scale() {
+            2 => 6,
+            4 => 10,
+            _ => 3,
+        }
+    }
+
+    pub(super) fn pixel(&self) -> bool {
+        let graphics = match self.vertical_delay {
+            true => self.old_graphics,
+            false => self.graphics,
+        };
+        if graphics == 0 {
+            return false;
+        }
+
+        let scale = self.scale();
+        // Stretched players start a pixel later than single width ones
+        let delay = if scale > 1 { 1 } else { 0 };
+
+        copy_offsets(self.nusiz).iter().any(|&start| {
+            let offset = self.position.offset_from(start).wrapping_sub(delay);
+
+            if offset < 8 * scale {
+                let bit = offset / scale;
+                let mask = match self.reflect {
+                    true => 0b0000_0001 << bit,
+                    false => 0b1000_0000 >> bit,
+                };
+                graphics & mask != 0
+            } else {
+                false
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Missile {
+    pub(super) position: PositionCounter,
+    pub(super) nusiz: u8,
+    pub(super) enabled: bool,
+    pub(super) locked_to_player: bool,
+}
+
+impl Missile {
+    pub(super) fn new() -> Self {
+        Missile {
+            position: PositionCounter::new(),
+            nusiz: 0,
+            enabled: false,
+            locked_to_player: false,
+        }
+    }
+
+    pub(super) fn reset_position(&mut self, hblank: bool) {
+        self.position.value = match hblank {
+            true => MISSILE_RESET_COUNTER_HBLANK,
+            false => MISSILE_RESET_COUNTER,
+        };
+    }
+
+    pub(super) fn pixel(&self) -> bool {
+        if !self.enabled || self.locked_to_player {
+            return false;
+        }
+
+        let size = 1 << ((self.nusiz >> 4) & 0b11);
+
+        copy_offsets(self.nusiz)
+            .iter()
+            .any(|&start| self.position.offset_from(start) < size)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Ball {
+    pub(super) position: PositionCounter,
+    pub(super) size: u8,
+    pub(super) vertical_delay: bool,
+    pub(super) enabled: bool,
+    // ENABL is copied here whenever GRP1 is written
+    pub(super) old_enabled: bool,
+}
+
+impl Ball {
+    pub(super) fn new() -> Self {
+        Ball {
+            position: PositionCounter::new(),
+            size: 1,
+            vertical_delay: false,
+            enabled: false,
+            old_enabled: false,
+        }
+    }
+
+    pub(super) fn reset_position(&mut self, hblank: bool) {
+        self.position.value = match hblank {
+            true => MISSILE_RESET_COUNTER_HBLANK,
+            false => MISSILE_RESET_COUNTER,
+        };
+    }
+
+    pub(super) fn pixel(&self) -> bool {
+        let enabled = match self.vertical_delay {
+            true => self.old_enabled,
+            false => self.enabled,
+        };
+
+        enabled && self.position.offset_from(0) < self.size
+    }
+}
+
+impl SaveState for PositionCounter {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.value);
+        writer.write_u8(self.motion_clocks);
+        writer.write_bool(self.moving);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.value = match reader.read_u8()? {
+            value if value < VISIBLE_PIXELS => value,
+            value => return Err(invalid_value("object position", value)),
+        };
+        self.motion_clocks = reader.read_u8()?;
+        self.moving = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+impl SaveState for Player {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.position.save_state(writer);
+        writer.write_u8(self.nusiz);
+        writer.write_bool(self.reflect);
+        writer.write_bool(self.vertical_delay);
+        writer.write_u8(self.graphics);
+        writer.write_u8(self.old_graphics);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.position.load_state(reader)?;
+        self.nusiz = reader.read_u8()?;
+        self.reflect = reader.read_bool()?;
+        self.vertical_delay = reader.read_bool()?;
+        self.graphics = reader.read_u8()?;
+        self.old_graphics = reader.read_u8()?;
+
+        Ok(())
+    }
+}
+
+impl SaveState for Missile {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.position.save_state(writer);
+        writer.write_u8(self.nusiz);
+        writer.write_bool(self.enabled);
+        writer.write_bool(self.locked_to_player);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.position.load_state(reader)?;
+        self.nusiz = reader.read_u8()?;
+        self.enabled = reader.read_bool()?;
+        self.locked_to_player = reader.read_bool()?;
+
+        Ok(())
+    }
+}
+
+impl SaveState for Ball {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.position.save_state(writer);
+        writer.write_u8(self.size);
+        writer.write_bool(self.vertical_delay);
+        writer.write_bool(self.enabled);
+        writer.write_bool(self.old_enabled);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.position.load_state(reader)?;
+        self.size = reader.read_u8()?;
+        self.vertical_delay = reader.read_bool()?;
+        self.enabled = reader.read_bool()?;
+        self.old_enabled = reader.read_bool()?;
+
+        Ok(())
+    }
+}
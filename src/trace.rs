@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::disasm::Line;
+use crate::cpu::{Cpu, Register};
+
+#[wasm_bindgen]
+extern "C" {
+    /// Any JS object with a `trace_line(line)` method, used to stream trace lines as they're produced
+    pub type TraceSink;
+
+    #[wasm_bindgen(structural, method)]
+    pub fn trace_line(this: &TraceSink, line: &str);
+}
+
+/// Column the registers start at, the same as nestest.log so the two line up
+const REGISTERS_COLUMN: usize = 48;
+
+///
+/// Format the trace line for the instruction about to be executed, e.g.
+///
+/// `F000  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD TIA:  0, 68 CYC:7`
+///
+/// This is the nestest.log layout with the PPU position replaced by the TIA
+/// scanline & colour clock, which is left out when there's no TIA.
+///
+pub(crate) fn format_line(
+    instruction: &Line,
+    cpu: &Cpu,
+    tia_position: Option<(usize, u8)>,
+) -> String {
+    let mut line = format!(
+        "{:<width$}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+        instruction.to_string(),
+        cpu.register(Register::A),
+        cpu.register(Register::X),
+        cpu.register(Register::Y),
+        // Bit 5 always reads as set, as it does when PHP pushes P
+        cpu.register(Register::Status) | 0x20,
+        cpu.register(Register::StackPointer),
+        width = REGISTERS_COLUMN
+    );
+    if let Some((scanline, color_clock)) = tia_position {
+        line.push_str(&format!(" TIA:{:>3},{:>3}", scanline, color_clock));
+    }
+    line.push_str(&format!(" CYC:{}", cpu.cycles));
+
+    line
+}
+
+///
+/// Collects trace lines, either keeping the most recent in a bounded buffer
+/// or handing each one to a callback as soon as it's produced.
+///
+pub(crate) enum Tracer {
+    Buffer {
+        lines: VecDeque<String>,
+        capacity: usize,
+    },
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+impl Tracer {
+    /// Keep the last `capacity` lines, older lines are dropped
+    pub(crate) fn buffered(capacity: usize) -> Self {
+        Tracer::Buffer {
+            lines: VecDeque::with_capacity(capacity.min(0x10000)),
+            capacity,
+        }
+    }
+
+    pub(crate) fn streaming(callback: Box<dyn FnMut(&str)>) -> Self {
+        Tracer::Callback(callback)
+    }
+
+    pub(crate) fn trace(&mut self, line: String) {
+        match self {
+            Tracer::Buffer { lines, capacity } => {
+                if *capacity == 0 {
+                    return;
+                }
+                if lines.len() == *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+            Tracer::Callback(callback) => callback(&line),
+        }
+    }
+
+    /// Remove and return the buffered lines, oldest first
+    pub(crate) fn take_lines(&mut self) -> Vec<String> {
+        match self {
+            Tracer::Buffer { lines, .. } => lines.drain(..).collect(),
+            Tracer::Callback(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tracer;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_buffer_keeps_most_recent_lines() {
+        let mut tracer = Tracer::buffered(2);
+        for line in ["a", "b", "c"] {
+            tracer.trace(line.to_string());
+        }
+
+        assert_eq!(tracer.take_lines(), vec!["b", "c"]);
+        assert!(tracer.take_lines().is_empty());
+    }
+
+    #[test]
+    fn test_callback_receives_every_line() {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let sink = received.clone();
+        let mut tracer = Tracer::streaming(Box::new(move |line| {
+            sink.borrow_mut().push(line.to_string())
+        }));
+        tracer.trace("a".to_string());
+        tracer.trace("b".to_string());
+
+        assert_eq!(*received.borrow(), vec!["a", "b"]);
+        assert!(tracer.take_lines().is_empty());
+    }
+}
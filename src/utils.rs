@@ -8,3 +8,10 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+pub fn init_logging() {
+    // The logger writes to the browser console so there's nowhere for it to
+    // go when running natively (e.g. under cargo test)
+    #[cfg(target_arch = "wasm32")]
+    wasm_logger::init(wasm_logger::Config::default());
+}
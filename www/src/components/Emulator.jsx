@@ -26,6 +26,19 @@ function Emulator({ rom }) {
     canvasContext.drawImage(backingCanvas, 0, 0, 160, 192, 0, 0, 320, 384);
   };
 
+  // Holding backspace plays the game backwards
+  const REWIND_KEY = 'Backspace';
+  window.addEventListener('keydown', (event) => {
+    if (event.key === REWIND_KEY) {
+      atari2600.setRewinding(true);
+    }
+  });
+  window.addEventListener('keyup', (event) => {
+    if (event.key === REWIND_KEY) {
+      atari2600.setRewinding(false);
+    }
+  });
+
   atari2600.run(drawFrame);
 
   return (
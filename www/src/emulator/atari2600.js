@@ -1,28 +1,85 @@
 import * as wasm from 'mos-6502-cpu';
-import TIA from './tia';
-import RIOT from './riot';
+import { memory } from 'mos-6502-cpu/atari_2600_rust_web_assembly_bg.wasm';
+
+// The TIA frame buffer starts at VSYNC, skip VSYNC & VBLANK to get to the visible picture
+const FIRST_VISIBLE_SCANLINE = 40;
+const VISIBLE_SCANLINES = 192;
 
 class Atari2600 {
-  constructor(rom) {
+  // cartridgeType optionally overrides bank switching detection, e.g. 'F8SC'
+  constructor(rom, cartridgeType = null) {
     this.rom = rom;
+    this.cartridgeType = cartridgeType;
     this.restart();
   }
 
   restart = () => {
     this.paused = false;
-    this.tia = new TIA();
-    this.riot = new RIOT();
+    this.rewinding = false;
+    if (this.system) {
+      this.system.free();
+    }
+    this.system = this.cartridgeType === null
+      ? new wasm.Atari2600(new Uint8Array(this.rom))
+      : wasm.Atari2600.new_with_cartridge_type(new Uint8Array(this.rom), this.cartridgeType);
     this.runTimeoutId = 0;
     this.lastFrameTimes = [];
     this.lastFrameTimePtr = 0;
     this.currentFps = 60;
-    this.cyclesPerFrame = 59736; // This is raw oscillator cycles (i.e. 3 of these per cpu cycle but 1 per pixel)
     this.drawCallback = null;
+  };
+
+  setAudioSampleRate = (sampleRate) => {
+    this.system.set_audio_sample_rate(sampleRate);
+  };
+
+  // state is a wasm.InputState describing the controllers and console switches
+  setInputState = (state) => {
+    this.system.set_input_state(state);
+  };
+
+  // Returns a Uint8Array snapshot of the whole machine
+  saveState = () => this.system.save_state();
+
+  // Throws if the snapshot was taken with a different rom or emulator version
+  loadState = (state) => {
+    this.system.load_state(state);
+  };
+
+  // While rewinding each frame steps back to the previous snapshot instead of running
+  setRewinding = (rewinding) => {
+    this.rewinding = rewinding;
+  };
 
-    // Must come last as immediately makes calls back to this class to read instructions
-    this.cpu = wasm.new_cpu(8, this);
+  // Log each instruction executed, keeping the last `capacity` lines for takeTrace
+  startTrace = (capacity) => {
+    this.system.start_trace(capacity);
   };
 
+  stopTrace = () => {
+    this.system.stop_trace();
+  };
+
+  // Returns the buffered trace as a string with one instruction per line
+  takeTrace = () => this.system.take_trace();
+
+  // callback(cycle, address, value, kind) is called for every cpu bus cycle, kind is a wasm.BusAccessKind
+  startBusObserver = (callback) => {
+    this.system.start_bus_observer({ on_bus_access: callback });
+  };
+
+  stopBusObserver = () => {
+    this.system.stop_bus_observer();
+  };
+
+  // Run whole cpu instructions at once, frames can then end a few colour clocks late
+  setFastMode = (enabled) => {
+    this.system.set_fast_mode(enabled);
+  };
+
+  // Returns a wasm.RegisterSnapshot with a, x, y, stack_pointer, program_counter & status
+  registers = () => this.system.registers();
+
   pause = () => {
     this.paused = true;
   };
@@ -35,16 +92,25 @@ class Atari2600 {
   runFrame = () => {
     const currentTimeMs = Date.now();
 
-    for (let clock = 0; clock < this.cyclesPerFrame; clock += 1) {
-      // CPU clocks at 1/3 the speed of the overall clock
-      if ((clock & 0b11) === 0b11 && !this.tia.sendingRdySignalToCpu) {
-        wasm.clock(this.cpu, this);
-      }
+    if (this.rewinding) {
+      this.system.rewind(1);
+    } else {
+      this.system.run_frame();
+    }
+
+    this.drawCallback(this.frameBuffer());
 
-      this.tia.clock();
+    // Always drain the audio samples so they don't build up when nothing is playing them
+    const audioSamples = this.system.take_audio_samples();
+    if (this.audioCallback) {
+      this.audioCallback(audioSamples);
     }
 
-    this.drawCallback(this.tia.frameBuffer);
+    if (this.system.is_jammed()) {
+      const pc = this.system.registers().program_counter;
+      console.error(`CPU jammed by a KIL opcode, PC $${pc.toString(16).toUpperCase().padStart(4, '0')}, restart to recover`);
+      this.paused = true;
+    }
 
     const frameTime = Date.now() - currentTimeMs;
     this.lastFrameTimes[this.lastFrameTimePtr] = frameTime;
@@ -57,55 +123,19 @@ class Atari2600 {
     }
   };
 
-  run = (drawCallback) => {
-    this.drawCallback = drawCallback;
-    this.runTimeoutId = setTimeout(this.runFrame, 0);
-  };
-
-  read_byte = (address) => {
-    const a12 = (address & 0b0001_0000_0000_0000) !== 0;
-    const a9 = (address & 0b0000_0010_0000_0000) !== 0;
-    const a7 = (address & 0b0000_0000_1000_0000) !== 0;
-
-    if (a12) {
-      return this.rom[address & 0xFFF];
-    }
-
-    if (!a7) {
-      return this.tia.read_byte(address & 0xF);
-    }
-
-    if (!a9) {
-      return this.riot.ram[address & 0x7F];
-    }
+  frameBuffer = () => {
+    const width = this.system.frame_width();
+    const start = this.system.frame_buffer_ptr() + FIRST_VISIBLE_SCANLINE * width * 4;
+    const pixels = new Uint8ClampedArray(memory.buffer, start, width * VISIBLE_SCANLINES * 4);
 
-    return this.riot.read_byte(address & 0x2ff);
+    return new ImageData(pixels, width, VISIBLE_SCANLINES);
   };
 
-  write_byte = (address, value) => {
-    const a12 = (address & 0b0001_0000_0000_0000) !== 0;
-    const a9 = (address & 0b0000_0010_0000_0000) !== 0;
-    const a7 = (address & 0b0000_0000_1000_0000) !== 0;
-
-    if (a12) {
-      // TODO - Handle bank switching on rom write
-      return;
-    }
-
-    if (!a7) {
-      this.tia.write_byte(address & 0x3f, value);
-    }
-
-    if (!a9) {
-      this.riot.ram[address & 0x7F] = value & 0xFF;
-    }
-
-    this.riot.write_byte(address & 0x2FF, value);
-  };
-
-  // eslint-disable-next-line class-methods-use-this, no-unused-vars
-  poll_for_interrupts = (_) => {
-    // console.log('Polling for interrupts');
+  // audioCallback receives a Float32Array of mono samples at the rate set by setAudioSampleRate
+  run = (drawCallback, audioCallback = null) => {
+    this.drawCallback = drawCallback;
+    this.audioCallback = audioCallback;
+    this.runTimeoutId = setTimeout(this.runFrame, 0);
   };
 }
 